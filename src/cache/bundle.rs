@@ -0,0 +1,410 @@
+//! Cache bundles - Portable archives of cached LLM results, to share a warm cache across a team or CI jobs
+//!
+//! A bundle is a gzip-compressed JSON lines file: a manifest line, then one line per entry holding the
+//! stored entry verbatim together with its MD5 checksum.
+
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Utc};
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use md5::{Digest, Md5};
+use serde::{Deserialize, Serialize};
+
+use super::CacheEntry;
+use super::store::{open_store, validate_key};
+use crate::config::{Config, LLMConfig};
+use crate::generator::checkpoint::RunCheckpoint;
+use crate::generator::prompt_templates::PromptTemplates;
+
+const BUNDLE_FORMAT: &str = "litho-cache-bundle";
+const BUNDLE_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BundleManifest {
+    format: String,
+    version: u32,
+    created_at: DateTime<Utc>,
+    litho_version: String,
+    /// Run the entries were selected from, None if the bundle holds the whole cache
+    since_run: Option<String>,
+    entries: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BundleEntry {
+    category: String,
+    hash: String,
+    /// MD5 of `payload`
+    checksum: String,
+    /// The entry as stored in the cache
+    payload: String,
+}
+
+/// Write the cached LLM results to a bundle. With `since`, only the entries a run read or wrote, or for runs
+/// that did not record them, the entries written since the run started
+pub async fn export(config: &Config, output: &Path, since: Option<&str>, category: Option<&str>) -> Result<()> {
+    let since_run = since.map(|run| find_run(config, run)).transpose()?;
+    let run_entries = match &since_run {
+        Some((run_id, _)) => RunCheckpoint::cache_entries(config, run_id)?,
+        None => None,
+    };
+    let store = open_store(&config.cache)?;
+
+    let mut entries = Vec::new();
+    for key in store.list().await? {
+        if category.is_some_and(|category| key.category != category) {
+            continue;
+        }
+        let Some(bytes) = store.peek(&key.category, &key.hash).await? else {
+            continue;
+        };
+        let payload = String::from_utf8(bytes)?;
+        let Ok(entry) = serde_json::from_str::<CacheEntry<serde_json::Value>>(&payload) else {
+            continue;
+        };
+        // Only LLM results are worth sharing, the other entries are cheap to rebuild
+        if entry.identity.is_none() {
+            continue;
+        }
+        if let Some((_, started)) = &since_run {
+            let used_by_run = run_entries
+                .as_ref()
+                .is_some_and(|used| used.contains(&(key.category.clone(), key.hash.clone())));
+            if !used_by_run && (entry.timestamp as i64) < started.timestamp() {
+                continue;
+            }
+        }
+        entries.push(BundleEntry {
+            checksum: checksum(&payload),
+            category: key.category,
+            hash: key.hash,
+            payload,
+        });
+    }
+
+    let manifest = BundleManifest {
+        format: BUNDLE_FORMAT.to_string(),
+        version: BUNDLE_VERSION,
+        created_at: Utc::now(),
+        litho_version: env!("CARGO_PKG_VERSION").to_string(),
+        since_run: since_run.map(|(run_id, _)| run_id),
+        entries: entries.len(),
+    };
+    let file = File::create(output)
+        .with_context(|| format!("Failed to create cache bundle {}", output.display()))?;
+    let mut writer = GzEncoder::new(BufWriter::new(file), Compression::default());
+    writeln!(writer, "{}", serde_json::to_string(&manifest)?)?;
+    for entry in &entries {
+        writeln!(writer, "{}", serde_json::to_string(entry)?)?;
+    }
+    writer.finish()?.flush()?;
+
+    println!(
+        "📦 Exported {} cache entries from {} to {}",
+        entries.len(),
+        store.location(),
+        output.display()
+    );
+    Ok(())
+}
+
+/// Merge a bundle into the local cache. Damaged entries, entries of models or prompt template versions the
+/// local configuration does not use, expired entries and entries already present are skipped
+pub async fn import(config: &Config, archive: &Path) -> Result<()> {
+    let file = File::open(archive)
+        .with_context(|| format!("Failed to open cache bundle {}", archive.display()))?;
+    let mut lines = BufReader::new(GzDecoder::new(file)).lines();
+    let not_a_bundle = || anyhow!("{} is not a Litho cache bundle", archive.display());
+    let first_line = lines.next().ok_or_else(not_a_bundle)?.map_err(|_| not_a_bundle())?;
+    let manifest = serde_json::from_str::<BundleManifest>(&first_line)
+        .ok()
+        .filter(|manifest| manifest.format == BUNDLE_FORMAT)
+        .ok_or_else(not_a_bundle)?;
+    if manifest.version > BUNDLE_VERSION {
+        return Err(anyhow!(
+            "Cache bundle {} has format version {}, this Litho reads up to {}",
+            archive.display(),
+            manifest.version,
+            BUNDLE_VERSION
+        ));
+    }
+
+    let models = local_models(config);
+    let template_versions = PromptTemplates::load(config)?.versions(config);
+    let expire_seconds = config.cache.expire_hours * 3600;
+    let now = Utc::now().timestamp() as u64;
+    let store = open_store(&config.cache)?;
+
+    let mut read = 0;
+    let (mut imported, mut present, mut damaged, mut other_model, mut other_templates, mut expired) =
+        (0, 0, 0, 0, 0, 0);
+    for line in lines {
+        // A truncated archive fails here, on the gzip checksum
+        let line = line.with_context(|| format!("Cache bundle {} is damaged", archive.display()))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        read += 1;
+        let Some((bundled, entry)) = serde_json::from_str::<BundleEntry>(&line)
+            .ok()
+            .filter(|bundled| checksum(&bundled.payload) == bundled.checksum)
+            .and_then(|bundled| {
+                let entry = serde_json::from_str::<CacheEntry<serde_json::Value>>(&bundled.payload).ok()?;
+                Some((bundled, entry))
+            })
+            .filter(|(bundled, entry)| {
+                // Category and hash become a path in the directory store, so they must not leave the cache
+                entry.prompt_hash == bundled.hash && validate_key(&bundled.category, &bundled.hash).is_ok()
+            })
+        else {
+            damaged += 1;
+            continue;
+        };
+        let Some(identity) = &entry.identity else {
+            damaged += 1;
+            continue;
+        };
+        if !models.contains(&(identity.provider.clone(), identity.model.clone())) {
+            other_model += 1;
+            continue;
+        }
+        if identity
+            .template_version
+            .as_ref()
+            .is_some_and(|version| !template_versions.contains(version))
+        {
+            other_templates += 1;
+            continue;
+        }
+        if now.saturating_sub(entry.timestamp) > expire_seconds {
+            expired += 1;
+            continue;
+        }
+        if store.peek(&bundled.category, &bundled.hash).await?.is_some() {
+            present += 1;
+            continue;
+        }
+        store
+            .put(&bundled.category, &bundled.hash, bundled.payload.into_bytes())
+            .await?;
+        imported += 1;
+    }
+    store.flush().await?;
+
+    if read != manifest.entries {
+        eprintln!(
+            "⚠️  Cache bundle {} lists {} entries but holds {}",
+            archive.display(),
+            manifest.entries,
+            read
+        );
+    }
+    println!(
+        "📥 Imported {} of {} cache entries into {}",
+        imported,
+        read,
+        store.location()
+    );
+    for (count, reason) in [
+        (present, "already cached"),
+        (other_model, "for a provider or model the configuration does not use"),
+        (other_templates, "built from prompt templates that differ from the local ones"),
+        (expired, "older than expire_hours"),
+        (damaged, "damaged"),
+    ] {
+        if count > 0 {
+            println!("   ⏭️  Skipped {} {}", count, reason);
+        }
+    }
+    Ok(())
+}
+
+/// Run id and start time of a run, `latest` for the most recent one
+fn find_run(config: &Config, run: &str) -> Result<(String, DateTime<Utc>)> {
+    let runs = RunCheckpoint::list_runs(config)?;
+    let manifest = if run == "latest" {
+        runs.into_iter().max_by_key(|manifest| manifest.created_at)
+    } else {
+        runs.into_iter().find(|manifest| manifest.run_id == run)
+    };
+    manifest
+        .map(|manifest| (manifest.run_id, manifest.created_at))
+        .ok_or_else(|| {
+            anyhow!(
+                "No run {} in {}",
+                run,
+                RunCheckpoint::runs_dir(config).display()
+            )
+        })
+}
+
+/// Providers and models the cache keys of this configuration can name, those of the routes included
+fn local_models(config: &Config) -> BTreeSet<(String, String)> {
+    let routed = config
+        .llm
+        .routing
+        .routes
+        .values()
+        .map(|route| route.apply(&config.llm));
+    std::iter::once(config.llm.clone())
+        .chain(routed)
+        .flat_map(|llm: LLMConfig| {
+            let provider = llm.provider.to_string();
+            [
+                (provider.clone(), llm.model_efficient),
+                (provider, llm.model_powerful),
+            ]
+        })
+        .collect()
+}
+
+fn checksum(payload: &str) -> String {
+    format!("{:x}", Md5::digest(payload.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::CacheIdentity;
+    use crate::cache::store::CacheStore;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    fn test_config(root: &Path, name: &str) -> Config {
+        let mut config = Config {
+            internal_path: root.join(name).join(".litho"),
+            ..Config::default()
+        };
+        config.cache.cache_dir = root.join(name).join("cache");
+        config
+    }
+
+    fn identity(config: &Config) -> CacheIdentity {
+        CacheIdentity {
+            provider: config.llm.provider.to_string(),
+            model: config.llm.model_efficient.clone(),
+            ..CacheIdentity::default()
+        }
+    }
+
+    /// Store an LLM result written `age` seconds ago, returns its hash
+    async fn put(store: &Arc<dyn CacheStore>, prompt: &str, identity: CacheIdentity, age: u64) -> String {
+        let hash = format!("{:x}", Md5::digest(prompt.as_bytes()));
+        let entry = CacheEntry {
+            data: serde_json::json!({ "answer": prompt }),
+            timestamp: Utc::now().timestamp() as u64 - age,
+            prompt_hash: hash.clone(),
+            token_usage: None,
+            model_name: Some(identity.model.clone()),
+            provider: Some(identity.provider.clone()),
+            identity: Some(identity),
+        };
+        store
+            .put("ai_code_insight", &hash, serde_json::to_vec(&entry).unwrap())
+            .await
+            .unwrap();
+        hash
+    }
+
+    /// Rewrite the entry lines of a bundle
+    fn rewrite(bundle: &Path, edit: impl Fn(&mut BundleEntry)) -> PathBuf {
+        let content = std::io::read_to_string(GzDecoder::new(File::open(bundle).unwrap())).unwrap();
+        let mut lines = content.lines();
+        let output = bundle.with_extension("edited.gz");
+        let mut writer = GzEncoder::new(File::create(&output).unwrap(), Compression::default());
+        writeln!(writer, "{}", lines.next().unwrap()).unwrap();
+        for line in lines {
+            let mut entry = serde_json::from_str::<BundleEntry>(line).unwrap();
+            edit(&mut entry);
+            writeln!(writer, "{}", serde_json::to_string(&entry).unwrap()).unwrap();
+        }
+        writer.finish().unwrap();
+        output
+    }
+
+    #[tokio::test]
+    async fn test_bundle_round_trip() {
+        let root = std::env::temp_dir().join(format!("litho-bundle-{}", uuid::Uuid::new_v4()));
+        let source = test_config(&root, "source");
+        let source_store = open_store(&source.cache).unwrap();
+        let local = identity(&source);
+        let kept = put(&source_store, "kept", local.clone(), 0).await;
+        let other_model = CacheIdentity {
+            model: "some-other-model".to_string(),
+            ..local.clone()
+        };
+        put(&source_store, "other model", other_model, 0).await;
+        let other_templates = CacheIdentity {
+            template_version: Some("edited".to_string()),
+            ..local.clone()
+        };
+        put(&source_store, "other templates", other_templates, 0).await;
+        source_store.flush().await.unwrap();
+
+        let bundle = root.join("cache.gz");
+        export(&source, &bundle, None, None).await.unwrap();
+
+        let target = test_config(&root, "target");
+        import(&target, &bundle).await.unwrap();
+        let target_store = open_store(&target.cache).unwrap();
+        let imported = target_store.list().await.unwrap();
+        assert_eq!(imported.len(), 1);
+        assert_eq!(imported[0].hash, kept);
+        assert_eq!(
+            target_store.peek("ai_code_insight", &kept).await.unwrap(),
+            source_store.peek("ai_code_insight", &kept).await.unwrap()
+        );
+
+        // Damaged entries are skipped, whether the checksum or the key is wrong
+        let damaged = test_config(&root, "damaged");
+        let corrupted = rewrite(&bundle, |entry| entry.payload = entry.payload.replace("kept", "kepT"));
+        import(&damaged, &corrupted).await.unwrap();
+        let escaping = rewrite(&bundle, |entry| entry.category = "../../escaped".to_string());
+        import(&damaged, &escaping).await.unwrap();
+        assert!(open_store(&damaged.cache).unwrap().list().await.unwrap().is_empty());
+        assert!(!root.join("escaped").exists());
+
+        // A truncated archive fails instead of importing part of it silently
+        let bytes = std::fs::read(&bundle).unwrap();
+        let truncated = root.join("truncated.gz");
+        std::fs::write(&truncated, &bytes[..bytes.len() - 8]).unwrap();
+        assert!(import(&damaged, &truncated).await.is_err());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_export_since_run() {
+        let root = std::env::temp_dir().join(format!("litho-bundle-{}", uuid::Uuid::new_v4()));
+        let config = test_config(&root, "source");
+        let store = open_store(&config.cache).unwrap();
+        let used = put(&store, "read by the run", identity(&config), 3600).await;
+        put(&store, "not used by the run", identity(&config), 3600).await;
+        store.flush().await.unwrap();
+
+        let run = RunCheckpoint::create(&config).unwrap();
+        run.record_cache_entries(&BTreeSet::from([("ai_code_insight".to_string(), used.clone())]))
+            .await
+            .unwrap();
+        let written = put(&store, "written by the run", identity(&config), 0).await;
+        store.flush().await.unwrap();
+
+        let bundle = root.join("cache.gz");
+        export(&config, &bundle, Some("latest"), None).await.unwrap();
+        let content = std::io::read_to_string(GzDecoder::new(File::open(&bundle).unwrap())).unwrap();
+        let hashes = content
+            .lines()
+            .skip(1)
+            .map(|line| serde_json::from_str::<BundleEntry>(line).unwrap().hash)
+            .collect::<BTreeSet<_>>();
+        assert_eq!(hashes, BTreeSet::from([used, written]));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
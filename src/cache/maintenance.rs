@@ -0,0 +1,386 @@
+//! Cache maintenance - Statistics, pruning, clearing and inspection of the cache store, behind `litho cache`
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result, anyhow};
+use serde::Deserialize;
+
+use super::CacheEntry;
+use super::store::{CacheStore, StoredKey, open_store};
+use crate::config::Config;
+use crate::llm::client::types::TokenUsage;
+
+/// Upper bounds of the age buckets of `stats`, the last bucket holds everything older
+const AGE_BUCKETS: [(&str, u64); 5] = [
+    ("< 1 day", 86_400),
+    ("< 1 week", 7 * 86_400),
+    ("< 30 days", 30 * 86_400),
+    ("< 90 days", 90 * 86_400),
+    ("< 1 year", 365 * 86_400),
+];
+
+/// Fields of an entry read for maintenance, the cached data is skipped
+#[derive(Deserialize)]
+struct EntryHeader {
+    timestamp: u64,
+    token_usage: Option<TokenUsage>,
+}
+
+/// Entry found in the cache store
+struct StoredEntry {
+    key: StoredKey,
+    /// None if the entry is not readable
+    header: Option<EntryHeader>,
+}
+
+impl StoredEntry {
+    /// Write time, None if the entry is not readable
+    fn timestamp(&self) -> Option<u64> {
+        self.header.as_ref().map(|header| header.timestamp)
+    }
+}
+
+#[derive(Default)]
+struct CategoryStats {
+    entries: usize,
+    bytes: u64,
+    input_tokens: usize,
+    output_tokens: usize,
+}
+
+/// Entries, bytes, ages and tokens cached per category
+pub async fn stats(config: &Config) -> Result<()> {
+    let store = open_store(&config.cache)?;
+    let entries = scan(store.as_ref()).await?;
+    if entries.is_empty() {
+        println!("📭 No cache entries in {}", store.location());
+        return Ok(());
+    }
+
+    let now = now();
+    let expire_seconds = config.cache.expire_hours * 3600;
+    let mut categories: BTreeMap<&str, CategoryStats> = BTreeMap::new();
+    let mut ages = [0usize; AGE_BUCKETS.len() + 1];
+    let (mut unreadable, mut expired) = (0, 0);
+    for entry in &entries {
+        let stats = categories.entry(&entry.key.category).or_default();
+        stats.entries += 1;
+        stats.bytes += entry.key.bytes;
+        let Some(header) = &entry.header else {
+            unreadable += 1;
+            continue;
+        };
+        if let Some(usage) = &header.token_usage {
+            stats.input_tokens += usage.input_tokens;
+            stats.output_tokens += usage.output_tokens;
+        }
+        let age = now.saturating_sub(header.timestamp);
+        if age > expire_seconds {
+            expired += 1;
+        }
+        let bucket = AGE_BUCKETS
+            .iter()
+            .position(|(_, limit)| age < *limit)
+            .unwrap_or(AGE_BUCKETS.len());
+        ages[bucket] += 1;
+    }
+
+    let total_bytes: u64 = entries.iter().map(|entry| entry.key.bytes).sum();
+    println!(
+        "📦 Cache {}: {} entries, {}",
+        store.location(),
+        entries.len(),
+        format_bytes(total_bytes)
+    );
+    for (category, stats) in &categories {
+        println!(
+            "   {}: {} entries, {}, {} input / {} output tokens cached",
+            category,
+            stats.entries,
+            format_bytes(stats.bytes),
+            stats.input_tokens,
+            stats.output_tokens
+        );
+    }
+
+    println!("🕒 Age");
+    let readable = entries.len() - unreadable;
+    let labels = AGE_BUCKETS
+        .iter()
+        .map(|(label, _)| *label)
+        .chain(std::iter::once("older"));
+    for (label, count) in labels.zip(ages) {
+        let width = (count * 40).div_ceil(readable.max(1));
+        println!("   {:<10} {:>6} {}", label, count, "█".repeat(width));
+    }
+    if expired > 0 {
+        println!(
+            "ℹ️  {} entries are older than expire_hours ({}) and are dropped when read, `litho cache prune --older-than {}h` deletes them",
+            expired, config.cache.expire_hours, config.cache.expire_hours
+        );
+    }
+    if unreadable > 0 {
+        println!("⚠️  {} entries are not readable", unreadable);
+    }
+    Ok(())
+}
+
+/// Delete entries older than `older_than`, then the oldest entries until the cache fits in `max_size` bytes.
+/// Unreadable entries have no known age, they are deleted last and only to fit in `max_size`
+pub async fn prune(config: &Config, older_than: Option<Duration>, max_size: Option<u64>) -> Result<()> {
+    let store = open_store(&config.cache)?;
+    let mut entries = scan(store.as_ref()).await?;
+    entries.sort_by_key(|entry| entry.timestamp().unwrap_or(u64::MAX));
+
+    let now = now();
+    let mut total: u64 = entries.iter().map(|entry| entry.key.bytes).sum();
+    let (mut removed, mut removed_bytes) = (0, 0);
+    for entry in &entries {
+        let too_old = older_than.zip(entry.timestamp()).is_some_and(|(limit, timestamp)| {
+            now.saturating_sub(timestamp) > limit.as_secs()
+        });
+        let too_big = max_size.is_some_and(|limit| total > limit);
+        if !too_old && !too_big {
+            continue;
+        }
+        remove(store.as_ref(), &entry.key).await?;
+        total -= entry.key.bytes;
+        removed += 1;
+        removed_bytes += entry.key.bytes;
+    }
+    store.flush().await?;
+
+    println!(
+        "🧹 Pruned {} entries ({}), {} entries ({}) left",
+        removed,
+        format_bytes(removed_bytes),
+        entries.len() - removed,
+        format_bytes(total)
+    );
+    Ok(())
+}
+
+/// Delete every entry, or the entries of one category
+pub async fn clear(config: &Config, category: Option<&str>) -> Result<()> {
+    let store = open_store(&config.cache)?;
+    let keys = store.list().await?;
+    if let Some(category) = category
+        && !keys.iter().any(|key| key.category == category)
+    {
+        let categories: BTreeSet<&str> = keys.iter().map(|key| key.category.as_str()).collect();
+        return Err(anyhow!(
+            "No cache entries in category {}, categories: {}",
+            category,
+            categories.into_iter().collect::<Vec<_>>().join(", ")
+        ));
+    }
+
+    let (mut removed, mut removed_bytes) = (0, 0);
+    for key in keys
+        .iter()
+        .filter(|key| category.is_none_or(|category| key.category == category))
+    {
+        remove(store.as_ref(), key).await?;
+        removed += 1;
+        removed_bytes += key.bytes;
+    }
+    store.flush().await?;
+    println!("🗑️  Cleared {} entries ({})", removed, format_bytes(removed_bytes));
+    Ok(())
+}
+
+/// Print the metadata and the cached response of an entry, found by its hash or a prefix of it
+pub async fn show(config: &Config, hash: &str) -> Result<()> {
+    let store = open_store(&config.cache)?;
+    let keys = store.list().await?;
+    let matches: Vec<&StoredKey> = keys.iter().filter(|key| key.hash.starts_with(hash)).collect();
+    let key = match matches[..] {
+        [key] => key,
+        [] => return Err(anyhow!("No cache entry with hash {}", hash)),
+        _ => {
+            let found: Vec<String> = matches
+                .iter()
+                .map(|key| format!("{}/{}", key.category, key.hash))
+                .collect();
+            return Err(anyhow!("Hash {} is ambiguous: {}", hash, found.join(", ")));
+        }
+    };
+
+    let bytes = store
+        .peek(&key.category, &key.hash)
+        .await?
+        .ok_or_else(|| anyhow!("Cache entry {} disappeared", key.hash))?;
+    let stored: CacheEntry<serde_json::Value> = serde_json::from_slice(&bytes)
+        .with_context(|| format!("{}/{} is not a readable cache entry", key.category, key.hash))?;
+
+    println!("🗂️  {}/{} in {}", key.category, key.hash, store.location());
+    println!("   prompt hash: {}", stored.prompt_hash);
+    let written = chrono::DateTime::from_timestamp(stored.timestamp as i64, 0)
+        .map(|time| time.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+        .unwrap_or_default();
+    println!("   written: {} ({} days ago)", written, now().saturating_sub(stored.timestamp) / 86_400);
+    if let (Some(provider), Some(model)) = (&stored.provider, &stored.model_name) {
+        println!("   produced by: {}/{}", provider, model);
+    }
+    if let Some(identity) = &stored.identity {
+        println!("   key: {}/{}", identity.provider, identity.model);
+        if let Some(temperature) = identity.temperature {
+            println!("   temperature: {}", temperature);
+        }
+        if let Some(version) = &identity.template_version {
+            println!("   prompt templates: {}", version);
+        }
+        if let Some(schema_hash) = &identity.schema_hash {
+            println!("   output schema: {}", schema_hash);
+        }
+    }
+    if let Some(usage) = &stored.token_usage {
+        println!(
+            "   tokens: {} input, {} output",
+            usage.input_tokens, usage.output_tokens
+        );
+    }
+    println!();
+    match &stored.data {
+        serde_json::Value::String(text) => println!("{}", text),
+        data => println!("{}", serde_json::to_string_pretty(data)?),
+    }
+    Ok(())
+}
+
+/// Entries of the store with their headers
+async fn scan(store: &dyn CacheStore) -> Result<Vec<StoredEntry>> {
+    let mut entries = Vec::new();
+    for key in store.list().await? {
+        let header = store
+            .peek(&key.category, &key.hash)
+            .await
+            .ok()
+            .flatten()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok());
+        entries.push(StoredEntry { key, header });
+    }
+    Ok(entries)
+}
+
+async fn remove(store: &dyn CacheStore, key: &StoredKey) -> Result<()> {
+    store
+        .remove(&key.category, &key.hash)
+        .await
+        .with_context(|| format!("Failed to delete {}/{}", key.category, key.hash))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Parse an age such as `90m`, `12h`, `30d` or `2w`
+pub fn parse_age(text: &str) -> std::result::Result<Duration, String> {
+    let text = text.trim();
+    let split = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid age {}, expected e.g. 12h, 30d or 2w", text))?;
+    let seconds = match unit.trim() {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" | "" => 86_400,
+        "w" => 7 * 86_400,
+        other => return Err(format!("unknown age unit {}, expected s, m, h, d or w", other)),
+    };
+    Ok(Duration::from_secs(number * seconds))
+}
+
+/// Parse a size such as `500MB`, `2G` or `1048576`
+pub fn parse_size(text: &str) -> std::result::Result<u64, String> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid size {}, expected e.g. 500MB or 2GB", text))?;
+    let factor: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1024,
+        "M" | "MB" => 1024 * 1024,
+        "G" | "GB" => 1024 * 1024 * 1024,
+        other => return Err(format!("unknown size unit {}, expected B, KB, MB or GB", other)),
+    };
+    Ok((number * factor as f64) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_age_and_size() {
+        assert_eq!(parse_age("12h"), Ok(Duration::from_secs(12 * 3600)));
+        assert_eq!(parse_age("30d"), Ok(Duration::from_secs(30 * 86_400)));
+        assert_eq!(parse_age("2w"), Ok(Duration::from_secs(14 * 86_400)));
+        assert!(parse_age("soon").is_err());
+        assert!(parse_age("3y").is_err());
+
+        assert_eq!(parse_size("1048576"), Ok(1024 * 1024));
+        assert_eq!(parse_size("500MB"), Ok(500 * 1024 * 1024));
+        assert_eq!(parse_size("1.5gb"), Ok(3 * 512 * 1024 * 1024));
+        assert!(parse_size("10 parsecs").is_err());
+        assert_eq!(format_bytes(1536), "1.5 KB");
+    }
+
+    #[tokio::test]
+    async fn test_prune_keeps_unreadable_entries_without_size_pressure() {
+        let root = std::env::temp_dir().join(format!("litho-prune-{}", uuid::Uuid::new_v4()));
+        let mut config = Config::default();
+        config.cache.cache_dir = root.clone();
+        let store = open_store(&config.cache).unwrap();
+        let entry = |timestamp: u64| serde_json::to_vec(&serde_json::json!({ "timestamp": timestamp })).unwrap();
+        let (old, new, broken) = ("a".repeat(32), "b".repeat(32), "c".repeat(32));
+        store.put("insights", &old, entry(0)).await.unwrap();
+        store.put("insights", &new, entry(now())).await.unwrap();
+        store.put("insights", &broken, b"not json".to_vec()).await.unwrap();
+        store.flush().await.unwrap();
+        drop(store);
+
+        let hashes = |config: Config| async move {
+            let store = open_store(&config.cache).unwrap();
+            let mut hashes: Vec<String> = store.list().await.unwrap().into_iter().map(|key| key.hash).collect();
+            hashes.sort();
+            hashes
+        };
+
+        prune(&config, Some(Duration::from_secs(86_400)), None).await.unwrap();
+        assert_eq!(hashes(config.clone()).await, vec![new, broken.clone()]);
+
+        // Under size pressure the readable entries go first, the unreadable one is the last left
+        prune(&config, None, Some(9)).await.unwrap();
+        assert_eq!(hashes(config.clone()).await, vec![broken]);
+        prune(&config, None, Some(0)).await.unwrap();
+        assert!(hashes(config.clone()).await.is_empty());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
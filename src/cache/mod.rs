@@ -1,21 +1,31 @@
 use anyhow::Result;
 use md5::{Digest, Md5};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
-use tokio::fs;
 
 use crate::config::CacheConfig;
 use crate::i18n::TargetLanguage;
-use crate::llm::client::types::TokenUsage;
+use crate::llm::client::pricing::PricingTable;
+use crate::llm::client::types::{LLMResponse, TokenUsage};
 
+pub mod bundle;
+pub mod maintenance;
+mod pack_store;
 pub mod performance_monitor;
+pub mod store;
 pub use performance_monitor::{CachePerformanceMonitor, CachePerformanceReport};
+use store::{CacheStore, open_store};
 
 /// Cache manager
 pub struct CacheManager {
     config: CacheConfig,
+    store: Arc<dyn CacheStore>,
     performance_monitor: CachePerformanceMonitor,
+    /// Category and hash of the LLM results read or written by this process
+    used_llm_entries: Mutex<BTreeSet<(String, String)>>,
 }
 
 /// Cache entry
@@ -23,20 +33,53 @@ pub struct CacheManager {
 pub struct CacheEntry<T> {
     pub data: T,
     pub timestamp: u64,
-    /// MD5 hash of the prompt, used for cache key generation and verification
+    /// MD5 hash of the cache key, the prompt and for LLM results their identity
     pub prompt_hash: String,
     /// Token usage information (optional, for accurate statistics)
     pub token_usage: Option<TokenUsage>,
     /// Model name used (optional)
     pub model_name: Option<String>,
+    /// Provider that produced the data (optional)
+    pub provider: Option<String>,
+    /// What the key of an LLM result covers besides the prompt, None for other entries
+    #[serde(default)]
+    pub identity: Option<CacheIdentity>,
+}
+
+/// What an LLM result depends on besides the prompt. Part of the cache key, so changing any of these
+/// invalidates exactly the entries it affects
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CacheIdentity {
+    pub provider: String,
+    /// Model routing picks for the call, before any fallover, fallback or budget degradation
+    pub model: String,
+    pub temperature: Option<f64>,
+    /// Version of the overridden prompt templates, None for the built-in ones
+    pub template_version: Option<String>,
+    /// Hash of the JSON schema of the output type, None for text replies
+    pub schema_hash: Option<String>,
+}
+
+impl CacheIdentity {
+    /// Hash of the JSON schema of a structured output type
+    pub fn schema_hash<T: JsonSchema>() -> String {
+        let schema = serde_json::to_string(&schemars::schema_for!(T)).unwrap_or_default();
+        format!("{:x}", Md5::digest(schema.as_bytes()))
+    }
 }
 
 impl CacheManager {
-    pub fn new(config: CacheConfig, target_language: TargetLanguage) -> Self {
-        Self {
+    pub fn new(
+        config: CacheConfig,
+        target_language: TargetLanguage,
+        pricing: Arc<PricingTable>,
+    ) -> Result<Self> {
+        Ok(Self {
+            store: open_store(&config)?,
             config,
-            performance_monitor: CachePerformanceMonitor::new(target_language),
-        }
+            performance_monitor: CachePerformanceMonitor::new(target_language, pricing),
+            used_llm_entries: Mutex::default(),
+        })
     }
 
     /// Generate MD5 hash of the prompt
@@ -46,12 +89,10 @@ impl CacheManager {
         format!("{:x}", hasher.finalize())
     }
 
-    /// Get cache file path
-    fn get_cache_path(&self, category: &str, hash: &str) -> PathBuf {
-        self.config
-            .cache_dir
-            .join(category)
-            .join(format!("{}.json", hash))
+    /// Cache key hash of a prompt and the identity of the LLM call answering it
+    fn hash_key(&self, prompt: &str, identity: &CacheIdentity) -> String {
+        let identity = serde_json::to_string(identity).unwrap_or_default();
+        self.hash_prompt(&format!("{}\n{}", prompt, identity))
     }
 
     /// Check if cache is expired
@@ -64,30 +105,39 @@ impl CacheManager {
         now - timestamp > expire_seconds
     }
 
-    /// Get cache
-    pub async fn get<T>(&self, category: &str, prompt: &str) -> Result<Option<T>>
+    /// Get the cached LLM result of a prompt, if it was produced with the same identity
+    pub async fn get_llm_result<T>(
+        &self,
+        category: &str,
+        prompt: &str,
+        identity: &CacheIdentity,
+    ) -> Result<Option<T>>
     where
         T: for<'de> Deserialize<'de>,
     {
         if !self.config.enabled {
             return Ok(None);
         }
-
-        let hash = self.hash_prompt(prompt);
-        let cache_path = self.get_cache_path(category, &hash);
-
-        if !cache_path.exists() {
+        // Regenerating skips reads, the fresh results still replace the entries
+        if self.config.force_regenerate {
             self.performance_monitor.record_cache_miss(category);
             return Ok(None);
         }
 
-        match fs::read_to_string(&cache_path).await {
-            Ok(content) => {
+        let hash = self.hash_key(prompt, identity);
+        match self.store.get(category, &hash).await {
+            Ok(Some(bytes)) => {
+                let content = String::from_utf8_lossy(&bytes);
                 match serde_json::from_str::<CacheEntry<T>>(&content) {
                     Ok(entry) => {
                         if self.is_expired(entry.timestamp) {
                             // Delete expired cache
-                            let _ = fs::remove_file(&cache_path).await;
+                            let _ = self.store.remove(category, &hash).await;
+                            self.performance_monitor.record_cache_miss(category);
+                            return Ok(None);
+                        }
+                        // Guards against hash collisions
+                        if entry.identity.as_ref() != Some(identity) {
                             self.performance_monitor.record_cache_miss(category);
                             return Ok(None);
                         }
@@ -101,9 +151,11 @@ impl CacheManager {
                                 category,
                                 estimated_inference_time,
                                 token_usage.clone(),
-                                "",
+                                entry.provider.as_deref().unwrap_or_default(),
+                                entry.model_name.as_deref().unwrap_or_default(),
                             );
                         }
+                        self.record_use(category, &hash);
                         Ok(Some(entry.data))
                     }
                     Err(e) => {
@@ -113,74 +165,53 @@ impl CacheManager {
                     }
                 }
             }
+            Ok(None) => {
+                self.performance_monitor.record_cache_miss(category);
+                Ok(None)
+            }
             Err(e) => {
                 self.performance_monitor
-                    .record_cache_error(category, &format!("Failed to read file: {}", e));
+                    .record_cache_error(category, &format!("Failed to read entry: {}", e));
                 Ok(None)
             }
         }
     }
 
-    /// Set cache (with token usage information)
-    pub async fn set_with_tokens<T>(
+    /// Cache an LLM result under its prompt and identity, with the token usage and the provider and model
+    /// that actually produced it
+    pub async fn set_llm_result<T>(
         &self,
         category: &str,
         prompt: &str,
-        data: T,
+        identity: &CacheIdentity,
+        response: &LLMResponse<T>,
         token_usage: TokenUsage,
     ) -> Result<()>
     where
         T: Serialize,
     {
-        if !self.config.enabled {
-            return Ok(());
-        }
-
-        let hash = self.hash_prompt(prompt);
-        let cache_path = self.get_cache_path(category, &hash);
-
-        // Ensure directory exists
-        if let Some(parent) = cache_path.parent() {
-            fs::create_dir_all(parent).await?;
-        }
-
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-
         let entry = CacheEntry {
-            data,
-            timestamp,
-            prompt_hash: hash,
+            data: &response.content,
+            timestamp: 0,
+            prompt_hash: self.hash_key(prompt, identity),
             token_usage: Some(token_usage),
-            model_name: None,
+            model_name: Some(response.model.clone()),
+            provider: Some(response.provider.clone()),
+            identity: Some(identity.clone()),
         };
-
-        match serde_json::to_string_pretty(&entry) {
-            Ok(content) => match fs::write(&cache_path, content).await {
-                Ok(_) => {
-                    self.performance_monitor.record_cache_write(category);
-                    Ok(())
-                }
-                Err(e) => {
-                    self.performance_monitor
-                        .record_cache_error(category, &format!("Failed to write file: {}", e));
-                    Err(e.into())
-                }
-            },
-            Err(e) => {
-                self.performance_monitor
-                    .record_cache_error(category, &format!("Serialization failed: {}", e));
-                Err(e.into())
-            }
-        }
+        self.write_entry(category, entry).await
     }
 
     /// Get compression result cache
-    pub async fn get_compression_cache(&self, original_content: &str, content_type: &str) -> Result<Option<String>> {
+    pub async fn get_compression_cache(
+        &self,
+        original_content: &str,
+        content_type: &str,
+        identity: &CacheIdentity,
+    ) -> Result<Option<String>> {
         let cache_key = format!("{}_{}", content_type, self.hash_prompt(original_content));
-        self.get::<String>("prompt_compression", &cache_key).await
+        self.get_llm_result::<String>("prompt_compression", &cache_key, identity)
+            .await
     }
 
     /// Set compression result cache
@@ -188,12 +219,39 @@ impl CacheManager {
         &self,
         original_content: &str,
         content_type: &str,
+        identity: &CacheIdentity,
         compressed_content: String,
     ) -> Result<()> {
         let cache_key = format!("{}_{}", content_type, self.hash_prompt(original_content));
-        self.set("prompt_compression", &cache_key, compressed_content).await
+        let entry = CacheEntry {
+            data: compressed_content,
+            timestamp: 0,
+            prompt_hash: self.hash_key(&cache_key, identity),
+            token_usage: None,
+            model_name: None,
+            provider: None,
+            identity: Some(identity.clone()),
+        };
+        self.write_entry("prompt_compression", entry).await
     }
     pub async fn set<T>(&self, category: &str, prompt: &str, data: T) -> Result<()>
+    where
+        T: Serialize,
+    {
+        let entry = CacheEntry {
+            data,
+            timestamp: 0,
+            prompt_hash: self.hash_prompt(prompt),
+            token_usage: None,
+            model_name: None,
+            provider: None,
+            identity: None,
+        };
+        self.write_entry(category, entry).await
+    }
+
+    /// Write an entry under its `prompt_hash`, stamped with the current time
+    async fn write_entry<T>(&self, category: &str, mut entry: CacheEntry<T>) -> Result<()>
     where
         T: Serialize,
     {
@@ -201,37 +259,28 @@ impl CacheManager {
             return Ok(());
         }
 
-        let hash = self.hash_prompt(prompt);
-        let cache_path = self.get_cache_path(category, &hash);
-
-        // Ensure directory exists
-        if let Some(parent) = cache_path.parent() {
-            fs::create_dir_all(parent).await?;
-        }
-
-        let timestamp = SystemTime::now()
+        entry.timestamp = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_secs();
 
-        let entry = CacheEntry {
-            data,
-            timestamp,
-            prompt_hash: hash,
-            token_usage: None,
-            model_name: None,
-        };
-
         match serde_json::to_string_pretty(&entry) {
-            Ok(content) => match fs::write(&cache_path, content).await {
+            Ok(content) => match self
+                .store
+                .put(category, &entry.prompt_hash, content.into_bytes())
+                .await
+            {
                 Ok(_) => {
+                    if entry.identity.is_some() {
+                        self.record_use(category, &entry.prompt_hash);
+                    }
                     self.performance_monitor.record_cache_write(category);
                     Ok(())
                 }
                 Err(e) => {
                     self.performance_monitor
-                        .record_cache_error(category, &format!("Failed to write file: {}", e));
-                    Err(e.into())
+                        .record_cache_error(category, &format!("Failed to write entry: {}", e));
+                    Err(e)
                 }
             },
             Err(e) => {
@@ -242,6 +291,20 @@ impl CacheManager {
         }
     }
 
+    fn record_use(&self, category: &str, hash: &str) {
+        if let Ok(mut used) = self.used_llm_entries.lock() {
+            used.insert((category.to_string(), hash.to_string()));
+        }
+    }
+
+    /// Category and hash of the LLM results this run read from or wrote to the cache
+    pub fn used_llm_entries(&self) -> BTreeSet<(String, String)> {
+        self.used_llm_entries
+            .lock()
+            .map(|used| used.clone())
+            .unwrap_or_default()
+    }
+
     /// Estimate inference time (based on content complexity)
     fn estimate_inference_time(&self, content: &str) -> Duration {
         // Estimate inference time based on content length
@@ -257,3 +320,81 @@ impl CacheManager {
         self.performance_monitor.generate_report()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::LLMConfig;
+
+    #[tokio::test]
+    async fn test_identity_is_part_of_the_key() {
+        let cache_dir = std::env::temp_dir().join(format!("litho-cache-{}", uuid::Uuid::new_v4()));
+        let config = CacheConfig {
+            cache_dir: cache_dir.clone(),
+            ..CacheConfig::default()
+        };
+        let pricing = Arc::new(PricingTable::new(&LLMConfig::default()));
+        let cache = CacheManager::new(config, TargetLanguage::English, pricing).unwrap();
+
+        let identity = CacheIdentity {
+            provider: "openai".to_string(),
+            model: "gpt-4o-mini".to_string(),
+            temperature: Some(0.1),
+            template_version: None,
+            schema_hash: Some(CacheIdentity::schema_hash::<String>()),
+        };
+        let response = LLMResponse {
+            content: "insight".to_string(),
+            usage: None,
+            model: identity.model.clone(),
+            provider: identity.provider.clone(),
+        };
+        cache
+            .set_llm_result("ai_code_insight", "prompt", &identity, &response, TokenUsage::new(10, 5))
+            .await
+            .unwrap();
+
+        let hit = cache
+            .get_llm_result::<String>("ai_code_insight", "prompt", &identity)
+            .await
+            .unwrap();
+        assert_eq!(hit, Some("insight".to_string()));
+
+        let changed = [
+            CacheIdentity {
+                provider: "anthropic".to_string(),
+                ..identity.clone()
+            },
+            CacheIdentity {
+                model: "gpt-4o".to_string(),
+                ..identity.clone()
+            },
+            CacheIdentity {
+                temperature: Some(0.7),
+                ..identity.clone()
+            },
+            CacheIdentity {
+                template_version: Some("edited".to_string()),
+                ..identity.clone()
+            },
+            CacheIdentity {
+                schema_hash: Some(CacheIdentity::schema_hash::<Vec<String>>()),
+                ..identity.clone()
+            },
+        ];
+        for other in &changed {
+            let miss = cache
+                .get_llm_result::<String>("ai_code_insight", "prompt", other)
+                .await
+                .unwrap();
+            assert_eq!(miss, None, "{:?}", other);
+        }
+        let other_prompt = cache
+            .get_llm_result::<String>("ai_code_insight", "other prompt", &identity)
+            .await
+            .unwrap();
+        assert_eq!(other_prompt, None);
+
+        std::fs::remove_dir_all(&cache_dir).unwrap();
+    }
+}
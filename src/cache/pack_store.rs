@@ -0,0 +1,579 @@
+//! Pack cache store - Compressed entries appended to a single pack file, located through an index,
+//! with least-recently-used eviction above a size cap
+//!
+//! The pack is the source of truth: every write and deletion is a checksummed record appended to it and
+//! synced. The index in `cache.idx` only saves rescanning the pack on open, it is replaced atomically and
+//! rebuilt from the pack when it is missing or stale. A record torn by a crash is cut off on the next open.
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result, anyhow};
+use async_trait::async_trait;
+use flate2::Compression;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use serde::{Deserialize, Serialize};
+
+use super::store::{CacheStore, StoredKey};
+
+const PACK_FILE: &str = "cache.pack";
+const INDEX_FILE: &str = "cache.idx";
+/// Locked for as long as a process has the pack open, the pack itself is replaced by compaction
+const LOCK_FILE: &str = "cache.lock";
+const MAGIC: &[u8; 8] = b"LITHOPK1";
+/// Magic and generation
+const PACK_HEADER_LEN: u64 = 16;
+/// Checksum, kind, key length and value length
+const RECORD_HEADER_LEN: usize = 4 + 1 + 2 + 4;
+const KIND_DELETE: u8 = 0;
+const KIND_PUT: u8 = 1;
+/// Writes after which the index is saved, it is also saved when the store is dropped
+const INDEX_SAVE_INTERVAL: usize = 64;
+/// Eviction frees space down to this share of the size cap, so it does not run on every write
+const EVICTION_TARGET: f64 = 0.9;
+/// Dead records are reclaimed once they outweigh the live ones and take at least this many bytes
+const MIN_COMPACTION_BYTES: u64 = 1024 * 1024;
+
+/// Location of a live record in the pack
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Slot {
+    offset: u64,
+    /// Length of the whole record
+    len: u64,
+    last_access: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PackIndex {
+    /// Generation of the pack the offsets point into, changed by every compaction
+    generation: u64,
+    /// Length of the pack the index covers, records after it are scanned on open
+    pack_len: u64,
+    slots: HashMap<String, Slot>,
+}
+
+/// Record read from the pack
+struct Record {
+    kind: u8,
+    key: String,
+    value: Vec<u8>,
+    len: u64,
+}
+
+struct PackState {
+    dir: PathBuf,
+    file: File,
+    /// Holds the exclusive lock on `cache.lock`, released when the state is dropped
+    _lock: File,
+    index: PackIndex,
+    /// Bytes of the live records
+    live_bytes: u64,
+    max_bytes: Option<u64>,
+    unsaved_writes: usize,
+    dirty: bool,
+}
+
+/// Cache store keeping every entry in `<cache_dir>/cache.pack`
+pub struct PackStore {
+    state: Arc<Mutex<PackState>>,
+    location: String,
+}
+
+impl PackStore {
+    pub fn open(dir: &Path, max_bytes: Option<u64>) -> Result<Self> {
+        let state = PackState::open(dir, max_bytes)?;
+        Ok(Self {
+            state: Arc::new(Mutex::new(state)),
+            location: dir.join(PACK_FILE).display().to_string(),
+        })
+    }
+
+    /// Run an operation on the pack off the async runtime
+    async fn run<R, F>(&self, operation: F) -> Result<R>
+    where
+        R: Send + 'static,
+        F: FnOnce(&mut PackState) -> Result<R> + Send + 'static,
+    {
+        let state = self.state.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut state = state
+                .lock()
+                .map_err(|_| anyhow!("Cache pack lock poisoned"))?;
+            operation(&mut state)
+        })
+        .await?
+    }
+}
+
+#[async_trait]
+impl CacheStore for PackStore {
+    async fn get(&self, category: &str, hash: &str) -> Result<Option<Vec<u8>>> {
+        let key = pack_key(category, hash);
+        self.run(move |state| state.read(&key, true)).await
+    }
+
+    async fn peek(&self, category: &str, hash: &str) -> Result<Option<Vec<u8>>> {
+        let key = pack_key(category, hash);
+        self.run(move |state| state.read(&key, false)).await
+    }
+
+    async fn put(&self, category: &str, hash: &str, value: Vec<u8>) -> Result<()> {
+        let key = pack_key(category, hash);
+        self.run(move |state| state.put(&key, &value)).await
+    }
+
+    async fn remove(&self, category: &str, hash: &str) -> Result<()> {
+        let key = pack_key(category, hash);
+        self.run(move |state| state.remove(&key)).await
+    }
+
+    async fn list(&self) -> Result<Vec<StoredKey>> {
+        self.run(|state| Ok(state.list())).await
+    }
+
+    async fn flush(&self) -> Result<()> {
+        self.run(|state| {
+            if state.dead_bytes()? > 0 {
+                state.compact()?;
+            }
+            state.save_index()
+        })
+        .await
+    }
+
+    fn location(&self) -> String {
+        self.location.clone()
+    }
+}
+
+impl PackState {
+    fn open(dir: &Path, max_bytes: Option<u64>) -> Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let lock = lock_pack(dir)?;
+        let pack_path = dir.join(PACK_FILE);
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&pack_path)
+            .with_context(|| format!("Failed to open cache pack {}", pack_path.display()))?;
+
+        let generation = if file.metadata()?.len() < PACK_HEADER_LEN {
+            let generation = rand::random::<u64>();
+            write_pack_header(&mut file, generation)?;
+            generation
+        } else {
+            read_pack_header(&mut file)
+                .with_context(|| format!("{} is not a Litho cache pack", pack_path.display()))?
+        };
+
+        let pack_len = file.metadata()?.len();
+        let index = std::fs::read(dir.join(INDEX_FILE))
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<PackIndex>(&bytes).ok())
+            .filter(|index| index.generation == generation && index.pack_len <= pack_len)
+            .unwrap_or_else(|| PackIndex {
+                generation,
+                pack_len: PACK_HEADER_LEN,
+                slots: HashMap::new(),
+            });
+
+        let mut state = Self {
+            dir: dir.to_path_buf(),
+            file,
+            _lock: lock,
+            live_bytes: index.slots.values().map(|slot| slot.len).sum(),
+            index,
+            max_bytes,
+            unsaved_writes: 0,
+            dirty: false,
+        };
+        state.scan_tail(pack_len)?;
+        Ok(state)
+    }
+
+    /// Apply the records written after the index was saved, cutting off a torn last record
+    fn scan_tail(&mut self, pack_len: u64) -> Result<()> {
+        let now = now();
+        let mut offset = self.index.pack_len;
+        while offset < pack_len {
+            let Some(record) = read_record(&mut self.file, offset)? else {
+                eprintln!(
+                    "⚠️  Cache pack {} has a damaged record at byte {}, dropping the {} bytes after it",
+                    self.dir.join(PACK_FILE).display(),
+                    offset,
+                    pack_len - offset
+                );
+                self.file.set_len(offset)?;
+                self.file.sync_all()?;
+                break;
+            };
+            if let Some(previous) = self.index.slots.remove(&record.key) {
+                self.live_bytes -= previous.len;
+            }
+            if record.kind == KIND_PUT {
+                self.index.slots.insert(
+                    record.key,
+                    Slot {
+                        offset,
+                        len: record.len,
+                        last_access: now,
+                    },
+                );
+                self.live_bytes += record.len;
+            }
+            offset += record.len;
+            self.dirty = true;
+        }
+        self.index.pack_len = offset;
+        Ok(())
+    }
+
+    /// Value of an entry, `touch` marks it as recently used for eviction
+    fn read(&mut self, key: &str, touch: bool) -> Result<Option<Vec<u8>>> {
+        let Some(slot) = self.index.slots.get(key).cloned() else {
+            return Ok(None);
+        };
+        let record = match read_record(&mut self.file, slot.offset)? {
+            Some(record) if record.key == key && record.kind == KIND_PUT => record,
+            _ => {
+                eprintln!("⚠️  Cache pack entry {} is damaged, dropping it", key);
+                self.remove(key)?;
+                return Ok(None);
+            }
+        };
+        if touch && let Some(slot) = self.index.slots.get_mut(key) {
+            slot.last_access = now();
+            self.dirty = true;
+        }
+        let mut value = Vec::new();
+        DeflateDecoder::new(&record.value[..]).read_to_end(&mut value)?;
+        Ok(Some(value))
+    }
+
+    fn put(&mut self, key: &str, value: &[u8]) -> Result<()> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(value)?;
+        let (offset, len) = self.append(KIND_PUT, key, &encoder.finish()?)?;
+        if let Some(previous) = self.index.slots.insert(
+            key.to_string(),
+            Slot {
+                offset,
+                len,
+                last_access: now(),
+            },
+        ) {
+            self.live_bytes -= previous.len;
+        }
+        self.live_bytes += len;
+
+        self.evict()?;
+        if self.dead_bytes()? >= MIN_COMPACTION_BYTES.max(self.live_bytes) {
+            self.compact()?;
+        }
+        self.unsaved_writes += 1;
+        if self.unsaved_writes >= INDEX_SAVE_INTERVAL {
+            self.save_index()?;
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &str) -> Result<()> {
+        if let Some(slot) = self.index.slots.remove(key) {
+            self.live_bytes -= slot.len;
+            self.append(KIND_DELETE, key, &[])?;
+        }
+        Ok(())
+    }
+
+    fn list(&self) -> Vec<StoredKey> {
+        self.index
+            .slots
+            .iter()
+            .map(|(key, slot)| {
+                let (category, hash) = key.rsplit_once('/').unwrap_or(("", key));
+                StoredKey {
+                    category: category.to_string(),
+                    hash: hash.to_string(),
+                    bytes: slot.len,
+                }
+            })
+            .collect()
+    }
+
+    /// Append a synced record at the end of the pack, returning its offset and length
+    fn append(&mut self, kind: u8, key: &str, value: &[u8]) -> Result<(u64, u64)> {
+        let record = encode_record(kind, key, value)?;
+        let offset = self.file.seek(SeekFrom::End(0))?;
+        self.file.write_all(&record)?;
+        self.file.sync_data()?;
+        self.index.pack_len = offset + record.len() as u64;
+        self.dirty = true;
+        Ok((offset, record.len() as u64))
+    }
+
+    /// Delete the least recently used entries once the live records exceed the size cap
+    fn evict(&mut self) -> Result<()> {
+        let Some(max_bytes) = self.max_bytes else {
+            return Ok(());
+        };
+        if self.live_bytes <= max_bytes {
+            return Ok(());
+        }
+        let target = (max_bytes as f64 * EVICTION_TARGET) as u64;
+        let mut by_access: Vec<(String, u64)> = self
+            .index
+            .slots
+            .iter()
+            .map(|(key, slot)| (key.clone(), slot.last_access))
+            .collect();
+        by_access.sort_by_key(|(_, last_access)| *last_access);
+
+        let mut evicted = 0;
+        for (key, _) in by_access {
+            if self.live_bytes <= target {
+                break;
+            }
+            self.remove(&key)?;
+            evicted += 1;
+        }
+        println!(
+            "🧹 Evicted {} least recently used cache entries to stay below {} MB",
+            evicted,
+            max_bytes / 1024 / 1024
+        );
+        Ok(())
+    }
+
+    /// Bytes of overwritten, deleted and deletion records
+    fn dead_bytes(&self) -> Result<u64> {
+        let pack_len = self.file.metadata()?.len();
+        Ok(pack_len.saturating_sub(PACK_HEADER_LEN + self.live_bytes))
+    }
+
+    /// Rewrite the live records into a new pack that replaces the current one atomically. The index is
+    /// only switched to the new pack once it is in place, a failure leaves the current pack and index in use
+    fn compact(&mut self) -> Result<()> {
+        let generation = rand::random::<u64>();
+        let tmp_path = self.dir.join(format!("{}.tmp", PACK_FILE));
+        let mut tmp = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&tmp_path)?;
+        write_pack_header(&mut tmp, generation)?;
+
+        let mut slots: Vec<(&String, &Slot)> = self.index.slots.iter().collect();
+        slots.sort_by_key(|(_, slot)| slot.offset);
+        let mut compacted = HashMap::with_capacity(slots.len());
+        let mut offset = PACK_HEADER_LEN;
+        let mut buffer = Vec::new();
+        for (key, slot) in slots {
+            buffer.resize(slot.len as usize, 0);
+            self.file.seek(SeekFrom::Start(slot.offset))?;
+            self.file.read_exact(&mut buffer)?;
+            tmp.write_all(&buffer)?;
+            compacted.insert(key.clone(), Slot { offset, ..slot.clone() });
+            offset += slot.len;
+        }
+        tmp.sync_all()?;
+
+        std::fs::rename(&tmp_path, self.dir.join(PACK_FILE))?;
+        self.file = tmp;
+        self.index.slots = compacted;
+        self.index.generation = generation;
+        self.index.pack_len = offset;
+        self.live_bytes = offset - PACK_HEADER_LEN;
+        self.save_index()
+    }
+
+    /// Replace the index file atomically
+    fn save_index(&mut self) -> Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+        let index_path = self.dir.join(INDEX_FILE);
+        let tmp_path = self.dir.join(format!("{}.tmp", INDEX_FILE));
+        std::fs::write(&tmp_path, serde_json::to_vec(&self.index)?)?;
+        std::fs::rename(&tmp_path, &index_path)?;
+        self.unsaved_writes = 0;
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+impl Drop for PackState {
+    fn drop(&mut self) {
+        if let Err(e) = self.save_index() {
+            eprintln!("⚠️  Failed to save the cache pack index: {}", e);
+        }
+    }
+}
+
+/// Take the exclusive lock of a pack directory, so two processes never append to or compact the same pack
+fn lock_pack(dir: &Path) -> Result<File> {
+    let lock_path = dir.join(LOCK_FILE);
+    let lock = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&lock_path)
+        .with_context(|| format!("Failed to open cache lock {}", lock_path.display()))?;
+    match lock.try_lock() {
+        Ok(()) => Ok(lock),
+        Err(std::fs::TryLockError::WouldBlock) => Err(anyhow!(
+            "Cache pack in {} is in use by another Litho process. Wait for it to finish, or give parallel jobs separate cache_dir settings",
+            dir.display()
+        )),
+        Err(std::fs::TryLockError::Error(e)) => {
+            Err(anyhow!(e).context(format!("Failed to lock cache pack in {}", dir.display())))
+        }
+    }
+}
+
+fn pack_key(category: &str, hash: &str) -> String {
+    format!("{}/{}", category, hash)
+}
+
+fn write_pack_header(file: &mut File, generation: u64) -> Result<()> {
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(MAGIC)?;
+    file.write_all(&generation.to_le_bytes())?;
+    file.sync_all()?;
+    Ok(())
+}
+
+fn read_pack_header(file: &mut File) -> Result<u64> {
+    let mut header = [0u8; PACK_HEADER_LEN as usize];
+    file.seek(SeekFrom::Start(0))?;
+    file.read_exact(&mut header)?;
+    if &header[..8] != MAGIC {
+        return Err(anyhow!("unknown file header"));
+    }
+    Ok(u64::from_le_bytes(header[8..].try_into()?))
+}
+
+/// Checksum, kind, key length, value length, key and value. The checksum covers everything after it
+fn encode_record(kind: u8, key: &str, value: &[u8]) -> Result<Vec<u8>> {
+    let key_len = u16::try_from(key.len()).map_err(|_| anyhow!("Cache key {} is too long", key))?;
+    let value_len = u32::try_from(value.len()).map_err(|_| anyhow!("Cache entry {} is too large", key))?;
+    let mut record = Vec::with_capacity(RECORD_HEADER_LEN + key.len() + value.len());
+    record.extend_from_slice(&[0; 4]);
+    record.push(kind);
+    record.extend_from_slice(&key_len.to_le_bytes());
+    record.extend_from_slice(&value_len.to_le_bytes());
+    record.extend_from_slice(key.as_bytes());
+    record.extend_from_slice(value);
+    let checksum = checksum(&record[4..]);
+    record[..4].copy_from_slice(&checksum.to_le_bytes());
+    Ok(record)
+}
+
+/// Record at an offset, None if it is truncated or its checksum does not match
+fn read_record(file: &mut File, offset: u64) -> Result<Option<Record>> {
+    let pack_len = file.metadata()?.len();
+    if offset + RECORD_HEADER_LEN as u64 > pack_len {
+        return Ok(None);
+    }
+    let mut header = [0u8; RECORD_HEADER_LEN];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut header)?;
+    let key_len = u16::from_le_bytes([header[5], header[6]]) as u64;
+    let value_len = u32::from_le_bytes([header[7], header[8], header[9], header[10]]) as u64;
+    let len = RECORD_HEADER_LEN as u64 + key_len + value_len;
+    if offset + len > pack_len {
+        return Ok(None);
+    }
+
+    let mut body = vec![0u8; (key_len + value_len) as usize];
+    file.read_exact(&mut body)?;
+    let mut covered = header[4..].to_vec();
+    covered.extend_from_slice(&body);
+    if checksum(&covered) != u32::from_le_bytes([header[0], header[1], header[2], header[3]]) {
+        return Ok(None);
+    }
+    let value = body.split_off(key_len as usize);
+    let Ok(key) = String::from_utf8(body) else {
+        return Ok(None);
+    };
+    Ok(Some(Record {
+        kind: header[4],
+        key,
+        value,
+        len,
+    }))
+}
+
+fn checksum(bytes: &[u8]) -> u32 {
+    let mut crc = flate2::Crc::new();
+    crc.update(bytes);
+    crc.sum()
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pack_survives_reopen_torn_writes_and_evicts() {
+        let dir = std::env::temp_dir().join(format!("litho-pack-{}", uuid::Uuid::new_v4()));
+        let value = |n: usize| format!("{{\"data\": \"{}\"}}", "answer ".repeat(n)).into_bytes();
+
+        let mut state = PackState::open(&dir, None).unwrap();
+        // A second process must not append to the same pack
+        assert!(PackState::open(&dir, None).is_err());
+        state.put("ai_code_insight/a1", &value(10)).unwrap();
+        state.put("ai_code_insight/b2", &value(20)).unwrap();
+        state.put("ai_code_insight/a1", &value(30)).unwrap();
+        state.remove("ai_code_insight/b2").unwrap();
+        assert_eq!(state.read("ai_code_insight/a1", true).unwrap(), Some(value(30)));
+        assert_eq!(state.read("ai_code_insight/b2", true).unwrap(), None);
+        drop(state);
+
+        // A crash in the middle of a write leaves a partial record behind
+        let pack_path = dir.join(PACK_FILE);
+        let mut file = OpenOptions::new().append(true).open(&pack_path).unwrap();
+        file.write_all(&encode_record(KIND_PUT, "ai_code_insight/c3", &value(5)).unwrap()[..20])
+            .unwrap();
+        drop(file);
+        std::fs::remove_file(dir.join(INDEX_FILE)).unwrap();
+
+        let mut state = PackState::open(&dir, None).unwrap();
+        assert_eq!(state.list().len(), 1);
+        assert_eq!(state.read("ai_code_insight/a1", true).unwrap(), Some(value(30)));
+        state.compact().unwrap();
+        assert_eq!(state.dead_bytes().unwrap(), 0);
+        assert_eq!(state.read("ai_code_insight/a1", true).unwrap(), Some(value(30)));
+        drop(state);
+
+        // The least recently used entries go once the cap is exceeded
+        let mut state = PackState::open(&dir, None).unwrap();
+        state.put("prompt_compression/d4", &value(3)).unwrap();
+        state.index.slots.get_mut("ai_code_insight/a1").unwrap().last_access = 0;
+        // Maintenance reads leave the order of use alone
+        assert_eq!(state.read("ai_code_insight/a1", false).unwrap(), Some(value(30)));
+        assert_eq!(state.index.slots["ai_code_insight/a1"].last_access, 0);
+        state.max_bytes = Some(state.live_bytes - 1);
+        state.evict().unwrap();
+        assert_eq!(state.read("ai_code_insight/a1", true).unwrap(), None);
+        assert_eq!(state.read("prompt_compression/d4", true).unwrap(), Some(value(3)));
+        drop(state);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
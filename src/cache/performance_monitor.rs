@@ -1,17 +1,20 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use crate::i18n::TargetLanguage;
+use crate::llm::client::pricing::PricingTable;
 use crate::llm::client::types::TokenUsage;
+use crate::llm::client::usage::ModelKey;
 
 /// Cache performance monitor
 #[derive(Clone)]
 pub struct CachePerformanceMonitor {
     metrics: Arc<CacheMetrics>,
     target_language: TargetLanguage,
+    pricing: Arc<PricingTable>,
 }
 
 /// Cache metrics
@@ -27,8 +30,8 @@ pub struct CacheMetrics {
     pub cache_errors: AtomicUsize,
     /// Total inference time saved (seconds)
     pub total_inference_time_saved: AtomicU64,
-    /// Total cost saved (estimated)
-    pub total_cost_saved: AtomicUsize,
+    /// Savings per model that produced the cached data
+    pub model_savings: Mutex<BTreeMap<ModelKey, ModelSavingsStats>>,
     /// Total input tokens saved
     pub total_input_tokens_saved: AtomicUsize,
     /// Total output tokens saved
@@ -62,6 +65,18 @@ pub struct CachePerformanceReport {
     pub output_tokens_saved: usize,
     /// Category statistics
     pub category_stats: HashMap<String, CategoryPerformanceStats>,
+    /// Savings per model that produced the cached data
+    pub model_stats: BTreeMap<ModelKey, ModelSavingsStats>,
+}
+
+/// Savings of the cache hits of one model
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelSavingsStats {
+    pub hits: usize,
+    pub input_tokens_saved: usize,
+    pub output_tokens_saved: usize,
+    /// Cost saved (USD), priced with the model's price
+    pub cost_saved: f64,
 }
 
 /// Category performance statistics
@@ -75,10 +90,11 @@ pub struct CategoryPerformanceStats {
 }
 
 impl CachePerformanceMonitor {
-    pub fn new(target_language: TargetLanguage) -> Self {
+    pub fn new(target_language: TargetLanguage, pricing: Arc<PricingTable>) -> Self {
         Self {
             metrics: Arc::new(CacheMetrics::default()),
             target_language,
+            pricing,
         }
     }
 
@@ -88,6 +104,7 @@ impl CachePerformanceMonitor {
         category: &str,
         inference_time_saved: Duration,
         token_usage: TokenUsage,
+        provider: &str,
         model_name: &str,
     ) {
         self.metrics.cache_hits.fetch_add(1, Ordering::Relaxed);
@@ -103,12 +120,19 @@ impl CachePerformanceMonitor {
             .total_output_tokens_saved
             .fetch_add(token_usage.output_tokens, Ordering::Relaxed);
 
-        // Calculate saved cost based on actual token usage
-        let estimated_cost_saved = token_usage.estimate_cost(model_name);
-        self.metrics.total_cost_saved.fetch_add(
-            (estimated_cost_saved * 1000.0) as usize, // Store as milli-dollars
-            Ordering::Relaxed,
-        );
+        // Calculate saved cost based on actual token usage and the price of the model
+        let estimated_cost_saved = self.pricing.cost(&token_usage, provider, model_name);
+        {
+            let model = if model_name.is_empty() { "unknown model" } else { model_name };
+            let mut model_savings = self.metrics.model_savings.lock().unwrap();
+            let stats = model_savings
+                .entry((provider.to_string(), model.to_string()))
+                .or_default();
+            stats.hits += 1;
+            stats.input_tokens_saved += token_usage.input_tokens;
+            stats.output_tokens_saved += token_usage.output_tokens;
+            stats.cost_saved += estimated_cost_saved;
+        }
 
         // Use localized message for cache hit with detailed statistics
         let msg = match &self.target_language {
@@ -166,7 +190,8 @@ impl CachePerformanceMonitor {
             .total_inference_time_saved
             .load(Ordering::Relaxed) as f64
             / 1000.0; // Convert to seconds
-        let cost_saved = self.metrics.total_cost_saved.load(Ordering::Relaxed) as f64 / 1000.0; // Convert to dollars
+        let model_stats = self.metrics.model_savings.lock().unwrap().clone();
+        let cost_saved = model_stats.values().map(|stats| stats.cost_saved).sum();
 
         let input_tokens_saved = self
             .metrics
@@ -196,12 +221,16 @@ impl CachePerformanceMonitor {
             input_tokens_saved,
             output_tokens_saved,
             category_stats: HashMap::new(), // TODO: Implement category statistics
+            model_stats,
         }
     }
 }
 
 impl Default for CachePerformanceMonitor {
     fn default() -> Self {
-        Self::new(TargetLanguage::default())
+        Self::new(
+            TargetLanguage::default(),
+            Arc::new(PricingTable::new(&Default::default())),
+        )
     }
 }
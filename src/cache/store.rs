@@ -0,0 +1,179 @@
+//! Cache stores - Where the serialized cache entries live, selected by `[cache] backend`
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use tokio::fs;
+
+use super::pack_store::PackStore;
+use crate::config::{CacheBackend, CacheConfig};
+
+/// Entry held by a store, as listed for maintenance
+#[derive(Debug, Clone)]
+pub struct StoredKey {
+    pub category: String,
+    pub hash: String,
+    /// Bytes the entry takes in the store
+    pub bytes: u64,
+}
+
+/// Storage of serialized cache entries, addressed by category and key hash
+#[async_trait]
+pub trait CacheStore: Send + Sync {
+    async fn get(&self, category: &str, hash: &str) -> Result<Option<Vec<u8>>>;
+
+    /// Read an entry without counting it as a use, for maintenance and export
+    async fn peek(&self, category: &str, hash: &str) -> Result<Option<Vec<u8>>> {
+        self.get(category, hash).await
+    }
+
+    async fn put(&self, category: &str, hash: &str, value: Vec<u8>) -> Result<()>;
+
+    /// Delete an entry, missing entries are ignored
+    async fn remove(&self, category: &str, hash: &str) -> Result<()>;
+
+    /// Every entry of the store
+    async fn list(&self) -> Result<Vec<StoredKey>>;
+
+    /// Persist pending state and reclaim the space of deleted entries
+    async fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Location shown in messages
+    fn location(&self) -> String;
+}
+
+/// Open the store configured for the cache
+pub fn open_store(config: &CacheConfig) -> Result<Arc<dyn CacheStore>> {
+    Ok(match config.backend {
+        CacheBackend::Directory => Arc::new(DirectoryStore::new(config.cache_dir.clone())),
+        CacheBackend::Pack => Arc::new(PackStore::open(
+            &config.cache_dir,
+            config.max_size_mb.map(|mb| mb * 1024 * 1024),
+        )?),
+    })
+}
+
+/// Check that a category and key hash name an entry inside the cache directory. Categories are `/`-separated
+/// scopes that may hold domain names, so each segment is checked instead of a fixed list
+pub fn validate_key(category: &str, hash: &str) -> Result<()> {
+    let valid_segment = |segment: &str| {
+        !segment.is_empty()
+            && segment != "."
+            && segment != ".."
+            && !segment.chars().any(|c| c == '\\' || c.is_control())
+    };
+    if !category.split('/').all(valid_segment) {
+        return Err(anyhow!("Invalid cache category {:?}", category));
+    }
+    if hash.len() != 32 || !hash.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f')) {
+        return Err(anyhow!("Invalid cache key hash {:?}", hash));
+    }
+    Ok(())
+}
+
+/// One pretty-printed JSON file per entry, `<cache_dir>/<category>/<hash>.json`
+pub struct DirectoryStore {
+    dir: PathBuf,
+}
+
+impl DirectoryStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn path(&self, category: &str, hash: &str) -> Result<PathBuf> {
+        validate_key(category, hash)?;
+        Ok(self.dir.join(category).join(format!("{}.json", hash)))
+    }
+}
+
+#[async_trait]
+impl CacheStore for DirectoryStore {
+    async fn get(&self, category: &str, hash: &str) -> Result<Option<Vec<u8>>> {
+        let path = self.path(category, hash)?;
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(fs::read(&path).await?))
+    }
+
+    async fn put(&self, category: &str, hash: &str, value: Vec<u8>) -> Result<()> {
+        let path = self.path(category, hash)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::write(&path, value).await?;
+        Ok(())
+    }
+
+    async fn remove(&self, category: &str, hash: &str) -> Result<()> {
+        match fs::remove_file(self.path(category, hash)?).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn list(&self) -> Result<Vec<StoredKey>> {
+        let mut keys = Vec::new();
+        if !self.dir.is_dir() {
+            return Ok(keys);
+        }
+        // Categories such as `studies_research/KeyModulesInsight/<domain>` nest directories
+        let mut pending = vec![self.dir.clone()];
+        while let Some(dir) = pending.pop() {
+            let category = dir
+                .strip_prefix(&self.dir)
+                .map(|relative| relative.to_string_lossy().replace('\\', "/"))
+                .unwrap_or_default();
+            let mut entries = fs::read_dir(&dir).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let path = entry.path();
+                let file_type = entry.file_type().await?;
+                if file_type.is_dir() {
+                    pending.push(path);
+                    continue;
+                }
+                if category.is_empty()
+                    || !file_type.is_file()
+                    || path.extension().is_none_or(|ext| ext != "json")
+                {
+                    continue;
+                }
+                keys.push(StoredKey {
+                    category: category.clone(),
+                    hash: path
+                        .file_stem()
+                        .map(|stem| stem.to_string_lossy().to_string())
+                        .unwrap_or_default(),
+                    bytes: entry.metadata().await?.len(),
+                });
+            }
+        }
+        Ok(keys)
+    }
+
+    fn location(&self) -> String {
+        self.dir.display().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys_stay_inside_the_cache_directory() {
+        let hash = "0123456789abcdef0123456789abcdef";
+        assert!(validate_key("ai_code_insight", hash).is_ok());
+        assert!(validate_key("studies_research/KeyModulesInsight/User Accounts", hash).is_ok());
+        assert!(validate_key("../../..", hash).is_err());
+        assert!(validate_key("studies_research/../../x", hash).is_err());
+        assert!(validate_key("/etc", hash).is_err());
+        assert!(validate_key("ai_code_insight", "../../x").is_err());
+        assert!(validate_key("ai_code_insight", "0123456789ABCDEF0123456789ABCDEF").is_err());
+    }
+}
@@ -1,4 +1,4 @@
-use crate::config::{Config, LLMProvider};
+use crate::config::{Config, FixtureConfig, FixtureMode, LLMProvider};
 use crate::i18n::TargetLanguage;
 use clap::{Parser, Subcommand};
 use std::path::PathBuf;
@@ -31,18 +31,34 @@ pub struct Args {
     #[arg(short, long)]
     pub name: Option<String>,
 
-    /// Skip project preprocessing
+    /// Skip project preprocessing, reuse the results of the last run from .litho/snapshots
     #[arg(long)]
     pub skip_preprocessing: bool,
 
-    /// Skip research document generation
+    /// Skip research document generation, reuse the results of the last run from .litho/snapshots
     #[arg(long)]
     pub skip_research: bool,
 
-    /// Skip final document generation
+    /// Skip final document generation, reuse the results of the last run from .litho/snapshots
     #[arg(long)]
     pub skip_documentation: bool,
 
+    /// Incremental mode, re-analyze only files changed since the last run
+    #[arg(long)]
+    pub incremental: bool,
+
+    /// Build every prompt and report token estimates and projected cost without calling any LLM
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Record every LLM request and response to this fixture directory
+    #[arg(long, conflicts_with = "replay_fixtures")]
+    pub record_fixtures: Option<PathBuf>,
+
+    /// Serve LLM responses from this fixture directory instead of calling the provider
+    #[arg(long)]
+    pub replay_fixtures: Option<PathBuf>,
+
     /// Enable verbose logging
     #[arg(short, long)]
     pub verbose: bool,
@@ -91,7 +107,7 @@ pub struct Args {
     #[arg(long)]
     pub no_cache: bool,
 
-    /// Force regeneration (clear cache)
+    /// Force regeneration, cached LLM results are not read but fresh ones are still written
     #[arg(long)]
     pub force_regenerate: bool,
 }
@@ -109,6 +125,108 @@ pub enum Commands {
         #[arg(long)]
         force: bool,
     },
+    /// Resume an interrupted documentation run from its checkpoint
+    Resume {
+        /// Run id to resume, defaults to the latest unfinished run
+        #[arg(long)]
+        run_id: Option<String>,
+    },
+    /// Inspect the recorded agent conversations
+    Trace {
+        #[command(subcommand)]
+        action: TraceCommand,
+    },
+    /// Manage the prompt templates
+    Prompts {
+        #[command(subcommand)]
+        action: PromptsCommand,
+    },
+    /// Inspect, prune and clear the LLM result cache
+    Cache {
+        #[command(subcommand)]
+        action: CacheCommand,
+    },
+}
+
+/// Cache subcommands
+#[derive(Subcommand, Debug)]
+pub enum CacheCommand {
+    /// Show entries, size, age and tokens cached per category
+    Stats,
+    /// Delete old entries, then the oldest ones until the cache fits in a size
+    Prune {
+        /// Delete entries older than this age, e.g. 12h, 30d or 2w
+        #[arg(long, value_parser = crate::cache::maintenance::parse_age, required_unless_present = "max_size")]
+        older_than: Option<std::time::Duration>,
+
+        /// Maximum size of the cache, e.g. 500MB or 2GB
+        #[arg(long, value_parser = crate::cache::maintenance::parse_size)]
+        max_size: Option<u64>,
+    },
+    /// Delete every entry, or those of one category
+    Clear {
+        /// Category to clear, e.g. ai_code_insight or prompt_compression
+        #[arg(long)]
+        category: Option<String>,
+    },
+    /// Print the metadata and the cached response of an entry
+    Show {
+        /// Hash of the entry, its file name in the cache directory, or a prefix of it
+        hash: String,
+    },
+    /// Write the cached LLM results to a portable bundle
+    Export {
+        /// Bundle file to write
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Only entries a run read or wrote, a run id from .litho/runs or `latest`. For runs made before
+        /// Litho recorded this, the entries written since the run started
+        #[arg(long)]
+        since: Option<String>,
+
+        /// Only entries of this category, e.g. ai_code_insight
+        #[arg(long)]
+        category: Option<String>,
+    },
+    /// Merge a bundle into the local cache, skipping entries of other models or prompt templates
+    Import {
+        /// Bundle file written by `cache export`
+        archive: PathBuf,
+    },
+}
+
+/// Prompt template subcommands
+#[derive(Subcommand, Debug)]
+pub enum PromptsCommand {
+    /// Write the built-in prompt templates to a directory, to override them with `prompts_dir`
+    Export {
+        /// Target directory, defaults to the configured `prompts_dir` or `./prompts`
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Overwrite existing template files
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+/// Trace subcommands
+#[derive(Subcommand, Debug)]
+pub enum TraceCommand {
+    /// Show the conversations of an agent, with its tool calls and their results
+    Show {
+        /// Agent name, as listed in `.litho/traces/`
+        agent: String,
+
+        /// Show the traces of every run instead of the latest one
+        #[arg(long)]
+        all: bool,
+
+        /// Show prompts and tool results without truncating them
+        #[arg(long)]
+        full: bool,
+    },
 }
 
 impl Args {
@@ -124,7 +242,9 @@ impl Args {
         let mut config = if let Some(config_path) = &self.config {
             // If config file path is explicitly specified, load from that path
             let msg = target_lang.msg_config_read_error().replace("{:?}", &format!("{:?}", config_path));
-            return Config::from_file(config_path).expect(&msg);
+            let mut config = Config::from_file(config_path).expect(&msg);
+            self.apply_stage_flags(&mut config);
+            return config;
         } else {
             // If no config file is explicitly specified, try loading from default location
             let default_config_path = std::env::current_dir()
@@ -133,13 +253,17 @@ impl Args {
 
             if default_config_path.exists() {
                 let msg = target_lang.msg_config_read_error().replace("{:?}", &format!("{:?}", default_config_path));
-                return Config::from_file(&default_config_path).expect(&msg);
+                let mut config = Config::from_file(&default_config_path).expect(&msg);
+                self.apply_stage_flags(&mut config);
+                return config;
             } else {
                 // Default config file doesn't exist, use default values
                 Config::default()
             }
         };
 
+        self.apply_stage_flags(&mut config);
+
         // Override settings from config file
         config.project_path = self.project_path.clone();
         config.output_path = self.output_path;
@@ -198,11 +322,57 @@ impl Args {
             }
         }
 
-        // Cache configuration
+        config
+    }
+
+    /// Apply stage skipping, incremental, cache and fixture flags, these take effect regardless of where the configuration came from
+    fn apply_stage_flags(&self, config: &mut Config) {
         if self.no_cache {
             config.cache.enabled = false;
         }
+        config.cache.force_regenerate |= self.force_regenerate;
+        config.skip_preprocessing |= self.skip_preprocessing;
+        config.skip_research |= self.skip_research;
+        config.skip_documentation |= self.skip_documentation;
+        config.incremental |= self.incremental;
+        if let Some(dir) = &self.record_fixtures {
+            config.llm.fixtures = Some(FixtureConfig {
+                mode: FixtureMode::Record,
+                dir: dir.clone(),
+            });
+        }
+        if let Some(dir) = &self.replay_fixtures {
+            config.llm.fixtures = Some(FixtureConfig {
+                mode: FixtureMode::Replay,
+                dir: dir.clone(),
+            });
+        }
+    }
+}
 
-        config
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flags_apply_to_a_config_file() {
+        let config_path = std::env::temp_dir().join(format!("litho-cli-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(&config_path, toml::to_string(&Config::default()).unwrap()).unwrap();
+        let args = Args::try_parse_from([
+            "deepwiki-rs",
+            "--config",
+            config_path.to_str().unwrap(),
+            "--skip-preprocessing",
+            "--skip-research",
+            "--no-cache",
+            "--force-regenerate",
+        ])
+        .unwrap();
+        let config = args.to_config();
+        assert!(config.skip_preprocessing && config.skip_research && !config.skip_documentation);
+        assert!(!config.cache.enabled);
+        assert!(config.cache.force_regenerate);
+
+        std::fs::remove_file(&config_path).unwrap();
     }
 }
@@ -1,5 +1,6 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::Read;
 use std::path::PathBuf;
@@ -111,12 +112,20 @@ pub struct Config {
     /// Files to exclude
     pub excluded_files: Vec<String>,
 
+    /// Whether the `.gitignore` files of the project exclude paths too, `.lithoignore` files always do
+    #[serde(default = "default_true")]
+    pub use_gitignore: bool,
+
     /// File extensions to exclude
     pub excluded_extensions: Vec<String>,
 
     /// Only include specified file extensions
     pub included_extensions: Vec<String>,
 
+    /// Files the agent tools may never read, glob patterns matched against the file name or relative path
+    #[serde(default = "default_tool_denied_files")]
+    pub tool_denied_files: Vec<String>,
+
     /// LLM model configuration
     pub llm: LLMConfig,
 
@@ -129,6 +138,34 @@ pub struct Config {
 
     /// Architecture meta description file path
     pub architecture_meta_path: Option<PathBuf>,
+
+    /// Directory of `.tpl` files overriding the built-in prompt templates
+    #[serde(default)]
+    pub prompts_dir: Option<PathBuf>,
+
+    /// Skip preprocessing stage and restore its results from the last snapshot
+    #[serde(default)]
+    pub skip_preprocessing: bool,
+
+    /// Skip research stage and restore its results from the last snapshot
+    #[serde(default)]
+    pub skip_research: bool,
+
+    /// Skip documentation stage and restore its results from the last snapshot
+    #[serde(default)]
+    pub skip_documentation: bool,
+
+    /// Re-analyze only files changed since the last run and reuse everything else
+    #[serde(default)]
+    pub incremental: bool,
+
+    /// Number of run checkpoints kept under `.litho/runs`, older runs are deleted when a run starts
+    #[serde(default = "default_keep_runs")]
+    pub keep_runs: usize,
+
+    /// User-defined research and compose agents
+    #[serde(default)]
+    pub custom_agents: Vec<CustomAgentConfig>,
 }
 
 /// LLM model configuration
@@ -159,15 +196,256 @@ pub struct LLMConfig {
     /// Retry attempts
     pub retry_attempts: u32,
 
-    /// Retry interval (milliseconds)
+    /// Retry interval (milliseconds), doubled after every failed attempt
     pub retry_delay_ms: u64,
 
+    /// Upper bound of the retry interval (milliseconds)
+    #[serde(default = "default_max_retry_delay_ms")]
+    pub max_retry_delay_ms: u64,
+
+    /// Repair prompts sent when structured output still violates its schema after local repairs
+    #[serde(default = "default_repair_attempts")]
+    pub repair_attempts: u32,
+
     /// Timeout duration (seconds)
     pub timeout_seconds: u64,
 
     pub disable_preset_tools: bool,
 
     pub max_parallels: usize,
+
+    /// Token and cost budget of a run
+    #[serde(default)]
+    pub budget: BudgetConfig,
+
+    /// Record LLM traffic to fixtures or replay it from them, for deterministic offline runs
+    #[serde(default)]
+    pub fixtures: Option<FixtureConfig>,
+
+    /// Per-model prices (USD per 1k tokens) used for cost reporting, overriding the built-in table
+    #[serde(default)]
+    pub pricing: BTreeMap<String, ModelPricing>,
+
+    /// Rate limits per provider name, shared by every LLM call of a run
+    #[serde(default)]
+    pub rate_limits: BTreeMap<String, RateLimitConfig>,
+
+    /// Model routing per agent and cache scope
+    #[serde(default)]
+    pub routing: RoutingConfig,
+
+    /// Backends tried in order when a call still fails after its retries
+    #[serde(default)]
+    pub fallback: Vec<FallbackBackend>,
+}
+
+/// A provider and model that failed calls fall through to
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct FallbackBackend {
+    pub provider: LLMProvider,
+
+    pub model: String,
+
+    /// API key of the provider, the `[llm]` key if unset
+    pub api_key: Option<String>,
+
+    /// API base URL of the provider, the `[llm]` URL if unset
+    pub api_base_url: Option<String>,
+}
+
+impl FallbackBackend {
+    /// LLM settings of the calls served by this backend
+    pub fn apply(&self, llm: &LLMConfig) -> LLMConfig {
+        let mut backend = llm.clone();
+        backend.provider = self.provider.clone();
+        backend.model_efficient = self.model.clone();
+        backend.model_powerful = self.model.clone();
+        if let Some(api_key) = &self.api_key {
+            backend.api_key = api_key.clone();
+        }
+        if let Some(api_base_url) = &self.api_base_url {
+            backend.api_base_url = api_base_url.clone();
+        }
+        backend
+    }
+}
+
+/// Model routing, routes are keyed by agent name (e.g. `KeyModulesInsight`) or cache scope (e.g. `ai_code_purpose`)
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct RoutingConfig {
+    /// Prompt size in bytes from which the powerful model is chosen, 32KB if unset
+    pub powerful_threshold_bytes: Option<usize>,
+
+    #[serde(flatten)]
+    pub routes: BTreeMap<String, ModelRoute>,
+}
+
+/// Model settings of the calls matching a route, unset fields keep the `[llm]` settings
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct ModelRoute {
+    /// Model of every call, replaces the size-based choice between efficient and powerful model
+    pub model: Option<String>,
+
+    /// Provider serving the model
+    pub provider: Option<LLMProvider>,
+
+    /// API key of the provider
+    pub api_key: Option<String>,
+
+    /// API base URL of the provider
+    pub api_base_url: Option<String>,
+
+    pub max_tokens: Option<u32>,
+
+    pub temperature: Option<f64>,
+
+    /// Prompt size in bytes from which the powerful model is chosen, when no model is set
+    pub powerful_threshold_bytes: Option<usize>,
+}
+
+impl RoutingConfig {
+    /// Route of a call: the agent name first, then the cache scope, then the longest cache scope prefix
+    pub fn find(&self, agent: &str, cache_scope: &str) -> Option<(&String, &ModelRoute)> {
+        self.routes
+            .get_key_value(agent)
+            .or_else(|| self.routes.get_key_value(cache_scope))
+            .or_else(|| {
+                self.routes
+                    .iter()
+                    .filter(|(key, _)| {
+                        cache_scope
+                            .strip_prefix(key.as_str())
+                            .is_some_and(|rest| rest.starts_with('/'))
+                    })
+                    .max_by_key(|(key, _)| key.len())
+            })
+    }
+}
+
+impl ModelRoute {
+    /// Whether the route needs a provider client of its own
+    pub fn has_own_provider(&self) -> bool {
+        self.provider.is_some() || self.api_key.is_some() || self.api_base_url.is_some()
+    }
+
+    /// LLM settings of the calls matching the route
+    pub fn apply(&self, llm: &LLMConfig) -> LLMConfig {
+        let mut routed = llm.clone();
+        if let Some(provider) = &self.provider {
+            routed.provider = provider.clone();
+        }
+        if let Some(api_key) = &self.api_key {
+            routed.api_key = api_key.clone();
+        }
+        if let Some(api_base_url) = &self.api_base_url {
+            routed.api_base_url = api_base_url.clone();
+        }
+        if let Some(model) = &self.model {
+            routed.model_efficient = model.clone();
+            routed.model_powerful = model.clone();
+        }
+        if let Some(max_tokens) = self.max_tokens {
+            routed.max_tokens = max_tokens;
+        }
+        if self.temperature.is_some() {
+            routed.temperature = self.temperature;
+        }
+        if self.powerful_threshold_bytes.is_some() {
+            routed.routing.powerful_threshold_bytes = self.powerful_threshold_bytes;
+        }
+        routed
+    }
+}
+
+fn default_max_retry_delay_ms() -> u64 {
+    60_000
+}
+
+fn default_repair_attempts() -> u32 {
+    2
+}
+
+/// Rate limit of a provider, unset limits are unlimited
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct RateLimitConfig {
+    /// Maximum requests per minute
+    pub requests_per_minute: Option<u32>,
+
+    /// Maximum input + output tokens per minute
+    pub tokens_per_minute: Option<u32>,
+}
+
+/// Price of a model in USD per 1k tokens
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ModelPricing {
+    /// Price of input tokens
+    pub input: f64,
+
+    /// Price of output tokens
+    pub output: f64,
+}
+
+/// Fixture directory of recorded LLM traffic
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct FixtureConfig {
+    pub mode: FixtureMode,
+
+    /// Directory holding one JSON file per recorded call
+    pub dir: PathBuf,
+}
+
+/// Whether LLM calls are recorded to fixtures or served from them
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FixtureMode {
+    /// Call the configured provider and save every request and response
+    Record,
+    /// Serve responses from the fixtures without contacting any provider
+    Replay,
+}
+
+/// Budget of a run, unset limits are unlimited
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct BudgetConfig {
+    /// Maximum input tokens
+    pub max_input_tokens: Option<usize>,
+
+    /// Maximum output tokens
+    pub max_output_tokens: Option<usize>,
+
+    /// Maximum estimated cost (USD)
+    pub max_cost: Option<f64>,
+
+    /// Share of any limit after which the run degrades to save tokens
+    #[serde(default = "default_degrade_threshold")]
+    pub degrade_threshold: f64,
+}
+
+fn default_tool_denied_files() -> Vec<String> {
+    vec![
+        ".env".to_string(),
+        ".env.*".to_string(),
+        "*.pem".to_string(),
+        "*.key".to_string(),
+        "*.p12".to_string(),
+        "id_rsa*".to_string(),
+        "id_ed25519*".to_string(),
+    ]
+}
+
+fn default_degrade_threshold() -> f64 {
+    0.8
+}
+
+impl Default for BudgetConfig {
+    fn default() -> Self {
+        Self {
+            max_input_tokens: None,
+            max_output_tokens: None,
+            max_cost: None,
+            degrade_threshold: default_degrade_threshold(),
+        }
+    }
 }
 
 /// Cache configuration
@@ -181,6 +459,29 @@ pub struct CacheConfig {
 
     /// Cache expiration time (hours)
     pub expire_hours: u64,
+
+    /// Ignore cached results and call the LLM again, fresh results are still written
+    #[serde(default)]
+    pub force_regenerate: bool,
+
+    /// Storage of the cache entries
+    #[serde(default)]
+    pub backend: CacheBackend,
+
+    /// Size cap of the pack backend in megabytes, the least recently used entries are evicted above it
+    #[serde(default)]
+    pub max_size_mb: Option<u64>,
+}
+
+/// Storage of the cache entries
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheBackend {
+    /// One JSON file per entry under `<cache_dir>/<category>/`
+    #[default]
+    Directory,
+    /// A single compressed pack file with an index, `<cache_dir>/cache.pack`
+    Pack,
 }
 
 /// Knowledge configuration for external documentation sources
@@ -295,6 +596,81 @@ fn default_true() -> bool {
     true
 }
 
+fn default_keep_runs() -> usize {
+    20
+}
+
+/// User-defined agent declared in litho.toml
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CustomAgentConfig {
+    /// Unique agent name, also the Memory key of its result
+    pub name: String,
+
+    /// Stage the agent runs in
+    #[serde(default)]
+    pub stage: CustomAgentStage,
+
+    /// Data sources that must be available
+    #[serde(default)]
+    pub required_sources: Vec<CustomAgentSource>,
+
+    /// Data sources used when available
+    #[serde(default)]
+    pub optional_sources: Vec<CustomAgentSource>,
+
+    /// System prompt
+    pub system_prompt: String,
+
+    /// Opening instruction placed before the research materials
+    #[serde(default)]
+    pub opening_instruction: String,
+
+    /// Closing instruction placed after the research materials
+    #[serde(default)]
+    pub closing_instruction: String,
+
+    /// LLM invocation mode
+    #[serde(default)]
+    pub llm_call_mode: CustomAgentCallMode,
+
+    /// Output file relative to the output directory, compose agents only, defaults to `<name>.md`
+    pub output_file: Option<String>,
+}
+
+/// Pipeline stage of a custom agent
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CustomAgentStage {
+    Research,
+    #[default]
+    Compose,
+}
+
+/// Data source of a custom agent
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum CustomAgentSource {
+    /// Preprocessing result: project_structure, code_insights, relationships or original_document
+    Memory(String),
+    /// Result of a research agent, built-in (e.g. SystemContextResearcher) or custom
+    Research(String),
+    /// External knowledge categories
+    Knowledge(Vec<String>),
+}
+
+/// LLM invocation mode of a custom agent
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CustomAgentCallMode {
+    /// Structured JSON output, research agents only
+    Extract,
+    /// Free-form text output
+    #[default]
+    Prompt,
+    /// Free-form text output with the built-in file tools
+    PromptWithTools,
+}
+
 impl Config {
     /// Load configuration from file
     pub fn from_file(path: &PathBuf) -> Result<Self> {
@@ -609,6 +985,7 @@ impl Default for Config {
                 "*.txt".to_string(),
                 ".env".to_string(),
             ],
+            use_gitignore: true,
             excluded_extensions: vec![
                 "jpg".to_string(),
                 "jpeg".to_string(),
@@ -628,10 +1005,18 @@ impl Default for Config {
                 "archive".to_string(),
             ],
             included_extensions: vec![],
+            tool_denied_files: default_tool_denied_files(),
             architecture_meta_path: None,
+            prompts_dir: None,
             llm: LLMConfig::default(),
             cache: CacheConfig::default(),
             knowledge: KnowledgeConfig::default(),
+            skip_preprocessing: false,
+            skip_research: false,
+            skip_documentation: false,
+            incremental: false,
+            keep_runs: default_keep_runs(),
+            custom_agents: Vec::new(),
         }
     }
 }
@@ -648,9 +1033,17 @@ impl Default for LLMConfig {
             temperature: Some(0.1),
             retry_attempts: 3,
             retry_delay_ms: 5000,
+            max_retry_delay_ms: default_max_retry_delay_ms(),
+            repair_attempts: default_repair_attempts(),
             timeout_seconds: 300,
             disable_preset_tools: false,
             max_parallels: 3,
+            budget: BudgetConfig::default(),
+            fixtures: None,
+            pricing: BTreeMap::new(),
+            rate_limits: BTreeMap::new(),
+            routing: RoutingConfig::default(),
+            fallback: Vec::new(),
         }
     }
 }
@@ -661,6 +1054,46 @@ impl Default for CacheConfig {
             enabled: true,
             cache_dir: PathBuf::from(".litho/cache"),
             expire_hours: 8760,
+            force_regenerate: false,
+            backend: CacheBackend::Directory,
+            max_size_mb: None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_routing_matches_agent_then_cache_scope() {
+        let routing: RoutingConfig = toml::from_str(
+            r#"
+            powerful_threshold_bytes = 65536
+
+            [KeyModulesInsight]
+            model = "gpt-4o"
+
+            [ai_code_purpose]
+            provider = "ollama"
+            model = "qwen3:0.6b"
+
+            [studies_research]
+            temperature = 0.3
+            "#,
+        )
+        .unwrap();
+        assert_eq!(routing.powerful_threshold_bytes, Some(65536));
+
+        let find = |agent, scope| routing.find(agent, scope).map(|(key, _)| key.as_str());
+        assert_eq!(find("KeyModulesInsight", "studies_research/x/domain"), Some("KeyModulesInsight"));
+        assert_eq!(find("CodePurposeEnhancer", "ai_code_purpose"), Some("ai_code_purpose"));
+        assert_eq!(find("WorkflowResearcher", "studies_research/x"), Some("studies_research"));
+        assert_eq!(find("OverviewEditor", "studies_research_other/x"), None);
+
+        let routed = routing.routes["ai_code_purpose"].apply(&LLMConfig::default());
+        assert_eq!(routed.provider, LLMProvider::Ollama);
+        assert_eq!(routed.model_efficient, "qwen3:0.6b");
+        assert_eq!(routed.model_powerful, "qwen3:0.6b");
+    }
+}
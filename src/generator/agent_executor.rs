@@ -2,14 +2,69 @@ use anyhow::Result;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use crate::cache::CacheIdentity;
 use crate::generator::context::GeneratorContext;
-use crate::llm::client::utils::estimate_token_usage;
+use crate::generator::dry_run::{placeholder_text, placeholder_value};
+use crate::llm::client::budget::BudgetExhausted;
+use crate::llm::client::LLMClient;
+use crate::llm::client::types::{LLMResponse, TokenUsage};
 
 pub struct AgentExecuteParams {
     pub prompt_sys: String,
     pub prompt_user: String,
     pub cache_scope: String,
+    /// Agent making the call, routes it together with the cache scope
+    pub agent: String,
     pub log_tag: String,
+    /// Version of the overridden prompt templates the prompts were built from, None for built-in ones
+    pub template_version: Option<String>,
+}
+
+/// Cache key of a call, `kind` tells apart the ways of calling the LLM
+fn cache_key(params: &AgentExecuteParams, kind: Option<&str>) -> String {
+    match kind {
+        Some(kind) => format!("{}|{}|{}", params.prompt_sys, params.prompt_user, kind),
+        None => format!("{}|{}", params.prompt_sys, params.prompt_user),
+    }
+}
+
+/// Identity of a call for its cache key, `schema_hash` is set for structured output
+fn cache_identity(
+    llm_client: &LLMClient,
+    params: &AgentExecuteParams,
+    schema_hash: Option<String>,
+) -> CacheIdentity {
+    let extraction = schema_hash.is_some();
+    CacheIdentity {
+        template_version: params.template_version.clone(),
+        schema_hash,
+        ..llm_client.cache_identity(&params.prompt_sys, &params.prompt_user, extraction)
+    }
+}
+
+/// Wrap an LLM error, budget exhaustion is kept intact so the workflow can stop cleanly
+fn analysis_error(e: anyhow::Error) -> anyhow::Error {
+    if BudgetExhausted::is(&e) {
+        e
+    } else {
+        anyhow::anyhow!("AI analysis failed: {}", e)
+    }
+}
+
+/// Add the usage of a call to the run's ledger, the cache scope identifies the agent
+fn record_usage<T>(
+    context: &GeneratorContext,
+    cache_scope: &str,
+    response: &LLMResponse<T>,
+    token_usage: &TokenUsage,
+) {
+    context.llm_client.usage().record(
+        cache_scope,
+        &response.provider,
+        &response.model,
+        token_usage,
+        response.usage.is_some(),
+    );
 }
 
 pub async fn prompt(context: &GeneratorContext, params: AgentExecuteParams) -> Result<String> {
@@ -17,43 +72,53 @@ pub async fn prompt(context: &GeneratorContext, params: AgentExecuteParams) -> R
     let prompt_user = &params.prompt_user;
     let cache_scope = &params.cache_scope;
     let log_tag = &params.log_tag;
+    let llm_client = context.llm_client.for_agent(&params.agent, cache_scope);
 
-    let prompt_key = format!("{}|{}|reply-prompt", prompt_sys, prompt_user);
+    let prompt_key = cache_key(&params, Some("reply-prompt"));
+    let identity = cache_identity(&llm_client, &params, None);
     // Try to get from cache - Use prompt directly as key, CacheManager will automatically calculate hash
     if let Some(cached_reply) = context
         .cache_manager
         .read()
         .await
-        .get::<serde_json::Value>(cache_scope, &prompt_key)
+        .get_llm_result::<serde_json::Value>(cache_scope, &prompt_key, &identity)
         .await?
     {
         let msg = context.config.target_language.msg_cache_hit().replace("{}", log_tag);
         println!("{}", msg);
+        if let Some(dry_run) = &context.dry_run {
+            dry_run.record(llm_client.llm_config(), &params, true);
+        }
         return Ok(cached_reply.to_string());
     }
 
+    if let Some(dry_run) = &context.dry_run {
+        dry_run.record(llm_client.llm_config(), &params, false);
+        return Ok(placeholder_text());
+    }
+
     let msg = context.config.target_language.msg_ai_analyzing().replace("{}", log_tag);
     println!("{}", msg);
 
-    let reply = context
-        .llm_client
+    let response = llm_client
         .prompt_without_react(prompt_sys, prompt_user)
         .await
-        .map_err(|e| anyhow::anyhow!("AI analysis failed: {}", e))?;
+        .map_err(analysis_error)?;
 
-    // Estimate token usage
+    // Token usage reported by the provider, estimated if it reported none
     let input_text = format!("{} {}", prompt_sys, prompt_user);
-    let token_usage = estimate_token_usage(&input_text, &reply);
+    let token_usage = response.usage_or_estimate(&input_text, &response.content);
+    record_usage(context, cache_scope, &response, &token_usage);
 
     // Cache result - Use method with token information
     context
         .cache_manager
         .write()
         .await
-        .set_with_tokens(cache_scope, &prompt_key, &reply, token_usage)
+        .set_llm_result(cache_scope, &prompt_key, &identity, &response, token_usage)
         .await?;
 
-    Ok(reply)
+    Ok(response.content)
 }
 
 pub async fn prompt_with_tools(
@@ -64,44 +129,53 @@ pub async fn prompt_with_tools(
     let prompt_user = &params.prompt_user;
     let cache_scope = &params.cache_scope;
     let log_tag = &params.log_tag;
+    let llm_client = context.llm_client.for_agent(&params.agent, cache_scope);
 
-    let prompt_key = format!("{}|{}|reply-prompt+tool", prompt_sys, prompt_user);
+    let prompt_key = cache_key(&params, Some("reply-prompt+tool"));
+    let identity = cache_identity(&llm_client, &params, None);
     // Try to get from cache - Use prompt directly as key, CacheManager will automatically calculate hash
     if let Some(cached_reply) = context
         .cache_manager
         .read()
         .await
-        .get::<serde_json::Value>(cache_scope, &prompt_key)
+        .get_llm_result::<serde_json::Value>(cache_scope, &prompt_key, &identity)
         .await?
     {
         let msg = context.config.target_language.msg_cache_hit().replace("{}", log_tag);
         println!("{}", msg);
+        if let Some(dry_run) = &context.dry_run {
+            dry_run.record(llm_client.llm_config(), &params, true);
+        }
         return Ok(cached_reply.to_string());
     }
 
+    if let Some(dry_run) = &context.dry_run {
+        dry_run.record(llm_client.llm_config(), &params, false);
+        return Ok(placeholder_text());
+    }
+
     let msg = context.config.target_language.msg_ai_analyzing().replace("{}", log_tag);
     println!("{}", msg);
 
-    let reply = context
-        .llm_client
+    let response = llm_client
         .prompt(prompt_sys, prompt_user)
         .await
-        .map_err(|e| anyhow::anyhow!("AI analysis failed: {}", e))?;
+        .map_err(analysis_error)?;
 
-    // Estimate token usage
+    // Token usage reported by the provider, estimated if it reported none
     let input_text = format!("{} {}", prompt_sys, prompt_user);
-    let output_text = serde_json::to_string(&reply).unwrap_or_default();
-    let token_usage = estimate_token_usage(&input_text, &output_text);
+    let token_usage = response.usage_or_estimate(&input_text, &response.content);
+    record_usage(context, cache_scope, &response, &token_usage);
 
     // Cache result - Use method with token information
     context
         .cache_manager
         .write()
         .await
-        .set_with_tokens(cache_scope, &prompt_key, &reply, token_usage)
+        .set_llm_result(cache_scope, &prompt_key, &identity, &response, token_usage)
         .await?;
 
-    Ok(reply)
+    Ok(response.content)
 }
 
 pub async fn extract<T>(context: &GeneratorContext, params: AgentExecuteParams) -> Result<T>
@@ -112,42 +186,52 @@ where
     let prompt_user = &params.prompt_user;
     let cache_scope = &params.cache_scope;
     let log_tag = &params.log_tag;
+    let llm_client = context.llm_client.for_agent(&params.agent, cache_scope);
 
-    let prompt_key = format!("{}|{}", prompt_sys, prompt_user);
+    let prompt_key = cache_key(&params, None);
+    let identity = cache_identity(&llm_client, &params, Some(CacheIdentity::schema_hash::<T>()));
     // Try to get from cache - Use prompt directly as key, CacheManager will automatically calculate hash
     if let Some(cached_reply) = context
         .cache_manager
         .read()
         .await
-        .get::<T>(cache_scope, &prompt_key)
+        .get_llm_result::<T>(cache_scope, &prompt_key, &identity)
         .await?
     {
         let msg = context.config.target_language.msg_cache_hit().replace("{}", log_tag);
         println!("{}", msg);
+        if let Some(dry_run) = &context.dry_run {
+            dry_run.record(llm_client.llm_config(), &params, true);
+        }
         return Ok(cached_reply);
     }
 
+    if let Some(dry_run) = &context.dry_run {
+        dry_run.record(llm_client.llm_config(), &params, false);
+        return Ok(serde_json::from_value(placeholder_value::<T>())?);
+    }
+
     let msg = context.config.target_language.msg_ai_analyzing().replace("{}", log_tag);
     println!("{}", msg);
 
-    let reply = context
-        .llm_client
+    let response = llm_client
         .extract::<T>(prompt_sys, prompt_user)
         .await
-        .map_err(|e| anyhow::anyhow!("AI analysis failed: {}", e))?;
+        .map_err(analysis_error)?;
 
-    // Estimate token usage
+    // Token usage reported by the provider, estimated if it reported none. The client recorded every call of the
+    // extraction in the usage ledger already
     let input_text = format!("{} {}", prompt_sys, prompt_user);
-    let output_text = serde_json::to_string(&reply).unwrap_or_default();
-    let token_usage = estimate_token_usage(&input_text, &output_text);
+    let output_text = serde_json::to_string(&response.content).unwrap_or_default();
+    let token_usage = response.usage_or_estimate(&input_text, &output_text);
 
     // Cache result - Use method with token information
     context
         .cache_manager
         .write()
         .await
-        .set_with_tokens(cache_scope, &prompt_key, &reply, token_usage)
+        .set_llm_result(cache_scope, &prompt_key, &identity, &response, token_usage)
         .await?;
 
-    Ok(reply)
+    Ok(response.content)
 }
@@ -0,0 +1,453 @@
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tokio::sync::Mutex;
+
+use crate::config::Config;
+use crate::generator::context::GeneratorContext;
+use crate::generator::incremental::agent_input_digest;
+use crate::generator::step_forward_agent::StepForwardAgent;
+use crate::memory::Memory;
+use crate::utils::project_fingerprint::{ProjectFingerprint, config_digest};
+
+/// Run manifest, records which steps of a run have already finished
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunManifest {
+    pub run_id: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// Digest of the analyzed project content when the run started
+    pub project_hash: String,
+    /// Digest of the configuration when the run started
+    pub config_hash: String,
+    /// Steps whose results have been checkpointed, in completion order
+    pub completed_steps: Vec<String>,
+    /// Whether the whole pipeline finished
+    pub finished: bool,
+}
+
+/// Category and hash of the LLM results read or written by a run, kept next to its manifest
+const CACHE_ENTRIES_FILE: &str = "cache_entries.json";
+
+/// Memory entry produced by a step
+#[derive(Debug, Serialize, Deserialize)]
+struct CheckpointEntry {
+    scope: String,
+    key: String,
+    value: Value,
+}
+
+/// Persisted result of a single step
+#[derive(Debug, Serialize, Deserialize)]
+struct StepCheckpoint {
+    step: String,
+    entries: Vec<CheckpointEntry>,
+}
+
+/// Checkpoint of a pipeline run, stored under `.litho/runs/<run-id>/`
+pub struct RunCheckpoint {
+    /// Run directory, None for runs that must not leave anything on disk (dry runs)
+    dir: Option<PathBuf>,
+    manifest: Mutex<RunManifest>,
+    /// Steps completed before this process started, these are skipped on resume
+    restored_steps: BTreeSet<String>,
+}
+
+impl RunCheckpoint {
+    /// Directory holding all runs
+    pub fn runs_dir(config: &Config) -> PathBuf {
+        config.internal_path.join("runs")
+    }
+
+    /// Start a new run
+    pub fn create(config: &Config) -> Result<Self> {
+        let now = Utc::now();
+        let run_id = format!(
+            "{}-{}",
+            now.format("%Y%m%d-%H%M%S"),
+            &uuid::Uuid::new_v4().simple().to_string()[..8]
+        );
+        let manifest = RunManifest {
+            run_id: run_id.clone(),
+            created_at: now,
+            updated_at: now,
+            project_hash: ProjectFingerprint::scan(config).digest(),
+            config_hash: config_digest(config),
+            completed_steps: Vec::new(),
+            finished: false,
+        };
+
+        let dir = Self::runs_dir(config).join(&run_id);
+        fs::create_dir_all(dir.join("steps"))?;
+        Self::write_manifest(&dir, &manifest)?;
+        println!("🗂️  Run {} checkpoints to {}", run_id, dir.display());
+        Self::prune(config)?;
+
+        Ok(Self {
+            dir: Some(dir),
+            manifest: Mutex::new(manifest),
+            restored_steps: BTreeSet::new(),
+        })
+    }
+
+    /// Checkpoint kept in memory only
+    pub fn ephemeral() -> Self {
+        let now = Utc::now();
+        Self {
+            dir: None,
+            manifest: Mutex::new(RunManifest {
+                run_id: "ephemeral".to_string(),
+                created_at: now,
+                updated_at: now,
+                project_hash: String::new(),
+                config_hash: String::new(),
+                completed_steps: Vec::new(),
+                finished: false,
+            }),
+            restored_steps: BTreeSet::new(),
+        }
+    }
+
+    /// Open an existing run for resuming, the latest unfinished run is used if no id is given
+    pub fn open(config: &Config, run_id: Option<&str>) -> Result<Self> {
+        let manifest = match run_id {
+            Some(run_id) => Self::read_manifest(&Self::runs_dir(config).join(run_id))?,
+            None => Self::list_runs(config)?
+                .into_iter()
+                .filter(|m| !m.finished)
+                .max_by_key(|m| m.created_at)
+                .ok_or_else(|| anyhow!("No unfinished run found in {}", Self::runs_dir(config).display()))?,
+        };
+
+        if manifest.finished {
+            return Err(anyhow!("Run {} has already finished, nothing to resume", manifest.run_id));
+        }
+        if manifest.config_hash != config_digest(config) {
+            return Err(anyhow!(
+                "Configuration changed since run {} was checkpointed, the checkpoint cannot be used",
+                manifest.run_id
+            ));
+        }
+        if manifest.project_hash != ProjectFingerprint::scan(config).digest() {
+            return Err(anyhow!(
+                "Project content changed since run {} was checkpointed, the checkpoint cannot be used",
+                manifest.run_id
+            ));
+        }
+
+        let dir = Self::runs_dir(config).join(&manifest.run_id);
+        let restored_steps = manifest.completed_steps.iter().cloned().collect();
+        println!(
+            "♻️  Resuming run {} ({} steps already completed)",
+            manifest.run_id,
+            manifest.completed_steps.len()
+        );
+
+        Ok(Self {
+            dir: Some(dir),
+            manifest: Mutex::new(manifest),
+            restored_steps,
+        })
+    }
+
+    /// List manifests of all runs
+    pub fn list_runs(config: &Config) -> Result<Vec<RunManifest>> {
+        let runs_dir = Self::runs_dir(config);
+        if !runs_dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut manifests = Vec::new();
+        for entry in fs::read_dir(&runs_dir)?.flatten() {
+            if let Ok(manifest) = Self::read_manifest(&entry.path()) {
+                manifests.push(manifest);
+            }
+        }
+        Ok(manifests)
+    }
+
+    /// Load results of all completed steps into Memory
+    pub fn restore_into(&self, memory: &mut Memory) -> Result<()> {
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+        for step in &self.restored_steps {
+            let content = fs::read_to_string(Self::step_path(dir, step))?;
+            let checkpoint: StepCheckpoint = serde_json::from_str(&content)?;
+            for entry in checkpoint.entries {
+                memory.store(&entry.scope, &entry.key, entry.value)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether a step was completed by the run being resumed
+    pub fn is_completed(&self, step: &str) -> bool {
+        self.restored_steps.contains(step)
+    }
+
+    /// Persist the Memory entries produced by a step and mark it completed
+    pub async fn record(&self, step: &str, memory: &Memory, scope: &str, keys: &[String]) -> Result<()> {
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+        let entries = keys
+            .iter()
+            .filter_map(|key| {
+                memory.get_value(scope, key).map(|value| CheckpointEntry {
+                    scope: scope.to_string(),
+                    key: key.clone(),
+                    value,
+                })
+            })
+            .collect();
+        let checkpoint = StepCheckpoint {
+            step: step.to_string(),
+            entries,
+        };
+        fs::write(Self::step_path(dir, step), serde_json::to_string(&checkpoint)?)?;
+
+        let mut manifest = self.manifest.lock().await;
+        if !manifest.completed_steps.iter().any(|s| s == step) {
+            manifest.completed_steps.push(step.to_string());
+        }
+        manifest.updated_at = Utc::now();
+        Self::write_manifest(dir, &manifest)
+    }
+
+    /// Mark the whole run as finished
+    pub async fn finish(&self) -> Result<()> {
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+        let mut manifest = self.manifest.lock().await;
+        manifest.finished = true;
+        manifest.updated_at = Utc::now();
+        Self::write_manifest(dir, &manifest)
+    }
+
+    /// Delete the runs older than the latest `keep_runs`
+    fn prune(config: &Config) -> Result<()> {
+        let mut runs = Self::list_runs(config)?;
+        runs.sort_by_key(|manifest| std::cmp::Reverse(manifest.created_at));
+        for manifest in runs.iter().skip(config.keep_runs.max(1)) {
+            fs::remove_dir_all(Self::runs_dir(config).join(&manifest.run_id))?;
+        }
+        Ok(())
+    }
+
+    /// Add cache entries to those the run used, `cache export --since` exports them
+    pub async fn record_cache_entries(&self, entries: &BTreeSet<(String, String)>) -> Result<()> {
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+        // Serialized with the manifest so steps finishing together do not lose each other's entries
+        let _manifest = self.manifest.lock().await;
+        let path = dir.join(CACHE_ENTRIES_FILE);
+        let mut recorded = read_cache_entries(&path)?.unwrap_or_default();
+        let count = recorded.len();
+        recorded.extend(entries.iter().cloned());
+        if recorded.len() != count || !path.exists() {
+            fs::write(&path, serde_json::to_string(&recorded)?)?;
+        }
+        Ok(())
+    }
+
+    /// Cache entries a run used, None for runs that did not record them
+    pub fn cache_entries(config: &Config, run_id: &str) -> Result<Option<BTreeSet<(String, String)>>> {
+        read_cache_entries(&Self::runs_dir(config).join(run_id).join(CACHE_ENTRIES_FILE))
+    }
+
+    fn step_path(dir: &Path, step: &str) -> PathBuf {
+        let file_name: String = step
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '_' })
+            .collect();
+        dir.join("steps").join(format!("{}.json", file_name))
+    }
+
+    fn read_manifest(dir: &Path) -> Result<RunManifest> {
+        let content = fs::read_to_string(dir.join("manifest.json"))?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    fn write_manifest(dir: &Path, manifest: &RunManifest) -> Result<()> {
+        fs::write(dir.join("manifest.json"), serde_json::to_string_pretty(manifest)?)?;
+        Ok(())
+    }
+}
+
+fn read_cache_entries(path: &Path) -> Result<Option<BTreeSet<(String, String)>>> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(Some(serde_json::from_str(&content)?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Execute an agent unless the resumed run already completed it, then checkpoint its result
+///
+/// In incremental mode the agent's previous result is reused when its inputs are unchanged since the last run.
+pub async fn execute_with_checkpoint<T>(agent: &T, context: &GeneratorContext) -> Result<()>
+where
+    T: StepForwardAgent + Send + Sync,
+{
+    let scope = agent.memory_scope_key();
+    let key = agent.agent_type();
+    let step = format!("{}/{}", scope, key);
+
+    if agent.is_optional()
+        && !context.checkpoint.is_completed(&step)
+        && context.llm_client.budget().is_degraded()
+    {
+        println!("⏭️  [{}] skipped to save LLM budget", key);
+        return Ok(());
+    }
+
+    let digest = match &context.incremental {
+        Some(_) => Some(agent_input_digest(agent, context).await?),
+        None => None,
+    };
+
+    if context.checkpoint.is_completed(&step) {
+        println!("⏭️  [{}] restored from checkpoint, skipping", key);
+    } else if let Some(outputs) = context
+        .incremental
+        .as_ref()
+        .zip(digest.as_ref())
+        .and_then(|(incremental, digest)| incremental.reusable_outputs(&step, &scope, digest))
+    {
+        println!("♻️  [{}] inputs unchanged since the last run, reusing previous result", key);
+        for (key, value) in outputs {
+            context.store_to_memory(&scope, &key, value).await?;
+        }
+        context.checkpoint_step(&step, &scope, &agent.output_keys(context).await).await?;
+    } else {
+        agent.execute(context).await?;
+        context.checkpoint_step(&step, &scope, &agent.output_keys(context).await).await?;
+    }
+
+    if let (Some(incremental), Some(digest)) = (&context.incremental, digest) {
+        incremental.record_step(&step, digest, agent.output_keys(context).await).await;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_config(root: &Path) -> Config {
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/main.rs"), "fn main() {}").unwrap();
+        Config {
+            project_path: root.to_path_buf(),
+            internal_path: root.join(".litho"),
+            ..Config::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resume_restores_completed_steps() {
+        let root = std::env::temp_dir().join(format!("litho-checkpoint-{}", uuid::Uuid::new_v4()));
+        let config = test_config(&root);
+
+        let run = RunCheckpoint::create(&config).unwrap();
+        let mut memory = Memory::new();
+        memory.store("studies_research", "SystemContextResearcher", "context").unwrap();
+        let keys = ["SystemContextResearcher".to_string()];
+        run.record("studies_research/SystemContextResearcher", &memory, "studies_research", &keys)
+            .await
+            .unwrap();
+
+        let resumed = RunCheckpoint::open(&config, None).unwrap();
+        assert!(resumed.is_completed("studies_research/SystemContextResearcher"));
+        assert!(!resumed.is_completed("studies_research/DomainModulesDetector"));
+        let mut restored = Memory::new();
+        resumed.restore_into(&mut restored).unwrap();
+        assert_eq!(
+            restored.get_untracked::<String>("studies_research", "SystemContextResearcher"),
+            Some("context".to_string())
+        );
+
+        // A finished run or a changed project cannot be resumed
+        resumed.finish().await.unwrap();
+        assert!(RunCheckpoint::open(&config, None).is_err());
+        let run = RunCheckpoint::create(&config).unwrap();
+        std::fs::write(root.join("src/main.rs"), "fn main() { changed() }").unwrap();
+        assert!(RunCheckpoint::open(&config, Some(&run.manifest.lock().await.run_id)).is_err());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_old_runs_are_pruned() {
+        let root = std::env::temp_dir().join(format!("litho-checkpoint-{}", uuid::Uuid::new_v4()));
+        let config = Config {
+            keep_runs: 2,
+            ..test_config(&root)
+        };
+
+        let first = RunCheckpoint::create(&config).unwrap();
+        let first_dir = first.dir.clone().unwrap();
+        for _ in 0..2 {
+            RunCheckpoint::create(&config).unwrap();
+        }
+        assert_eq!(RunCheckpoint::list_runs(&config).unwrap().len(), 2);
+        assert!(!first_dir.exists());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_resume_restores_the_report_of_every_domain() {
+        use crate::generator::research::agents::key_modules_insight::KeyModulesInsight;
+        use crate::generator::research::memory::{MemoryRetriever, MemoryScope};
+        use std::sync::Arc;
+        use tokio::sync::RwLock;
+
+        let root = std::env::temp_dir().join(format!("litho-checkpoint-{}", uuid::Uuid::new_v4()));
+        let config = test_config(&root);
+        let context = GeneratorContext {
+            checkpoint: Arc::new(RunCheckpoint::create(&config).unwrap()),
+            ..GeneratorContext::for_test(config.clone())
+        };
+
+        // What KeyModulesInsight stores once its domains are analyzed
+        let agent = KeyModulesInsight;
+        let report = serde_json::json!({
+            "domain_name": "Billing",
+            "module_name": "Invoices",
+            "module_description": "",
+            "interaction": "",
+            "implementation": "",
+            "associated_files": [],
+            "flowchart_mermaid": "",
+            "sequence_diagram_mermaid": "",
+        });
+        let domain_key = KeyModulesInsight::domain_key("Billing");
+        context.store_research(&domain_key, report.clone()).await.unwrap();
+        context.store_research(&agent.agent_type(), serde_json::json!([report])).await.unwrap();
+        let keys = agent.output_keys(&context).await;
+        assert_eq!(keys, vec![agent.agent_type(), domain_key.clone()]);
+        let step = format!("{}/{}", MemoryScope::STUDIES_RESEARCH, agent.agent_type());
+        context.checkpoint_step(&step, MemoryScope::STUDIES_RESEARCH, &keys).await.unwrap();
+
+        let resumed = RunCheckpoint::open(&config, None).unwrap();
+        let mut memory = Memory::new();
+        resumed.restore_into(&mut memory).unwrap();
+        let resumed = GeneratorContext {
+            checkpoint: Arc::new(resumed),
+            memory: Arc::new(RwLock::new(memory)),
+            ..GeneratorContext::for_test(config.clone())
+        };
+        execute_with_checkpoint(&agent, &resumed).await.unwrap();
+        // The module editors of the compose stage read the report of their domain
+        assert_eq!(resumed.get_research(&domain_key).await, Some(report));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -1,7 +1,9 @@
 use crate::generator::compose::memory::MemoryScope;
+use crate::generator::checkpoint::execute_with_checkpoint;
 use crate::generator::context::GeneratorContext;
 use crate::generator::outlet::DocTree;
 use crate::generator::research::memory::MemoryRetriever;
+use crate::generator::research::agents::key_modules_insight::KeyModulesInsight;
 use crate::generator::research::types::{AgentType as ResearchAgentType, KeyModuleReport};
 use crate::generator::step_forward_agent::{
     AgentDataConfig, DataSource, FormatterConfig, LLMCallMode, PromptTemplate, StepForwardAgent,
@@ -30,17 +32,13 @@ impl KeyModulesInsightEditor {
             let analysis_futures: Vec<_> = insight_reports
                 .into_iter()
                 .map(|insight_report| {
-                    let insight_key = format!(
-                        "{}_{}",
-                        ResearchAgentType::KeyModulesInsight,
-                        &insight_report.domain_name
-                    );
+                    let insight_key = KeyModulesInsight::domain_key(&insight_report.domain_name);
                     let domain_name = insight_report.domain_name.clone();
-                    let kmie = KeyModuleInsightEditor::new(insight_key.clone(), insight_report);
+                    let kmie = KeyModuleInsightEditor::new(insight_key.clone(), domain_name.clone());
                     let context_clone = context.clone();
 
                     Box::pin(async move {
-                        let result = kmie.execute(&context_clone).await;
+                        let result = execute_with_checkpoint(&kmie, &context_clone).await;
                         (insight_key, domain_name, result)
                     })
                 })
@@ -55,25 +53,52 @@ impl KeyModulesInsightEditor {
 
                 doc_tree.insert(
                     &insight_key,
-                    format!("{}/{}.md", context.config.target_language.get_directory_name("deep_exploration"), &domain_name).as_str(),
+                    &Self::doc_relative_path(context, &domain_name),
                 );
             }
         }
 
         Ok(())
     }
+
+    /// Register documents of previously generated modules, used when documentation is restored from a snapshot
+    pub async fn restore_doc_tree(&self, context: &GeneratorContext, doc_tree: &mut DocTree) {
+        let prefix = format!("{}_", ResearchAgentType::KeyModulesInsight);
+        for key in context.list_memory_keys(MemoryScope::DOCUMENTATION).await {
+            if let Some(domain_name) = key.strip_prefix(&prefix) {
+                doc_tree.insert(&key, &Self::doc_relative_path(context, domain_name));
+            }
+        }
+    }
+
+    /// Agent name and default prompt template of the per-domain editor
+    pub fn domain_editor_template() -> (String, PromptTemplate) {
+        let editor = KeyModuleInsightEditor::new(String::new(), String::new());
+        (editor.agent_name(), editor.prompt_template())
+    }
+
+    fn doc_relative_path(context: &GeneratorContext, domain_name: &str) -> String {
+        format!(
+            "{}/{}.md",
+            context
+                .config
+                .target_language
+                .get_directory_name("deep_exploration"),
+            domain_name
+        )
+    }
 }
 
 struct KeyModuleInsightEditor {
     insight_key: String,
-    report: KeyModuleReport,
+    domain_name: String,
 }
 
 impl KeyModuleInsightEditor {
-    fn new(insight_key: String, report: KeyModuleReport) -> Self {
+    fn new(insight_key: String, domain_name: String) -> Self {
         KeyModuleInsightEditor {
             insight_key,
-            report,
+            domain_name,
         }
     }
 }
@@ -107,19 +132,20 @@ impl StepForwardAgent for KeyModuleInsightEditor {
         }
     }
 
+    fn prompt_variables(&self) -> Vec<(&'static str, String)> {
+        vec![("domain_name", self.domain_name.clone())]
+    }
+
     fn prompt_template(&self) -> PromptTemplate {
-        let report = &self.report;
-        let opening_instruction = format!(
-            r#"The topic you need to analyze is: {}
+        let opening_instruction = r#"The topic you need to analyze is: {{domain_name}}
             ## Documentation Quality Requirements:
-            1. **Completeness**: Based on research materials, cover all important aspects of the topic `{}`, without omitting key information
+            1. **Completeness**: Based on research materials, cover all important aspects of the topic `{{domain_name}}`, without omitting key information
             2. **Accuracy**: Based on research data, ensure accuracy of technical details
             3. **Professionalism**: Use standard architecture terminology and expressions
             4. **Readability**: Clear structure, rich language narrative, and easy to understand
             5. **Practicality**: Provide valuable module knowledge and technical implementation details.
-            "#,
-            &report.domain_name, &report.domain_name
-        );
+            "#
+        .to_string();
 
         PromptTemplate {
             system_prompt: r#"You are a software expert skilled at writing technical documentation. Based on the research materials and requirements provided by users, write technical documentation for the technical implementation of corresponding modules in existing projects"#.to_string(),
@@ -4,14 +4,17 @@ use crate::generator::compose::agents::database_editor::DatabaseEditor;
 use crate::generator::compose::agents::key_modules_insight_editor::KeyModulesInsightEditor;
 use crate::generator::compose::agents::overview_editor::OverviewEditor;
 use crate::generator::compose::agents::workflow_editor::WorkflowEditor;
+use crate::config::CustomAgentStage;
+use crate::generator::checkpoint::execute_with_checkpoint;
 use crate::generator::context::GeneratorContext;
+use crate::generator::custom_agent::CustomAgent;
+use crate::generator::step_forward_agent::StepForwardAgent;
 use crate::generator::outlet::DocTree;
 use crate::generator::preprocess::memory::{MemoryScope, ScopedKeys};
-use crate::generator::step_forward_agent::StepForwardAgent;
 use crate::types::code::{CodeInsight, CodePurpose};
 use anyhow::Result;
 
-mod agents;
+pub mod agents;
 pub mod memory;
 pub mod types;
 
@@ -25,13 +28,13 @@ impl DocumentationComposer {
         println!("📝 Target language: {}", context.config.target_language.display_name());
 
         let overview_editor = OverviewEditor::default();
-        overview_editor.execute(context).await?;
+        execute_with_checkpoint(&overview_editor, context).await?;
 
         let architecture_editor = ArchitectureEditor::default();
-        architecture_editor.execute(context).await?;
+        execute_with_checkpoint(&architecture_editor, context).await?;
 
         let workflow_editor = WorkflowEditor::default();
-        workflow_editor.execute(context).await?;
+        execute_with_checkpoint(&workflow_editor, context).await?;
 
         let key_modules_insight_editor = KeyModulesInsightEditor::default();
         key_modules_insight_editor
@@ -39,17 +42,33 @@ impl DocumentationComposer {
             .await?;
 
         let boundary_editor = BoundaryEditor::default();
-        boundary_editor.execute(context).await?;
+        execute_with_checkpoint(&boundary_editor, context).await?;
 
         // Database documentation (only if database files exist)
         if self.has_database_files(context).await {
             let database_editor = DatabaseEditor::default();
-            database_editor.execute(context).await?;
+            execute_with_checkpoint(&database_editor, context).await?;
+        }
+
+        // User-defined documents declared in litho.toml
+        for agent in CustomAgent::for_stage(&context.config.custom_agents, CustomAgentStage::Compose) {
+            execute_with_checkpoint(&agent, context).await?;
+            doc_tree.insert(&agent.agent_type(), &agent.output_file());
         }
 
         Ok(())
     }
 
+    /// Rebuild the dynamic part of the document tree from documentation restored into Memory
+    pub async fn restore_doc_tree(&self, context: &GeneratorContext, doc_tree: &mut DocTree) {
+        KeyModulesInsightEditor::default()
+            .restore_doc_tree(context, doc_tree)
+            .await;
+        for agent in CustomAgent::for_stage(&context.config.custom_agents, CustomAgentStage::Compose) {
+            doc_tree.insert(&agent.agent_type(), &agent.output_file());
+        }
+    }
+
     /// Check if the project has database-related files
     async fn has_database_files(&self, context: &GeneratorContext) -> bool {
         if let Some(insights) = context
@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
 
 use anyhow::Result;
@@ -8,8 +9,12 @@ use tokio::sync::RwLock;
 use crate::{
     cache::CacheManager, 
     config::Config, 
+    generator::{
+        checkpoint::RunCheckpoint, dry_run::DryRunRecorder, incremental::IncrementalRun,
+        prompt_templates::PromptTemplates,
+    },
     llm::client::LLMClient, 
-    memory::Memory,
+    memory::{Memory, MemorySnapshot},
 };
 
 #[derive(Clone)]
@@ -22,6 +27,14 @@ pub struct GeneratorContext {
     pub cache_manager: Arc<RwLock<CacheManager>>,
     /// Generator memory
     pub memory: Arc<RwLock<Memory>>,
+    /// Checkpoint of the current run
+    pub checkpoint: Arc<RunCheckpoint>,
+    /// Incremental run state, only present in incremental mode
+    pub incremental: Option<Arc<IncrementalRun>>,
+    /// Records LLM calls instead of sending them, only present in dry-run mode
+    pub dry_run: Option<Arc<DryRunRecorder>>,
+    /// Prompt templates, with the overrides of `prompts_dir`
+    pub prompt_templates: Arc<PromptTemplates>,
 }
 
 impl GeneratorContext {
@@ -55,6 +68,52 @@ impl GeneratorContext {
         memory.list_keys(scope)
     }
 
+    /// Directory holding persisted stage snapshots
+    pub fn snapshot_dir(&self) -> PathBuf {
+        self.config.internal_path.join("snapshots")
+    }
+
+    /// Persist all data of a Memory scope to the snapshot directory
+    pub async fn save_memory_snapshot(&self, scope: &str) -> Result<()> {
+        let entries = self.memory.read().await.export_scope(scope);
+        let snapshot = MemorySnapshot {
+            scope: scope.to_string(),
+            created_at: chrono::Utc::now(),
+            entries,
+        };
+        let path = snapshot.save(&self.snapshot_dir())?;
+        println!("💾 Snapshot of [{}] saved to {}", scope, path.display());
+        if let Some(incremental) = &self.incremental {
+            incremental.save().await?;
+        }
+        Ok(())
+    }
+
+    /// Restore a Memory scope from its snapshot, returns false if no snapshot exists
+    pub async fn restore_memory_snapshot(&self, scope: &str) -> Result<bool> {
+        match MemorySnapshot::load(&self.snapshot_dir(), scope)? {
+            Some(snapshot) => {
+                let mut memory = self.memory.write().await;
+                memory.import_scope(scope, snapshot.entries)?;
+                println!(
+                    "♻️  Restored [{}] from snapshot created at {}",
+                    scope,
+                    snapshot.created_at.format("%Y-%m-%d %H:%M:%S UTC")
+                );
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Checkpoint the Memory entries produced by a finished step
+    pub async fn checkpoint_step(&self, step: &str, scope: &str, keys: &[String]) -> Result<()> {
+        let memory = self.memory.read().await;
+        self.checkpoint.record(step, &memory, scope, keys).await?;
+        let used = self.cache_manager.read().await.used_llm_entries();
+        self.checkpoint.record_cache_entries(&used).await
+    }
+
     /// Get Memory usage statistics
     pub async fn get_memory_stats(&self) -> HashMap<String, usize> {
         let memory = self.memory.read().await;
@@ -96,3 +155,28 @@ impl GeneratorContext {
         }
     }
 }
+
+#[cfg(test)]
+impl GeneratorContext {
+    /// Context of a test run, with an empty Memory and a checkpoint that is not persisted
+    pub fn for_test(config: Config) -> Self {
+        let llm_client = LLMClient::new(config.clone()).unwrap();
+        Self {
+            cache_manager: Arc::new(RwLock::new(
+                CacheManager::new(
+                    config.cache.clone(),
+                    config.target_language.clone(),
+                    llm_client.pricing(),
+                )
+                .unwrap(),
+            )),
+            llm_client,
+            prompt_templates: Arc::new(PromptTemplates::load(&config).unwrap()),
+            config,
+            memory: Arc::new(RwLock::new(Memory::new())),
+            checkpoint: Arc::new(RunCheckpoint::ephemeral()),
+            incremental: None,
+            dry_run: None,
+        }
+    }
+}
@@ -0,0 +1,294 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::path::{Component, Path};
+
+use anyhow::{Result, anyhow};
+use schemars::{JsonSchema, Schema, SchemaGenerator, json_schema};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::config::{CustomAgentCallMode, CustomAgentConfig, CustomAgentSource, CustomAgentStage};
+use crate::generator::compose::memory::MemoryScope as ComposeMemoryScope;
+use crate::generator::compose::types::AgentType as ComposeAgentType;
+use crate::generator::prompt_templates::builtin_agent_names;
+use crate::generator::research::memory::MemoryScope as ResearchMemoryScope;
+use crate::generator::research::types::AgentType as ResearchAgentType;
+use crate::generator::step_forward_agent::{
+    AgentDataConfig, DataSource, FormatterConfig, LLMCallMode, PromptTemplate, StepForwardAgent,
+};
+
+/// Result of a custom agent, text in prompt modes and a free-form JSON object in extract mode
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CustomAgentOutput(pub Value);
+
+impl JsonSchema for CustomAgentOutput {
+    fn schema_name() -> Cow<'static, str> {
+        "CustomAgentOutput".into()
+    }
+
+    fn json_schema(_generator: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "type": "object",
+            "additionalProperties": true
+        })
+    }
+}
+
+/// Agent declared in the `custom_agents` section of litho.toml
+pub struct CustomAgent {
+    config: CustomAgentConfig,
+}
+
+impl CustomAgent {
+    pub fn new(config: CustomAgentConfig) -> Self {
+        Self { config }
+    }
+
+    /// Custom agents of a stage, in declaration order
+    pub fn for_stage(configs: &[CustomAgentConfig], stage: CustomAgentStage) -> Vec<Self> {
+        configs
+            .iter()
+            .filter(|c| c.stage == stage)
+            .cloned()
+            .map(Self::new)
+            .collect()
+    }
+
+    /// Check custom agent declarations before the pipeline starts
+    pub fn validate(configs: &[CustomAgentConfig]) -> Result<()> {
+        let builtin_names = builtin_names();
+        let mut names = HashSet::new();
+        for config in configs {
+            if config.name.trim().is_empty() {
+                return Err(anyhow!("Custom agent name must not be empty"));
+            }
+            if !names.insert(config.name.as_str()) {
+                return Err(anyhow!("Custom agent [{}] is declared more than once", config.name));
+            }
+            if config.name.contains(['/', '\\']) || config.name.starts_with('.') {
+                return Err(anyhow!(
+                    "Custom agent [{}] names a file, it must not contain path separators or start with a dot",
+                    config.name
+                ));
+            }
+            if builtin_names.iter().any(|builtin| builtin.eq_ignore_ascii_case(&config.name)) {
+                return Err(anyhow!(
+                    "Custom agent [{}] takes the name of a built-in agent or of its result, choose another name",
+                    config.name
+                ));
+            }
+            if let Some(output_file) = &config.output_file {
+                let path = Path::new(output_file);
+                let inside_output = path
+                    .components()
+                    .all(|component| matches!(component, Component::Normal(_)));
+                if output_file.trim().is_empty() || !inside_output {
+                    return Err(anyhow!(
+                        "Custom agent [{}] has output_file [{}], expected a relative path inside the output directory",
+                        config.name,
+                        output_file
+                    ));
+                }
+            }
+            if config.stage == CustomAgentStage::Compose
+                && config.llm_call_mode == CustomAgentCallMode::Extract
+            {
+                return Err(anyhow!(
+                    "Custom agent [{}] writes a document and cannot use the extract call mode",
+                    config.name
+                ));
+            }
+            for source in config.required_sources.iter().chain(&config.optional_sources) {
+                if let CustomAgentSource::Memory(key) = source {
+                    memory_source(key).ok_or_else(|| {
+                        anyhow!(
+                            "Custom agent [{}] uses unknown memory source [{}], expected one of project_structure, code_insights, relationships, original_document, git_history",
+                            config.name,
+                            key
+                        )
+                    })?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Output file of a compose agent, relative to the output directory
+    pub fn output_file(&self) -> String {
+        self.config
+            .output_file
+            .clone()
+            .unwrap_or_else(|| format!("{}.md", self.config.name))
+    }
+
+    fn data_sources(sources: &[CustomAgentSource]) -> Vec<DataSource> {
+        sources
+            .iter()
+            .filter_map(|source| match source {
+                CustomAgentSource::Memory(key) => memory_source(key),
+                CustomAgentSource::Research(name) => Some(DataSource::ResearchResult(research_key(name))),
+                CustomAgentSource::Knowledge(categories) => {
+                    Some(DataSource::ExternalKnowledgeByCategory(categories.clone()))
+                }
+            })
+            .collect()
+    }
+}
+
+impl StepForwardAgent for CustomAgent {
+    type Output = CustomAgentOutput;
+
+    fn agent_type(&self) -> String {
+        self.config.name.clone()
+    }
+
+    fn agent_name(&self) -> String {
+        self.config.name.clone()
+    }
+
+    fn memory_scope_key(&self) -> String {
+        match self.config.stage {
+            CustomAgentStage::Research => ResearchMemoryScope::STUDIES_RESEARCH.to_string(),
+            CustomAgentStage::Compose => ComposeMemoryScope::DOCUMENTATION.to_string(),
+        }
+    }
+
+    fn should_include_timestamp(&self) -> bool {
+        self.config.stage == CustomAgentStage::Compose
+    }
+
+    fn data_config(&self) -> AgentDataConfig {
+        AgentDataConfig {
+            required_sources: Self::data_sources(&self.config.required_sources),
+            optional_sources: Self::data_sources(&self.config.optional_sources),
+        }
+    }
+
+    fn prompt_template(&self) -> PromptTemplate {
+        PromptTemplate {
+            system_prompt: self.config.system_prompt.clone(),
+            opening_instruction: self.config.opening_instruction.clone(),
+            closing_instruction: self.config.closing_instruction.clone(),
+            llm_call_mode: match self.config.llm_call_mode {
+                CustomAgentCallMode::Extract => LLMCallMode::Extract,
+                CustomAgentCallMode::Prompt => LLMCallMode::Prompt,
+                CustomAgentCallMode::PromptWithTools => LLMCallMode::PromptWithTools,
+            },
+            formatter_config: FormatterConfig::default(),
+        }
+    }
+}
+
+/// Preprocessing result referenced by its Memory key
+fn memory_source(key: &str) -> Option<DataSource> {
+    match key {
+        "project_structure" => Some(DataSource::PROJECT_STRUCTURE),
+        "code_insights" => Some(DataSource::CODE_INSIGHTS),
+        "relationships" => Some(DataSource::DEPENDENCY_ANALYSIS),
+        "original_document" => Some(DataSource::README_CONTENT),
+        "git_history" => Some(DataSource::GIT_HISTORY),
+        _ => None,
+    }
+}
+
+/// Names of the built-in agents and of the results and documents they produce. A custom agent taking one
+/// would share its memory key, cache scope and prompt templates
+fn builtin_names() -> Vec<String> {
+    let research = [
+        ResearchAgentType::SystemContextResearcher,
+        ResearchAgentType::DomainModulesDetector,
+        ResearchAgentType::ArchitectureResearcher,
+        ResearchAgentType::WorkflowResearcher,
+        ResearchAgentType::KeyModulesInsight,
+        ResearchAgentType::BoundaryAnalyzer,
+        ResearchAgentType::DatabaseOverviewAnalyzer,
+    ]
+    .into_iter()
+    .flat_map(|agent_type| [format!("{:?}", agent_type), agent_type.to_string()]);
+    let compose = [
+        ComposeAgentType::Overview,
+        ComposeAgentType::Architecture,
+        ComposeAgentType::Workflow,
+        ComposeAgentType::Boundary,
+        ComposeAgentType::Database,
+    ]
+    .into_iter()
+    .flat_map(|agent_type| [format!("{:?}", agent_type), agent_type.to_string()]);
+    research.chain(compose).chain(builtin_agent_names()).collect()
+}
+
+/// Memory key of a research result, built-in agents may be referenced by their type name
+fn research_key(name: &str) -> String {
+    let agent_type = match name {
+        "SystemContextResearcher" => ResearchAgentType::SystemContextResearcher,
+        "DomainModulesDetector" => ResearchAgentType::DomainModulesDetector,
+        "ArchitectureResearcher" => ResearchAgentType::ArchitectureResearcher,
+        "WorkflowResearcher" => ResearchAgentType::WorkflowResearcher,
+        "KeyModulesInsight" => ResearchAgentType::KeyModulesInsight,
+        "BoundaryAnalyzer" => ResearchAgentType::BoundaryAnalyzer,
+        "DatabaseOverviewAnalyzer" => ResearchAgentType::DatabaseOverviewAnalyzer,
+        _ => return name.to_string(),
+    };
+    agent_type.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn agent(name: &str) -> CustomAgentConfig {
+        CustomAgentConfig {
+            name: name.to_string(),
+            stage: CustomAgentStage::Compose,
+            required_sources: vec![],
+            optional_sources: vec![],
+            system_prompt: "You document security".to_string(),
+            opening_instruction: String::new(),
+            closing_instruction: String::new(),
+            llm_call_mode: CustomAgentCallMode::Prompt,
+            output_file: None,
+        }
+    }
+
+    fn with_output(name: &str, output_file: &str) -> CustomAgentConfig {
+        CustomAgentConfig {
+            output_file: Some(output_file.to_string()),
+            ..agent(name)
+        }
+    }
+
+    #[test]
+    fn test_validate_custom_agents() {
+        assert!(CustomAgent::validate(&[agent("SecurityReview"), with_output("Glossary", "guides/glossary.md")]).is_ok());
+
+        assert!(CustomAgent::validate(&[agent("")]).is_err());
+        assert!(CustomAgent::validate(&[agent("SecurityReview"), agent("SecurityReview")]).is_err());
+        assert!(CustomAgent::validate(&[agent("../SecurityReview")]).is_err());
+
+        // Built-in agents, their results and their documents
+        for builtin in ["Overview", "overview", "OverviewEditor", "KeyModulesInsight", "System Context Research Report"] {
+            assert!(CustomAgent::validate(&[agent(builtin)]).is_err(), "{} accepted", builtin);
+        }
+
+        for output_file in ["../../etc/x.md", "/tmp/x.md", "guides/../../x.md", ""] {
+            assert!(
+                CustomAgent::validate(&[with_output("SecurityReview", output_file)]).is_err(),
+                "{} accepted",
+                output_file
+            );
+        }
+
+        let extract = CustomAgentConfig {
+            llm_call_mode: CustomAgentCallMode::Extract,
+            ..agent("SecurityReview")
+        };
+        assert!(CustomAgent::validate(&[extract]).is_err());
+
+        let unknown_source = CustomAgentConfig {
+            required_sources: vec![CustomAgentSource::Memory("secrets".to_string())],
+            ..agent("SecurityReview")
+        };
+        assert!(CustomAgent::validate(&[unknown_source]).is_err());
+    }
+}
@@ -0,0 +1,331 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use anyhow::Result;
+use schemars::JsonSchema;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use tokio::sync::RwLock;
+
+use crate::cache::CacheManager;
+use crate::config::{Config, LLMConfig};
+use crate::generator::agent_executor::AgentExecuteParams;
+use crate::generator::checkpoint::RunCheckpoint;
+use crate::generator::compose::DocumentationComposer;
+use crate::generator::context::GeneratorContext;
+use crate::generator::custom_agent::CustomAgent;
+use crate::generator::outlet::DocTree;
+use crate::generator::preprocess::PreProcessAgent;
+use crate::generator::prompt_templates::PromptTemplates;
+use crate::generator::research::orchestrator::ResearchOrchestrator;
+use crate::generator::types::Generator;
+use crate::llm::client::LLMClient;
+use crate::llm::client::pricing::PricingTable;
+use crate::llm::client::types::TokenUsage;
+use crate::llm::client::utils::evaluate_befitting_model;
+use crate::memory::Memory;
+use crate::utils::token_estimator::TokenEstimator;
+
+/// Assumed number of output tokens per LLM call, replies are unknown without calling a provider
+const ESTIMATED_OUTPUT_TOKENS: usize = 1500;
+
+/// An LLM call that would have been sent
+#[derive(Debug, Clone, Serialize)]
+pub struct DryRunCall {
+    pub stage: String,
+    pub log_tag: String,
+    pub cache_scope: String,
+    /// Model chosen by `evaluate_befitting_model`
+    pub model: String,
+    pub input_tokens: usize,
+    pub output_tokens: usize,
+    /// Whether the reply is already cached, cached calls cost nothing
+    pub cached: bool,
+    pub cost: f64,
+}
+
+/// Collects the LLM calls of a dry run
+pub struct DryRunRecorder {
+    stage: Mutex<String>,
+    calls: Mutex<Vec<DryRunCall>>,
+    token_estimator: TokenEstimator,
+    pricing: Arc<PricingTable>,
+}
+
+impl DryRunRecorder {
+    pub fn new(pricing: Arc<PricingTable>) -> Self {
+        Self {
+            stage: Mutex::new(String::new()),
+            calls: Mutex::new(Vec::new()),
+            token_estimator: TokenEstimator::new(),
+            pricing,
+        }
+    }
+
+    /// Stage that subsequent calls are attributed to
+    pub fn set_stage(&self, stage: &str) {
+        *self.stage.lock().unwrap() = stage.to_string();
+    }
+
+    /// Record a call instead of sending it
+    pub fn record(&self, llm_config: &LLMConfig, params: &AgentExecuteParams, cached: bool) {
+        let (model, _) = evaluate_befitting_model(llm_config, &params.prompt_sys, &params.prompt_user);
+        let input_tokens = self
+            .token_estimator
+            .estimate_tokens(&format!("{} {}", params.prompt_sys, params.prompt_user))
+            .estimated_tokens;
+        let output_tokens = ESTIMATED_OUTPUT_TOKENS;
+        let cost = if cached {
+            0.0
+        } else {
+            self
+                .pricing
+                .cost(
+                    &TokenUsage::new(input_tokens, output_tokens),
+                    &llm_config.provider.to_string(),
+                    &model,
+                )
+        };
+
+        let call = DryRunCall {
+            stage: self.stage.lock().unwrap().clone(),
+            log_tag: params.log_tag.clone(),
+            cache_scope: params.cache_scope.clone(),
+            model,
+            input_tokens,
+            output_tokens,
+            cached,
+            cost,
+        };
+        println!(
+            "   🧪 [{}] {} input tokens -> {}{}",
+            call.log_tag,
+            call.input_tokens,
+            call.model,
+            if cached { " (cached)" } else { "" }
+        );
+        self.calls.lock().unwrap().push(call);
+    }
+
+    /// All recorded calls
+    pub fn calls(&self) -> Vec<DryRunCall> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// Print per stage and per model totals
+    pub fn print_report(&self) {
+        let calls = self.calls();
+
+        #[derive(Default)]
+        struct Totals {
+            calls: usize,
+            cached: usize,
+            input_tokens: usize,
+            output_tokens: usize,
+            cost: f64,
+        }
+        impl Totals {
+            fn add(&mut self, call: &DryRunCall) {
+                self.calls += 1;
+                if call.cached {
+                    self.cached += 1;
+                } else {
+                    self.input_tokens += call.input_tokens;
+                    self.output_tokens += call.output_tokens;
+                    self.cost += call.cost;
+                }
+            }
+        }
+
+        let mut stages: Vec<(String, Totals)> = Vec::new();
+        let mut models: BTreeMap<String, Totals> = BTreeMap::new();
+        let mut total = Totals::default();
+        for call in &calls {
+            match stages.iter_mut().find(|(stage, _)| stage == &call.stage) {
+                Some((_, totals)) => totals.add(call),
+                None => {
+                    let mut totals = Totals::default();
+                    totals.add(call);
+                    stages.push((call.stage.clone(), totals));
+                }
+            }
+            models.entry(call.model.clone()).or_default().add(call);
+            total.add(call);
+        }
+
+        println!("\n📊 Dry-run report (no LLM provider was contacted)");
+        println!(
+            "{:<16} {:>7} {:>7} {:>14} {:>14} {:>10}",
+            "Stage", "Calls", "Cached", "Input tokens", "Output tokens", "Cost"
+        );
+        for (stage, t) in stages.iter().map(|(s, t)| (s.as_str(), t)).chain([("Total", &total)]) {
+            println!(
+                "{:<16} {:>7} {:>7} {:>14} {:>14} {:>10}",
+                stage,
+                t.calls,
+                t.cached,
+                t.input_tokens,
+                t.output_tokens,
+                format!("${:.4}", t.cost)
+            );
+        }
+
+        println!("\n🧠 Model selection:");
+        for (model, t) in &models {
+            println!(
+                "   {}: {} calls ({} cached), {} input tokens",
+                model, t.calls, t.cached, t.input_tokens
+            );
+        }
+
+        println!(
+            "\n💡 Output tokens assume ~{} tokens per call. Prompts that embed earlier LLM results were built from placeholders, and per-domain agents were counted for a single placeholder domain.",
+            ESTIMATED_OUTPUT_TOKENS
+        );
+    }
+}
+
+/// Placeholder reply of a free-form prompt, roughly the assumed output size
+pub fn placeholder_text() -> String {
+    "Dry-run placeholder. ".repeat(ESTIMATED_OUTPUT_TOKENS * 4 / 21)
+}
+
+/// Minimal value matching the JSON schema of `T`, used as the reply of structured extraction
+pub fn placeholder_value<T: JsonSchema>() -> Value {
+    let schema = serde_json::to_value(schemars::schema_for!(T)).unwrap_or(Value::Null);
+    placeholder_for_schema(&schema, &schema, 0)
+}
+
+fn placeholder_for_schema(schema: &Value, root: &Value, depth: usize) -> Value {
+    // Guard against recursive schemas
+    if depth > 16 {
+        return Value::Null;
+    }
+    let Some(object) = schema.as_object() else {
+        return Value::Null;
+    };
+
+    if let Some(reference) = object.get("$ref").and_then(Value::as_str) {
+        let resolved = reference
+            .strip_prefix("#/")
+            .and_then(|path| root.pointer(&format!("/{}", path)))
+            .unwrap_or(&Value::Null);
+        return placeholder_for_schema(resolved, root, depth + 1);
+    }
+    if let Some(value) = object.get("const") {
+        return value.clone();
+    }
+    if let Some(first) = object.get("enum").and_then(Value::as_array).and_then(|v| v.first()) {
+        return first.clone();
+    }
+    for key in ["oneOf", "anyOf", "allOf"] {
+        if let Some(variants) = object.get(key).and_then(Value::as_array) {
+            let variant = variants
+                .iter()
+                .find(|v| v.get("type").and_then(Value::as_str) != Some("null"))
+                .unwrap_or(&Value::Null);
+            return placeholder_for_schema(variant, root, depth + 1);
+        }
+    }
+
+    let schema_type = match object.get("type") {
+        Some(Value::String(t)) => t.as_str(),
+        Some(Value::Array(types)) => types
+            .iter()
+            .filter_map(Value::as_str)
+            .find(|t| *t != "null")
+            .unwrap_or("null"),
+        _ if object.contains_key("properties") => "object",
+        _ => "null",
+    };
+
+    match schema_type {
+        "object" => {
+            let mut map = Map::new();
+            if let Some(properties) = object.get("properties").and_then(Value::as_object) {
+                for (name, property) in properties {
+                    map.insert(name.clone(), placeholder_for_schema(property, root, depth + 1));
+                }
+            }
+            Value::Object(map)
+        }
+        "array" => match object.get("prefixItems").and_then(Value::as_array) {
+            Some(items) => Value::Array(
+                items
+                    .iter()
+                    .map(|item| placeholder_for_schema(item, root, depth + 1))
+                    .collect(),
+            ),
+            None => Value::Array(vec![placeholder_for_schema(
+                object.get("items").unwrap_or(&Value::Null),
+                root,
+                depth + 1,
+            )]),
+        },
+        "string" => Value::from("placeholder"),
+        "integer" => Value::from(0),
+        "number" => Value::from(0.0),
+        "boolean" => Value::from(false),
+        _ => Value::Null,
+    }
+}
+
+/// Run the whole pipeline against placeholder replies and report what it would cost
+pub async fn launch(c: &Config) -> Result<()> {
+    let start = Instant::now();
+    CustomAgent::validate(&c.custom_agents)?;
+
+    let config = c.clone();
+    let llm_client = LLMClient::new(config.clone())?;
+    let pricing = llm_client.pricing();
+    let recorder = Arc::new(DryRunRecorder::new(pricing.clone()));
+    let prompt_templates = Arc::new(PromptTemplates::load(&config)?);
+    let context = GeneratorContext {
+        llm_client,
+        cache_manager: Arc::new(RwLock::new(CacheManager::new(
+            config.cache.clone(),
+            config.target_language.clone(),
+            pricing,
+        )?)),
+        config,
+        memory: Arc::new(RwLock::new(Memory::new())),
+        checkpoint: Arc::new(RunCheckpoint::ephemeral()),
+        incremental: None,
+        dry_run: Some(recorder.clone()),
+        prompt_templates,
+    };
+
+    println!("🧪 Dry run: building every prompt without contacting the LLM provider");
+
+    recorder.set_stage("preprocess");
+    PreProcessAgent::new().execute(context.clone()).await?;
+
+    recorder.set_stage("research");
+    ResearchOrchestrator
+        .execute_research_pipeline(&context)
+        .await?;
+
+    recorder.set_stage("documentation");
+    let mut doc_tree = DocTree::new(&context.config.target_language);
+    DocumentationComposer
+        .execute(&context, &mut doc_tree)
+        .await?;
+
+    recorder.print_report();
+    println!("\n⏱️  Dry run finished in {:.2}s", start.elapsed().as_secs_f64());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::code::CodeInsight;
+
+    #[test]
+    fn test_placeholder_value_deserializes() {
+        let value = placeholder_value::<CodeInsight>();
+        let insight: CodeInsight = serde_json::from_value(value).unwrap();
+        assert_eq!(insight.responsibilities, vec!["placeholder".to_string()]);
+    }
+}
@@ -0,0 +1,450 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tokio::sync::Mutex;
+
+use crate::config::Config;
+use crate::generator::compose::memory::MemoryScope as ComposeMemoryScope;
+use crate::generator::context::GeneratorContext;
+use crate::generator::preprocess::memory::{MemoryScope as PreprocessMemoryScope, ScopedKeys};
+use crate::generator::research::memory::MemoryScope as ResearchMemoryScope;
+use crate::generator::step_forward_agent::{DataSource, StepForwardAgent};
+use crate::integrations::KnowledgeSyncer;
+use crate::memory::MemorySnapshot;
+use crate::types::code::{CodeDossier, CodeInsight};
+use crate::utils::git_history;
+use crate::utils::project_fingerprint::{ProjectFingerprint, config_digest, hash_bytes};
+
+/// State persisted at the end of every stage, the baseline for the next incremental run
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IncrementalState {
+    /// HEAD commit of the project repository, if it is a git repository
+    pub commit: Option<String>,
+    pub config_hash: String,
+    /// Relative file path -> content hash
+    pub files: BTreeMap<PathBuf, String>,
+    /// Step (`scope/agent_type`) -> digest of the inputs it was generated from
+    pub step_inputs: BTreeMap<String, String>,
+    /// Step -> Memory keys it wrote to its scope, all of them are restored when the step is reused
+    #[serde(default)]
+    pub step_outputs: BTreeMap<String, Vec<String>>,
+}
+
+/// Incremental run, knows what changed since the last run and which results can be reused
+pub struct IncrementalRun {
+    path: PathBuf,
+    previous: Option<IncrementalState>,
+    current: Mutex<IncrementalState>,
+    changed_files: BTreeSet<PathBuf>,
+    /// Outputs of the last run, scope -> key -> value
+    previous_outputs: HashMap<String, HashMap<String, Value>>,
+}
+
+impl IncrementalRun {
+    /// Compare the project against the state of the last run
+    pub fn prepare(config: &Config) -> Result<Self> {
+        let path = config.internal_path.join("incremental.json");
+        let fingerprint = ProjectFingerprint::scan(config);
+        let current = IncrementalState {
+            commit: git_history::head(&config.project_path),
+            config_hash: config_digest(config),
+            files: fingerprint.files,
+            step_inputs: BTreeMap::new(),
+            step_outputs: BTreeMap::new(),
+        };
+
+        let previous = match fs::read_to_string(&path) {
+            Ok(content) => match serde_json::from_str::<IncrementalState>(&content) {
+                Ok(state) if state.config_hash == current.config_hash => Some(state),
+                Ok(_) => {
+                    println!("🔁 Incremental mode: configuration changed, regenerating everything");
+                    None
+                }
+                Err(e) => {
+                    eprintln!("⚠️  Failed to parse incremental state, regenerating everything: {}", e);
+                    None
+                }
+            },
+            Err(_) => {
+                println!("🔁 Incremental mode: no previous run found, regenerating everything");
+                None
+            }
+        };
+
+        let mut previous_outputs = HashMap::new();
+        let mut changed_files = BTreeSet::new();
+        if let Some(previous) = &previous {
+            // Content hashes catch edits that git cannot see, e.g. reverted uncommitted changes
+            changed_files = diff_files(&previous.files, &current.files);
+            if let Some(commit) = &previous.commit {
+                changed_files.extend(git_changed_files(&config.project_path, commit).unwrap_or_default());
+            }
+            for scope in [
+                PreprocessMemoryScope::PREPROCESS,
+                ResearchMemoryScope::STUDIES_RESEARCH,
+                ComposeMemoryScope::DOCUMENTATION,
+            ] {
+                if let Some(snapshot) = MemorySnapshot::load(&config.internal_path.join("snapshots"), scope)? {
+                    previous_outputs.insert(scope.to_string(), snapshot.entries);
+                }
+            }
+            println!(
+                "🔁 Incremental mode: {} files changed since the last run{}",
+                changed_files.len(),
+                previous
+                    .commit
+                    .as_ref()
+                    .map(|c| format!(" (commit {})", &c[..c.len().min(8)]))
+                    .unwrap_or_default()
+            );
+        }
+
+        Ok(Self {
+            path,
+            previous,
+            current: Mutex::new(current),
+            changed_files,
+            previous_outputs,
+        })
+    }
+
+    /// Split core files into those that must be analyzed again and the reusable insights of the rest
+    pub fn partition_codes(&self, codes: &[CodeDossier]) -> (Vec<CodeDossier>, Vec<CodeInsight>) {
+        if self.previous.is_none() {
+            return (codes.to_vec(), Vec::new());
+        }
+        let previous_insights: Vec<CodeInsight> = self
+            .previous_outputs
+            .get(PreprocessMemoryScope::PREPROCESS)
+            .and_then(|entries| entries.get(ScopedKeys::CODE_INSIGHTS))
+            .and_then(|value| serde_json::from_value(value.clone()).ok())
+            .unwrap_or_default();
+        partition_codes(codes, previous_insights, &self.changed_files)
+    }
+
+    /// Previous outputs of a step as Memory key and value, if the step's inputs are unchanged since the last run
+    /// and every key it wrote is still in the snapshot of its scope
+    pub fn reusable_outputs(&self, step: &str, scope: &str, digest: &str) -> Option<Vec<(String, Value)>> {
+        let previous = self.previous.as_ref()?;
+        if previous.step_inputs.get(step).map(String::as_str) != Some(digest) {
+            return None;
+        }
+        let entries = self.previous_outputs.get(scope)?;
+        previous
+            .step_outputs
+            .get(step)?
+            .iter()
+            .map(|key| Some((key.clone(), entries.get(key)?.clone())))
+            .collect()
+    }
+
+    /// Remember the input digest and the written Memory keys of a step for the next run
+    pub async fn record_step(&self, step: &str, digest: String, keys: Vec<String>) {
+        let mut current = self.current.lock().await;
+        current.step_inputs.insert(step.to_string(), digest);
+        current.step_outputs.insert(step.to_string(), keys);
+    }
+
+    /// Persist the state, called whenever a stage snapshot is saved
+    pub async fn save(&self) -> Result<()> {
+        let current = self.current.lock().await;
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, serde_json::to_string_pretty(&*current)?)?;
+        Ok(())
+    }
+}
+
+/// Keep the order of `codes`, reuse insights of files that neither changed nor depend on a changed file
+fn partition_codes(
+    codes: &[CodeDossier],
+    previous_insights: Vec<CodeInsight>,
+    changed_files: &BTreeSet<PathBuf>,
+) -> (Vec<CodeDossier>, Vec<CodeInsight>) {
+    let mut previous_by_path: HashMap<PathBuf, CodeInsight> = previous_insights
+        .into_iter()
+        .map(|insight| (insight.code_dossier.file_path.clone(), insight))
+        .collect();
+
+    let mut to_analyze = Vec::new();
+    let mut reused = Vec::new();
+    for code in codes {
+        match previous_by_path.remove(&code.file_path) {
+            Some(insight)
+                if !changed_files.contains(&code.file_path)
+                    && !depends_on_changed(&insight, changed_files) =>
+            {
+                reused.push(insight)
+            }
+            _ => to_analyze.push(code.clone()),
+        }
+    }
+    (to_analyze, reused)
+}
+
+/// Whether any internal dependency of the insight designates a changed file
+fn depends_on_changed(insight: &CodeInsight, changed_files: &BTreeSet<PathBuf>) -> bool {
+    let source_file = &insight.code_dossier.file_path;
+    insight
+        .dependencies
+        .iter()
+        .filter(|dependency| !dependency.is_external)
+        .any(|dependency| changed_files.iter().any(|changed| dependency.refers_to(source_file, changed)))
+}
+
+/// Files that were added, removed or modified between two fingerprints
+fn diff_files(
+    previous: &BTreeMap<PathBuf, String>,
+    current: &BTreeMap<PathBuf, String>,
+) -> BTreeSet<PathBuf> {
+    let mut changed: BTreeSet<PathBuf> = current
+        .iter()
+        .filter(|(path, hash)| previous.get(*path) != Some(hash))
+        .map(|(path, _)| path.clone())
+        .collect();
+    changed.extend(previous.keys().filter(|path| !current.contains_key(*path)).cloned());
+    changed
+}
+
+/// Files changed in the working tree since `commit`, including untracked files, relative to the project path
+fn git_changed_files(project_path: &Path, commit: &str) -> Option<BTreeSet<PathBuf>> {
+    let run = |args: &[&str]| -> Option<Vec<PathBuf>> {
+        let output = Command::new("git").arg("-C").arg(project_path).args(args).output().ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        Some(
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .filter(|line| !line.is_empty())
+                .map(PathBuf::from)
+                .collect(),
+        )
+    };
+    let mut changed: BTreeSet<PathBuf> = run(&["diff", "--name-only", "--relative", commit])?
+        .into_iter()
+        .collect();
+    changed.extend(run(&["ls-files", "--others", "--exclude-standard"])?);
+    Some(changed)
+}
+
+/// Digest of everything that goes into an agent's prompt
+pub async fn agent_input_digest<T>(agent: &T, context: &GeneratorContext) -> Result<String>
+where
+    T: StepForwardAgent + Send + Sync,
+{
+    let agent_type = agent.agent_type();
+    let data_config = agent.data_config();
+    let template = agent.prompt_template();
+
+    let mut material = vec![
+        agent.memory_scope_key(),
+        agent_type.clone(),
+        template.system_prompt,
+        template.opening_instruction,
+        template.closing_instruction,
+    ];
+
+    let all_sources = [data_config.required_sources, data_config.optional_sources].concat();
+    for source in &all_sources {
+        let value = match source {
+            DataSource::MemoryData { scope, key } => context.memory.read().await.get_value(scope, key),
+            DataSource::ResearchResult(key) => context
+                .memory
+                .read()
+                .await
+                .get_value(ResearchMemoryScope::STUDIES_RESEARCH, key),
+            DataSource::ExternalKnowledgeByCategory(categories) => {
+                KnowledgeSyncer::new(context.config.clone()).ok().map(|syncer| {
+                    let knowledge: Vec<String> = categories
+                        .iter()
+                        .filter_map(|c| {
+                            syncer
+                                .load_cached_knowledge_by_category(c, Some(&agent_type))
+                                .ok()
+                                .flatten()
+                        })
+                        .collect();
+                    Value::from(knowledge)
+                })
+            }
+        };
+        material.push(normalize(value.unwrap_or(Value::Null)).to_string());
+    }
+
+    if let Some(custom) = agent.provide_custom_prompt_content(context).await? {
+        material.push(custom);
+    }
+
+    Ok(hash_bytes(material.join("\0").as_bytes()))
+}
+
+/// Drop volatile fields such as file modification times, which change on checkout without changing content
+fn normalize(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .filter(|(key, _)| key != "last_modified")
+                .map(|(key, value)| (key, normalize(value)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(normalize).collect()),
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::preprocess::extractors::language_processors::LanguageProcessorManager;
+    use crate::types::code::{CodeComplexity, CodePurpose};
+
+    fn dossier(path: &str) -> CodeDossier {
+        CodeDossier {
+            name: path.to_string(),
+            file_path: PathBuf::from(path),
+            source_summary: String::new(),
+            code_purpose: CodePurpose::Other,
+            importance_score: 1.0,
+            description: None,
+            functions: vec![],
+            interfaces: vec![],
+        }
+    }
+
+    /// Insight with the dependencies the language processor finds in `content`
+    fn insight(path: &str, content: &str) -> CodeInsight {
+        CodeInsight {
+            code_dossier: dossier(path),
+            detailed_description: String::new(),
+            responsibilities: vec![],
+            interfaces: vec![],
+            dependencies: LanguageProcessorManager::new().extract_dependencies(Path::new(path), content),
+            complexity_metrics: CodeComplexity {
+                cyclomatic_complexity: 1.0,
+                lines_of_code: 1,
+                number_of_functions: 0,
+                number_of_classes: 0,
+            },
+        }
+    }
+
+    #[test]
+    fn test_partition_reanalyzes_changed_files_and_direct_dependents() {
+        let codes = vec![
+            dossier("src/cache/mod.rs"),
+            dossier("src/client.rs"),
+            dossier("src/utils/format.rs"),
+            dossier("src/main.rs"),
+            dossier("web/src/app.ts"),
+            dossier("web/src/utils/format.ts"),
+            dossier("src/new.rs"),
+        ];
+        let previous = vec![
+            insight("src/cache/mod.rs", ""),
+            insight("src/client.rs", "use crate::cache::CacheManager;\nuse std::sync::Arc;\n"),
+            insight("src/utils/format.rs", "use crate::utils::{sources, threads};\n"),
+            insight("src/main.rs", "use crate::config::Config;\nmod client;\n"),
+            insight("web/src/app.ts", "import { formatDate } from './utils/format';\n"),
+            insight("web/src/utils/format.ts", ""),
+        ];
+        let changed = BTreeSet::from([
+            PathBuf::from("src/cache/mod.rs"),
+            PathBuf::from("web/src/utils/format.ts"),
+        ]);
+
+        let (to_analyze, reused) = partition_codes(&codes, previous, &changed);
+
+        let to_analyze: Vec<_> = to_analyze.iter().map(|c| c.file_path.clone()).collect();
+        assert_eq!(
+            to_analyze,
+            vec![
+                PathBuf::from("src/cache/mod.rs"),
+                PathBuf::from("src/client.rs"),
+                PathBuf::from("web/src/app.ts"),
+                PathBuf::from("web/src/utils/format.ts"),
+                PathBuf::from("src/new.rs"),
+            ]
+        );
+        let reused: Vec<_> = reused.iter().map(|i| i.code_dossier.file_path.clone()).collect();
+        assert_eq!(reused, vec![PathBuf::from("src/utils/format.rs"), PathBuf::from("src/main.rs")]);
+    }
+
+    #[tokio::test]
+    async fn test_reused_step_restores_every_key_it_wrote() {
+        use crate::generator::checkpoint::execute_with_checkpoint;
+        use crate::generator::research::agents::key_modules_insight::KeyModulesInsight;
+        use crate::generator::research::memory::MemoryRetriever;
+        use std::sync::Arc;
+
+        let root = std::env::temp_dir().join(format!("litho-incremental-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() {}").unwrap();
+        let config = Config {
+            project_path: root.clone(),
+            internal_path: root.join(".litho"),
+            incremental: true,
+            ..Config::default()
+        };
+        let context = GeneratorContext::for_test(config.clone());
+        for source in ["SystemContextResearcher", "DomainModulesDetector"] {
+            context.store_research(source, Value::from(source)).await.unwrap();
+        }
+
+        // The last run stored the report of every domain next to the aggregate result
+        let agent = KeyModulesInsight;
+        let scope = agent.memory_scope_key();
+        let step = format!("{}/{}", scope, agent.agent_type());
+        let domain_key = KeyModulesInsight::domain_key("Billing");
+        let digest = agent_input_digest(&agent, &context).await.unwrap();
+        let previous = IncrementalState {
+            commit: None,
+            config_hash: config_digest(&config),
+            files: ProjectFingerprint::scan(&config).files,
+            step_inputs: BTreeMap::from([(step.clone(), digest.clone())]),
+            step_outputs: BTreeMap::from([(step.clone(), vec![agent.agent_type(), domain_key.clone()])]),
+        };
+        fs::create_dir_all(&config.internal_path).unwrap();
+        fs::write(
+            config.internal_path.join("incremental.json"),
+            serde_json::to_string(&previous).unwrap(),
+        )
+        .unwrap();
+        MemorySnapshot {
+            scope: scope.clone(),
+            created_at: chrono::Utc::now(),
+            entries: HashMap::from([
+                (agent.agent_type(), serde_json::json!([])),
+                (domain_key.clone(), Value::from("Billing report")),
+            ]),
+        }
+        .save(&config.internal_path.join("snapshots"))
+        .unwrap();
+
+        let incremental = IncrementalRun::prepare(&config).unwrap();
+        assert!(incremental.reusable_outputs(&step, &scope, "changed inputs").is_none());
+        let context = GeneratorContext {
+            incremental: Some(Arc::new(incremental)),
+            ..context
+        };
+        execute_with_checkpoint(&agent, &context).await.unwrap();
+        assert_eq!(context.get_research(&domain_key).await, Some(Value::from("Billing report")));
+
+        // A step whose snapshot lost one of its keys runs again instead of being reused partially
+        let incomplete = MemorySnapshot {
+            scope: scope.clone(),
+            created_at: chrono::Utc::now(),
+            entries: HashMap::from([(agent.agent_type(), serde_json::json!([]))]),
+        };
+        incomplete.save(&config.internal_path.join("snapshots")).unwrap();
+        let incremental = IncrementalRun::prepare(&config).unwrap();
+        assert!(incremental.reusable_outputs(&step, &scope, &digest).is_none());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
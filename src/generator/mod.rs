@@ -1,5 +1,10 @@
+pub mod checkpoint;
 pub mod context;
+pub mod custom_agent;
+pub mod dry_run;
+pub mod incremental;
 pub mod preprocess;
+pub mod prompt_templates;
 pub mod research;
 pub mod compose;
 pub mod types;
@@ -1,7 +1,7 @@
 use anyhow::Result;
 use chrono;
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::time::Instant;
 
 use crate::generator::compose::memory::MemoryScope as ComposeMemoryScope;
@@ -10,6 +10,12 @@ use crate::generator::preprocess::memory::{MemoryScope as PreprocessMemoryScope,
 use crate::generator::research::memory::MemoryScope as ResearchMemoryScope;
 use crate::generator::research::types::AgentType as ResearchAgentType;
 use crate::generator::workflow::{TimingKeys, TimingScope};
+use crate::cache::performance_monitor::ModelSavingsStats;
+use crate::config::{BudgetConfig, ModelPricing};
+use crate::llm::client::budget::BudgetUsage;
+use crate::llm::client::pricing::PriceSource;
+use crate::llm::client::rate_limit::ThrottleStats;
+use crate::llm::client::usage::{ModelKey, UsageTotals};
 
 /// Summary data collector - responsible for extracting four types of research materials from context
 pub struct SummaryDataCollector;
@@ -45,6 +51,38 @@ pub struct SummaryData {
     pub generated_docs: Vec<String>,
     /// Timing statistics
     pub timing_stats: TimingStats,
+    /// LLM budget usage, only present when a budget is configured
+    pub budget_stats: Option<BudgetStatsData>,
+    /// Token usage of the LLM calls made by this run
+    pub token_usage_stats: TokenUsageStats,
+    /// Rate limiting and retries of the LLM calls made by this run
+    pub rate_limit_stats: RateLimitStatsData,
+}
+
+/// Rate limiting data
+#[derive(Debug)]
+pub struct RateLimitStatsData {
+    /// Whether any provider has a configured rate limit
+    pub limited: bool,
+    pub throttle: ThrottleStats,
+}
+
+/// Token usage data, as reported by the providers
+#[derive(Debug)]
+pub struct TokenUsageStats {
+    pub provider: String,
+    pub total: UsageTotals,
+    pub by_agent: BTreeMap<String, UsageTotals>,
+    pub by_model: BTreeMap<ModelKey, UsageTotals>,
+    /// Price of every model that was called or had cache hits
+    pub prices: BTreeMap<ModelKey, (ModelPricing, PriceSource)>,
+}
+
+/// LLM budget data
+#[derive(Debug)]
+pub struct BudgetStatsData {
+    pub limits: BudgetConfig,
+    pub usage: BudgetUsage,
 }
 
 /// Cache statistics data
@@ -61,6 +99,7 @@ pub struct CacheStatsData {
     pub performance_improvement: f64,
     pub input_tokens_saved: usize,
     pub output_tokens_saved: usize,
+    pub savings_by_model: BTreeMap<ModelKey, ModelSavingsStats>,
 }
 
 /// Timing statistics data
@@ -134,6 +173,7 @@ impl SummaryDataCollector {
             performance_improvement: cache_report.performance_improvement,
             input_tokens_saved: cache_report.input_tokens_saved,
             output_tokens_saved: cache_report.output_tokens_saved,
+            savings_by_model: cache_report.model_stats,
         };
 
         // Collect generated documents list
@@ -144,6 +184,37 @@ impl SummaryDataCollector {
         // Collect timing statistics (from various stages in memory, if available)
         let timing_stats = Self::collect_timing_stats(context).await;
 
+        let budget = context.llm_client.budget();
+        let budget_stats = budget.is_limited().then(|| BudgetStatsData {
+            limits: budget.config().clone(),
+            usage: budget.usage(),
+        });
+
+        let usage = context.llm_client.usage();
+        let by_model = usage.by_model();
+        let pricing = context.llm_client.pricing();
+        let prices = by_model
+            .keys()
+            .chain(cache_stats.savings_by_model.keys())
+            .map(|(provider, model)| {
+                let key = (provider.clone(), model.clone());
+                (key, pricing.price(provider, model))
+            })
+            .collect();
+        let token_usage_stats = TokenUsageStats {
+            provider: context.config.llm.provider.to_string(),
+            total: usage.total(),
+            by_agent: usage.by_agent(),
+            by_model,
+            prices,
+        };
+
+        let rate_limiter = context.llm_client.rate_limiter();
+        let rate_limit_stats = RateLimitStatsData {
+            limited: rate_limiter.is_limited(),
+            throttle: rate_limiter.stats(),
+        };
+
         let summary_generation_time = start_time.elapsed().as_secs_f64();
         let mut timing_stats = timing_stats;
         timing_stats.summary_generation_time = summary_generation_time;
@@ -157,6 +228,9 @@ impl SummaryDataCollector {
             cache_stats,
             generated_docs,
             timing_stats,
+            budget_stats,
+            token_usage_stats,
+            rate_limit_stats,
         })
     }
 
@@ -308,6 +382,16 @@ impl SummaryContentGenerator {
             stats.input_tokens_saved + stats.output_tokens_saved
         ));
         content.push_str(&format!("- **Estimated Cost Savings**: ${:.4}\n", stats.cost_saved));
+        for (model, savings) in &stats.savings_by_model {
+            content.push_str(&format!(
+                "  - {}: {} hits, {} input + {} output tokens, ${:.4}\n",
+                model_label(model),
+                savings.hits,
+                savings.input_tokens_saved,
+                savings.output_tokens_saved,
+                savings.cost_saved
+            ));
+        }
         if stats.performance_improvement > 0.0 {
             content.push_str(&format!(
                 "- **Performance Improvement**: {:.1}%\n",
@@ -325,6 +409,58 @@ impl SummaryContentGenerator {
         }
         content.push_str("\n");
 
+        content.push_str("## LLM Token Usage\n\n");
+        let usage = &data.token_usage_stats;
+        content.push_str(&format!("- **Provider**: {}\n", usage.provider));
+        Self::push_usage_line(&mut content, "**Total**", &usage.total);
+        if !usage.by_model.is_empty() {
+            content.push_str("\n### By Model\n");
+            for (model, totals) in &usage.by_model {
+                Self::push_usage_line(&mut content, &model_label(model), totals);
+            }
+        }
+        if !usage.by_agent.is_empty() {
+            content.push_str("\n### By Agent\n");
+            for (agent, totals) in &usage.by_agent {
+                Self::push_usage_line(&mut content, agent, totals);
+            }
+        }
+        if !usage.prices.is_empty() {
+            content.push_str("\n### Prices (USD per 1k tokens)\n");
+            for (model, (price, source)) in &usage.prices {
+                content.push_str(&format!(
+                    "- {}: ${} input, ${} output ({})\n",
+                    model_label(model),
+                    price.input,
+                    price.output,
+                    source
+                ));
+            }
+        }
+        content.push('\n');
+
+        if let Some(budget) = &data.budget_stats {
+            Self::push_budget_section(&mut content, "## LLM Budget\n\n", budget);
+        }
+
+        let rate_limit = &data.rate_limit_stats;
+        if rate_limit.limited || rate_limit.throttle.retries > 0 {
+            let throttle = &rate_limit.throttle;
+            content.push_str("## Rate Limiting\n\n");
+            content.push_str(&format!(
+                "- **Throttled Calls**: {} ({:.1} seconds waiting for the rate limit)\n",
+                throttle.throttled_calls, throttle.throttled_seconds
+            ));
+            content.push_str(&format!(
+                "- **Rate Limit Errors**: {}\n",
+                throttle.rate_limit_errors
+            ));
+            content.push_str(&format!(
+                "- **Retries**: {} ({:.1} seconds of backoff)\n\n",
+                throttle.retries, throttle.backoff_seconds
+            ));
+        }
+
         // 4. Core research data summary
         content.push_str("## Core Research Data Summary\n\n");
         content.push_str("Complete content of four types of research materials according to Prompt template data integration rules:\n\n");
@@ -399,6 +535,54 @@ impl SummaryContentGenerator {
         content
     }
 
+    /// One list item with the usage of a group of LLM calls
+    fn push_usage_line(content: &mut String, label: &str, totals: &UsageTotals) {
+        content.push_str(&format!(
+            "- {}: {} calls, {} input + {} output tokens, ${:.4}",
+            label, totals.calls, totals.input_tokens, totals.output_tokens, totals.cost
+        ));
+        if totals.estimated_calls > 0 {
+            content.push_str(&format!(
+                " ({} calls estimated, the provider reported no usage)",
+                totals.estimated_calls
+            ));
+        }
+        content.push('\n');
+    }
+
+    /// How the LLM budget was spent against its limits
+    fn push_budget_section(content: &mut String, title: &str, budget: &BudgetStatsData) {
+        let usage = &budget.usage;
+        let limits = &budget.limits;
+        let limit = |max: Option<String>| max.unwrap_or_else(|| "unlimited".to_string());
+
+        content.push_str(title);
+        content.push_str(&format!("- **LLM Calls**: {}\n", usage.calls));
+        content.push_str(&format!(
+            "- **Input Tokens**: {} / {}\n",
+            usage.input_tokens,
+            limit(limits.max_input_tokens.map(|max| max.to_string()))
+        ));
+        content.push_str(&format!(
+            "- **Output Tokens**: {} / {}\n",
+            usage.output_tokens,
+            limit(limits.max_output_tokens.map(|max| max.to_string()))
+        ));
+        content.push_str(&format!(
+            "- **Estimated Cost**: ${:.4} / {}\n",
+            usage.cost,
+            limit(limits.max_cost.map(|max| format!("${:.4}", max)))
+        ));
+        let status = if usage.exhausted {
+            "🔴 Exhausted, the run stopped early with partial results"
+        } else if usage.degraded {
+            "🟡 Degraded after reaching the threshold"
+        } else {
+            "🟢 Within budget"
+        };
+        content.push_str(&format!("- **Status**: {}\n\n", status));
+    }
+
     /// Generate brief version of summary content
     fn generate_brief_content(data: &SummaryData) -> String {
         let mut content = String::new();
@@ -463,6 +647,9 @@ impl SummaryContentGenerator {
             stats.input_tokens_saved + stats.output_tokens_saved
         ));
         content.push_str(&format!("**Cost Savings**: ${:.4}\n", stats.cost_saved));
+        for (model, savings) in &stats.savings_by_model {
+            content.push_str(&format!("- {}: ${:.4}\n", model_label(model), savings.cost_saved));
+        }
 
         // Efficiency assessment
         if timing.total_execution_time > 0.0 && stats.inference_time_saved > 0.0 {
@@ -477,6 +664,27 @@ impl SummaryContentGenerator {
         }
         content.push_str("\n");
 
+        content.push_str("## Token Usage Overview\n\n");
+        let usage = &data.token_usage_stats;
+        content.push_str(&format!("**Provider**: {}\n", usage.provider));
+        Self::push_usage_line(&mut content, "**Total**", &usage.total);
+        for (model, totals) in &usage.by_model {
+            Self::push_usage_line(&mut content, &model_label(model), totals);
+        }
+        content.push('\n');
+
+        if let Some(budget) = &data.budget_stats {
+            Self::push_budget_section(&mut content, "## Budget Overview\n\n", budget);
+        }
+
+        let throttle = &data.rate_limit_stats.throttle;
+        if throttle.throttled_calls > 0 || throttle.retries > 0 {
+            content.push_str(&format!(
+                "**Throttling**: {} calls throttled, {} rate limit errors, {} retries\n\n",
+                throttle.throttled_calls, throttle.rate_limit_errors, throttle.retries
+            ));
+        }
+
         // 4. Research data overview
         content.push_str("## Research Data Overview\n\n");
         content.push_str("Successfully collected four types of research materials according to Prompt template data integration rules:\n\n");
@@ -615,3 +823,12 @@ impl SummaryContentGenerator {
         content
     }
 }
+
+/// `provider/model`, the model alone for cache entries that did not record their provider
+fn model_label((provider, model): &ModelKey) -> String {
+    if provider.is_empty() {
+        model.clone()
+    } else {
+        format!("{}/{}", provider, model)
+    }
+}
@@ -2,6 +2,7 @@ use crate::generator::agent_executor::{AgentExecuteParams, extract};
 use crate::{
     generator::{
         context::GeneratorContext,
+        prompt_templates::{CODE_ANALYZE_SYS, CODE_ANALYZE_TEMPLATES, CODE_ANALYZE_USER, PromptTemplates},
         preprocess::extractors::language_processors::LanguageProcessorManager,
     },
     types::{
@@ -43,7 +44,11 @@ impl CodeAnalyze {
                 Box::pin(async move {
                     let code_analyze = CodeAnalyze { language_processor };
                     let agent_params = code_analyze
-                        .prepare_single_code_agent_params(&project_structure_clone, &code_clone)
+                        .prepare_single_code_agent_params(
+                            &context_clone.prompt_templates,
+                            &project_structure_clone,
+                            &code_clone,
+                        )
                         .await?;
                     let mut code_insight =
                         extract::<CodeInsight>(&context_clone, agent_params).await?;
@@ -81,6 +86,7 @@ impl CodeAnalyze {
 impl CodeAnalyze {
     async fn prepare_single_code_agent_params(
         &self,
+        templates: &PromptTemplates,
         project_structure: &ProjectStructure,
         codes: &CodeDossier,
     ) -> Result<AgentExecuteParams> {
@@ -88,14 +94,16 @@ impl CodeAnalyze {
         let code_analyse = self.analyze_code_by_rules(codes, project_structure).await?;
 
         // Then use AI for enhanced analysis
-        let prompt_user = self.build_code_analysis_prompt(project_structure, &code_analyse);
-        let prompt_sys = include_str!("prompts/code_analyze_sys.tpl").to_string();
+        let prompt_user = self.build_code_analysis_prompt(templates, project_structure, &code_analyse);
+        let prompt_sys = templates.render(CODE_ANALYZE_SYS, &[]);
 
         Ok(AgentExecuteParams {
             prompt_sys,
             prompt_user,
             cache_scope: "ai_code_insight".to_string(),
+            agent: "CodeAnalyze".to_string(),
             log_tag: codes.name.to_string(),
+            template_version: templates.version(CODE_ANALYZE_TEMPLATES),
         })
     }
 }
@@ -103,6 +111,7 @@ impl CodeAnalyze {
 impl CodeAnalyze {
     fn build_code_analysis_prompt(
         &self,
+        templates: &PromptTemplates,
         project_structure: &ProjectStructure,
         analysis: &CodeInsight,
     ) -> String {
@@ -112,19 +121,24 @@ impl CodeAnalyze {
         let dependency_code =
             read_dependency_code_source(&self.language_processor, analysis, project_path);
 
-        format!(
-            include_str!("prompts/code_analyze_user.tpl"),
-            analysis.code_dossier.name,
-            analysis.code_dossier.file_path.display(),
-            analysis.code_dossier.code_purpose.display_name(),
-            analysis.code_dossier.importance_score,
-            analysis.responsibilities.join(", "),
-            analysis.interfaces.len(),
-            analysis.dependencies.len(),
-            analysis.complexity_metrics.lines_of_code,
-            analysis.complexity_metrics.cyclomatic_complexity,
-            analysis.code_dossier.source_summary,
-            dependency_code
+        templates.render(
+            CODE_ANALYZE_USER,
+            &[
+                ("component_name", analysis.code_dossier.name.clone()),
+                ("file_path", analysis.code_dossier.file_path.display().to_string()),
+                ("component_type", analysis.code_dossier.code_purpose.display_name().to_string()),
+                ("importance_score", format!("{:.2}", analysis.code_dossier.importance_score)),
+                ("responsibilities", analysis.responsibilities.join(", ")),
+                ("interface_count", analysis.interfaces.len().to_string()),
+                ("dependency_count", analysis.dependencies.len().to_string()),
+                ("lines_of_code", analysis.complexity_metrics.lines_of_code.to_string()),
+                (
+                    "cyclomatic_complexity",
+                    format!("{:.1}", analysis.complexity_metrics.cyclomatic_complexity),
+                ),
+                ("source_code", analysis.code_dossier.source_summary.clone()),
+                ("dependency_code", dependency_code),
+            ],
         )
     }
 
@@ -8,6 +8,9 @@ use crate::{
 };
 use crate::generator::agent_executor::{AgentExecuteParams, extract};
 use crate::generator::context::GeneratorContext;
+use crate::generator::prompt_templates::{
+    CODE_PURPOSE_ANALYZE_TEMPLATES, CODE_PURPOSE_ANALYZE_USER, PromptTemplates,
+};
 
 /// AI component type analysis result
 #[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
@@ -45,13 +48,16 @@ impl CodePurposeEnhancer {
 
         // If there's AI analyzer and file content, use AI enhanced analysis
         let prompt_sys = "You are a professional code architecture analyst specializing in analyzing component types of code files.".to_string();
-        let prompt_user = self.build_code_purpose_analysis_prompt(file_path, file_content, file_name);
+        let templates = &context.prompt_templates;
+        let prompt_user = self.build_code_purpose_analysis_prompt(templates, file_path, file_content, file_name);
 
         let analyze_result = extract::<AICodePurposeAnalysis>(context, AgentExecuteParams {
             prompt_sys,
             prompt_user,
             cache_scope: "ai_code_purpose".to_string(),
+            agent: "CodePurposeEnhancer".to_string(),
             log_tag: file_name.to_string(),
+            template_version: templates.version(CODE_PURPOSE_ANALYZE_TEMPLATES),
         }).await;
 
         return match analyze_result {
@@ -77,6 +83,7 @@ impl CodePurposeEnhancer {
     /// Build component type analysis prompt
     fn build_code_purpose_analysis_prompt(
         &self,
+        templates: &PromptTemplates,
         file_path: &Path,
         file_content: &str,
         file_name: &str,
@@ -89,11 +96,13 @@ impl CodePurposeEnhancer {
             file_content.to_string()
         };
 
-        format!(
-            include_str!("prompts/code_purpose_analyze_user.tpl"),
-            file_path.display(),
-            file_name,
-            content_preview
+        templates.render(
+            CODE_PURPOSE_ANALYZE_USER,
+            &[
+                ("file_path", file_path.display().to_string()),
+                ("file_name", file_name.to_string()),
+                ("content_preview", content_preview),
+            ],
         )
     }
 }
@@ -1,25 +1,25 @@
 Please conduct an in-depth analysis of the component based on the following source code:
 
 ## Component Basic Information
-- Component Name: {}
-- File Path: {}
-- Component Type: {}
-- Importance Score: {:.2}
+- Component Name: {{component_name}}
+- File Path: {{file_path}}
+- Component Type: {{component_type}}
+- Importance Score: {{importance_score}}
 
 ## Current Static Analysis Results
-- Responsibilities: {}
-- Number of Interfaces: {}
-- Number of Dependencies: {}
-- Lines of Code: {}
-- Cyclomatic Complexity: {:.1}
+- Responsibilities: {{responsibilities}}
+- Number of Interfaces: {{interface_count}}
+- Number of Dependencies: {{dependency_count}}
+- Lines of Code: {{lines_of_code}}
+- Cyclomatic Complexity: {{cyclomatic_complexity}}
 
 ## Component Source Code
 ```
-{}
+{{source_code}}
 ```
 
 ## Dependent Component Code Snippets
-{}
+{{dependency_code}}
 
 Please conduct an in-depth analysis based on the source code, focusing on:
 1. Detailed functional description and business logic of the component
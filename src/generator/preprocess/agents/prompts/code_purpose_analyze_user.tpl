@@ -1,12 +1,12 @@
 Please analyze the component type of the following code file:
 
 ## File Information
-- **File Path**: {}
-- **File Name**: {}
+- **File Path**: {{file_path}}
+- **File Name**: {{file_name}}
 
 ## File Content Preview
 ```
-{}
+{{content_preview}}
 ```
 
 ## Analysis Requirements
@@ -83,7 +83,9 @@ Generate a project-level dependency relationship graph, focusing on:
             prompt_sys,
             prompt_user,
             cache_scope: "ai_relationships_insights".to_string(),
+            agent: "RelationshipsAnalyze".to_string(),
             log_tag: "Dependency Relationship Analysis".to_string(),
+            template_version: None,
         })
     }
 
@@ -0,0 +1,36 @@
+use std::path::Path;
+
+use crate::config::Config;
+use crate::types::git_history::GitHistory;
+use crate::utils::git_history::{head, read_commits, summarize};
+use crate::utils::ignore_matcher::IgnoreMatcher;
+
+/// Most recent commits the history is built from
+const MAX_COMMITS: usize = 1000;
+const MAX_FILES: usize = 200;
+const MAX_CO_CHANGES: usize = 100;
+const MAX_RECENT_COMMITS: usize = 30;
+
+/// Read the history of the project from its local git repository, None if the project is not versioned with git
+pub fn extract(config: &Config) -> Option<GitHistory> {
+    let commits = read_commits(&config.project_path, None, MAX_COMMITS)?;
+    if commits.is_empty() {
+        return None;
+    }
+    let ignore_matcher = IgnoreMatcher::new(config);
+    let mut history = summarize(
+        &commits,
+        |file| is_analyzed(config, &ignore_matcher, file),
+        MAX_FILES,
+        MAX_CO_CHANGES,
+        MAX_RECENT_COMMITS,
+    );
+    history.head = head(&config.project_path);
+    Some(history)
+}
+
+/// Whether a file still exists and is not excluded from the analysis
+fn is_analyzed(config: &Config, ignore_matcher: &IgnoreMatcher, relative_path: &str) -> bool {
+    let relative_path = Path::new(relative_path);
+    config.project_path.join(relative_path).is_file() && !ignore_matcher.is_excluded(relative_path, false)
+}
@@ -46,11 +46,9 @@ impl LanguageProcessor for RustProcessor {
                                     !use_str.starts_with("super::") && 
                                     !use_str.starts_with("self::");
                     
-                    // Parse dependency name
-                    let dependency_name = self.extract_dependency_name(use_str).unwrap_or_else(|| use_str.to_string());
-                    
+                    // The whole use path, it names the module the items come from
                     dependencies.push(Dependency {
-                        name: dependency_name,
+                        name: use_str.to_string(),
                         path: Some(source_file.clone()),
                         is_external,
                         line_number: Some(line_num + 1),
@@ -307,38 +305,4 @@ impl RustProcessor {
             Some(doc_lines.join(" "))
         }
     }
-
-    /// Extract dependency name from use path
-    fn extract_dependency_name(&self, use_path: &str) -> Option<String> {
-        // Handle complex use statements, like use crate::{module1, module2}
-        if use_path.contains('{') && use_path.contains('}') {
-            if let Some(start) = use_path.find('{') {
-                if let Some(end) = use_path.find('}') {
-                    let inner = &use_path[start + 1..end];
-                    // Return first module name
-                    if let Some(first_module) = inner.split(',').next() {
-                        return Some(first_module.trim().to_string());
-                    }
-                }
-            }
-        }
-
-        // Handle use crate::module::item as alias
-        if let Some(as_pos) = use_path.find(" as ") {
-            let path_part = &use_path[..as_pos].trim();
-            return Some(self.extract_simple_dependency_name(path_part));
-        }
-
-        Some(self.extract_simple_dependency_name(use_path))
-    }
-
-    /// Extract dependency name from simple path
-    fn extract_simple_dependency_name(&self, path: &str) -> String {
-        // For crate::module::item, return item
-        if let Some(last_part) = path.split("::").last() {
-            last_part.to_string()
-        } else {
-            path.to_string()
-        }
-    }
 }
\ No newline at end of file
@@ -1,3 +1,4 @@
+pub mod git_history_extractor;
 pub mod language_processors;
 pub mod structure_extractor;
 pub mod original_document_extractor;
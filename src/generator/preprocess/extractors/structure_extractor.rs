@@ -4,7 +4,7 @@ use crate::generator::preprocess::extractors::language_processors::LanguageProce
 use crate::types::code::{CodeDossier, CodePurpose, CodePurposeMapper};
 use crate::types::project_structure::ProjectStructure;
 use crate::types::{DirectoryInfo, FileInfo};
-use crate::utils::file_utils::{is_binary_file_path, is_test_directory, is_test_file};
+use crate::utils::ignore_matcher::IgnoreMatcher;
 use crate::utils::sources::read_code_source;
 use anyhow::Result;
 use futures::future::BoxFuture;
@@ -16,6 +16,7 @@ use std::path::PathBuf;
 pub struct StructureExtractor {
     language_processor: LanguageProcessorManager,
     code_purpose_enhancer: CodePurposeEnhancer,
+    ignore_matcher: IgnoreMatcher,
     context: GeneratorContext,
 }
 
@@ -24,6 +25,7 @@ impl StructureExtractor {
         Self {
             language_processor: LanguageProcessorManager::new(),
             code_purpose_enhancer: CodePurposeEnhancer::new(),
+            ignore_matcher: IgnoreMatcher::new(&context.config),
             context,
         }
     }
@@ -106,10 +108,11 @@ impl StructureExtractor {
             while let Some(entry) = entries.next_entry().await? {
                 let path = entry.path();
                 let file_type = entry.file_type().await?;
+                let relative_path = path.strip_prefix(root_path).unwrap_or(&path);
 
                 if file_type.is_file() {
                     // Check if this file should be ignored
-                    if !self.should_ignore_file(&path) {
+                    if !self.ignore_matcher.is_excluded(relative_path, false) {
                         if let Ok(metadata) = std::fs::metadata(&path) {
                             let file_info = self.create_file_info(&path, root_path, &metadata)?;
 
@@ -128,14 +131,8 @@ impl StructureExtractor {
                         }
                     }
                 } else if file_type.is_dir() {
-                    let dir_name = path
-                        .file_name()
-                        .unwrap_or_default()
-                        .to_string_lossy()
-                        .to_string();
-
-                    // Skip hidden directories and commonly ignored directories
-                    if !self.should_ignore_directory(&dir_name) {
+                    // Skip hidden, test and excluded directories
+                    if !self.ignore_matcher.is_excluded(relative_path, true) {
                         dir_subdirectory_count += 1;
 
                         // Recursively scan subdirectories
@@ -222,102 +219,6 @@ impl StructureExtractor {
         }
     }
 
-    fn should_ignore_directory(&self, dir_name: &str) -> bool {
-        let config = &self.context.config;
-        let dir_name_lower = dir_name.to_lowercase();
-
-        // Check excluded directories configured in Config
-        for excluded_dir in &config.excluded_dirs {
-            if dir_name_lower == excluded_dir.to_lowercase() {
-                return true;
-            }
-        }
-
-        // Check if it's a test directory (if not including test files)
-        if !config.include_tests && is_test_directory(dir_name) {
-            return true;
-        }
-
-        // Check hidden directories
-        if !config.include_hidden && dir_name.starts_with('.') {
-            return true;
-        }
-
-        false
-    }
-
-    fn should_ignore_file(&self, path: &PathBuf) -> bool {
-        let config = &self.context.config;
-        let file_name = path
-            .file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or("")
-            .to_lowercase();
-
-        let _path_str = path.to_string_lossy().to_lowercase();
-
-        // Check excluded files
-        for excluded_file in &config.excluded_files {
-            if excluded_file.contains('*') {
-                // Simple wildcard matching
-                let pattern = excluded_file.replace('*', "");
-                if file_name.contains(&pattern.to_lowercase()) {
-                    return true;
-                }
-            } else if file_name == excluded_file.to_lowercase() {
-                return true;
-            }
-        }
-
-        // Check excluded extensions
-        if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
-            if config
-                .excluded_extensions
-                .contains(&extension.to_lowercase())
-            {
-                return true;
-            }
-        }
-
-        // Check included extensions (if specified)
-        if !config.included_extensions.is_empty() {
-            if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
-                if !config
-                    .included_extensions
-                    .contains(&extension.to_lowercase())
-                {
-                    return true;
-                }
-            } else {
-                return true; // No extension and include list is specified
-            }
-        }
-
-        // Check test files (if not including test files)
-        if !config.include_tests && is_test_file(path) {
-            return true;
-        }
-
-        // Check hidden files
-        if !config.include_hidden && file_name.starts_with('.') {
-            return true;
-        }
-
-        // Check file size
-        if let Ok(metadata) = std::fs::metadata(path) {
-            if metadata.len() > config.max_file_size {
-                return true;
-            }
-        }
-
-        // Check binary files
-        if is_binary_file_path(path) {
-            return true;
-        }
-
-        false
-    }
-
     fn calculate_importance_scores(
         &self,
         files: &mut [FileInfo],
@@ -11,4 +11,5 @@ impl ScopedKeys {
     pub const PROJECT_STRUCTURE: &'static str = "project_structure";
     pub const CODE_INSIGHTS: &'static str = "code_insights";
     pub const RELATIONSHIPS: &'static str = "relationships";
+    pub const GIT_HISTORY: &'static str = "git_history";
 }
\ No newline at end of file
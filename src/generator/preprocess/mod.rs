@@ -2,7 +2,7 @@ use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use tokio::time::Instant;
 
-use crate::generator::preprocess::extractors::original_document_extractor;
+use crate::generator::preprocess::extractors::{git_history_extractor, original_document_extractor};
 use crate::generator::preprocess::memory::{MemoryScope, ScopedKeys};
 use crate::types::original_document::OriginalDocument;
 use crate::{
@@ -15,7 +15,7 @@ use crate::{
         types::Generator,
     },
     types::{
-        code::CodeInsight, code_releationship::RelationshipAnalysis,
+        code::CodeInsight, code_releationship::RelationshipAnalysis, git_history::GitHistory,
         project_structure::ProjectStructure,
     },
 };
@@ -35,6 +35,8 @@ pub struct PreprocessingResult {
     pub core_code_insights: Vec<CodeInsight>,
     // Dependencies between code
     pub relationships: RelationshipAnalysis,
+    // Version control history, None if the project is not in a git repository
+    pub git_history: Option<GitHistory>,
     pub processing_time: f64,
 }
 
@@ -70,7 +72,19 @@ impl Generator<PreprocessingResult> for PreProcessAgent {
             project_structure.total_files, project_structure.total_directories
         );
 
-        // 3. Identify core components
+        // 3. Read version control history
+        println!("🕰️  Reading git history...");
+        let git_history = git_history_extractor::extract(config);
+        match &git_history {
+            Some(history) => println!(
+                "   Read {} commits touching {} files",
+                history.analyzed_commits,
+                history.files.len()
+            ),
+            None => println!("   No git history found, skipping"),
+        }
+
+        // 4. Identify core components
         println!("🎯 Identifying main source code files...");
         let important_codes = structure_extractor
             .identify_core_codes(&project_structure)
@@ -78,14 +92,31 @@ impl Generator<PreprocessingResult> for PreProcessAgent {
 
         println!("   Identified {} main source code files", important_codes.len());
 
-        // 4. Analyze core components using AI
+        // 5. Analyze core components using AI
         println!("🤖 Analyzing core files using AI...");
         let code_analyze = CodeAnalyze::new();
-        let core_code_insights = code_analyze
-            .execute(&context, &important_codes, &project_structure)
+        let (codes_to_analyze, reused_insights) = match &context.incremental {
+            Some(incremental) => incremental.partition_codes(&important_codes),
+            None => (important_codes.clone(), Vec::new()),
+        };
+        if !reused_insights.is_empty() {
+            println!(
+                "   ♻️  Reusing insights of {} unchanged files, re-analyzing {} files",
+                reused_insights.len(),
+                codes_to_analyze.len()
+            );
+        }
+        let mut core_code_insights = code_analyze
+            .execute(&context, &codes_to_analyze, &project_structure)
             .await?;
-
-        // 5. Analyze component relationships
+        core_code_insights.extend(reused_insights);
+        core_code_insights.sort_by_key(|insight| {
+            important_codes
+                .iter()
+                .position(|code| code.file_path == insight.code_dossier.file_path)
+        });
+
+        // 6. Analyze component relationships
         println!("🔗 Analyzing component relationships...");
         let relationships_analyze = RelationshipsAnalyze::new();
         let relationships = relationships_analyze
@@ -96,7 +127,7 @@ impl Generator<PreprocessingResult> for PreProcessAgent {
 
         println!("✅ Project preprocessing completed, took {:.2} seconds", processing_time);
 
-        // 6. Store preprocessing results to Memory
+        // 7. Store preprocessing results to Memory
         context
             .store_to_memory(
                 MemoryScope::PREPROCESS,
@@ -125,12 +156,18 @@ impl Generator<PreprocessingResult> for PreProcessAgent {
                 &original_document,
             )
             .await?;
+        if let Some(git_history) = &git_history {
+            context
+                .store_to_memory(MemoryScope::PREPROCESS, ScopedKeys::GIT_HISTORY, git_history)
+                .await?;
+        }
 
         Ok(PreprocessingResult {
             original_document,
             project_structure,
             core_code_insights,
             relationships,
+            git_history,
             processing_time,
         })
     }
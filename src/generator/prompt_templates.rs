@@ -0,0 +1,369 @@
+//! Prompt templates - Built-in prompts, overridable by `.tpl` files in the configured `prompts_dir`
+//!
+//! A step-forward agent's prompt is overridden per slot with `<AgentName>.<slot>.tpl` (slots `system`,
+//! `opening` and `closing`), the other prompts by their template name, e.g. `code_analyze_user.tpl`.
+//! Templates use named `{{placeholder}}`s.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, anyhow};
+use md5::{Digest, Md5};
+
+use crate::config::Config;
+use crate::generator::custom_agent::CustomAgent;
+use crate::generator::compose::agents::{
+    architecture_editor::ArchitectureEditor, boundary_editor::BoundaryEditor,
+    database_editor::DatabaseEditor, key_modules_insight_editor::KeyModulesInsightEditor,
+    overview_editor::OverviewEditor, workflow_editor::WorkflowEditor,
+};
+use crate::generator::research::agents::{
+    architecture_researcher::ArchitectureResearcher, boundary_analyzer::BoundaryAnalyzer,
+    database_overview_analyzer::DatabaseOverviewAnalyzer,
+    domain_modules_detector::DomainModulesDetector,
+    system_context_researcher::SystemContextResearcher, workflow_researcher::WorkflowResearcher,
+};
+use crate::generator::step_forward_agent::{PromptTemplate, StepForwardAgent};
+
+pub const CODE_ANALYZE_SYS: &str = "code_analyze_sys.tpl";
+pub const CODE_ANALYZE_USER: &str = "code_analyze_user.tpl";
+pub const CODE_PURPOSE_ANALYZE_USER: &str = "code_purpose_analyze_user.tpl";
+pub const KEY_MODULES_INSIGHT_SYS: &str = "key_modules_insight_sys.tpl";
+pub const KEY_MODULES_INSIGHT_USER: &str = "key_modules_insight_user.tpl";
+
+/// Named templates each prompt is built from, versioned together
+pub const CODE_ANALYZE_TEMPLATES: &[&str] = &[CODE_ANALYZE_SYS, CODE_ANALYZE_USER];
+pub const CODE_PURPOSE_ANALYZE_TEMPLATES: &[&str] = &[CODE_PURPOSE_ANALYZE_USER];
+pub const KEY_MODULES_INSIGHT_TEMPLATES: &[&str] = &[KEY_MODULES_INSIGHT_SYS, KEY_MODULES_INSIGHT_USER];
+
+/// Built-in templates overridden by their name
+const NAMED_TEMPLATES: [(&str, &str); 5] = [
+    (CODE_ANALYZE_SYS, include_str!("preprocess/agents/prompts/code_analyze_sys.tpl")),
+    (CODE_ANALYZE_USER, include_str!("preprocess/agents/prompts/code_analyze_user.tpl")),
+    (
+        CODE_PURPOSE_ANALYZE_USER,
+        include_str!("preprocess/agents/prompts/code_purpose_analyze_user.tpl"),
+    ),
+    (KEY_MODULES_INSIGHT_SYS, include_str!("research/agents/prompts/key_modules_insight_sys.tpl")),
+    (KEY_MODULES_INSIGHT_USER, include_str!("research/agents/prompts/key_modules_insight_user.tpl")),
+];
+
+/// Slots of a step-forward agent's prompt template
+const SLOTS: [&str; 3] = ["system", "opening", "closing"];
+
+/// Prompt templates of a run, the built-in ones and the overrides read from `prompts_dir`
+#[derive(Debug, Default)]
+pub struct PromptTemplates {
+    overrides: BTreeMap<String, String>,
+}
+
+impl PromptTemplates {
+    /// Read the overrides of the configured `prompts_dir`, none if it is not set
+    pub fn load(config: &Config) -> Result<Self> {
+        let Some(dir) = &config.prompts_dir else {
+            return Ok(Self::default());
+        };
+        if !dir.is_dir() {
+            return Err(anyhow!(
+                "Prompt templates directory {} does not exist",
+                dir.display()
+            ));
+        }
+
+        let custom_agents = config
+            .custom_agents
+            .iter()
+            .map(|agent| CustomAgent::new(agent.clone()).agent_name());
+        let agents = builtin_agent_names()
+            .into_iter()
+            .chain(custom_agents)
+            .collect::<BTreeSet<_>>();
+        let mut overrides = BTreeMap::new();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|ext| ext != "tpl") {
+                continue;
+            }
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            if !Self::is_known_name(&name, &agents) {
+                eprintln!(
+                    "⚠️  Prompt template {} overrides no template, expected a template name or <AgentName>.<system|opening|closing>.tpl of a built-in or custom agent",
+                    name
+                );
+                continue;
+            }
+            let content = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read prompt template {}", path.display()))?;
+            overrides.insert(name, content);
+        }
+        println!(
+            "📝 Loaded {} prompt template overrides from {}",
+            overrides.len(),
+            dir.display()
+        );
+        Ok(Self { overrides })
+    }
+
+    /// Whether a file name is a named template or a slot of one of the agents
+    fn is_known_name(name: &str, agents: &BTreeSet<String>) -> bool {
+        NAMED_TEMPLATES.iter().any(|(builtin, _)| *builtin == name)
+            || name
+                .strip_suffix(".tpl")
+                .and_then(|stem| stem.rsplit_once('.'))
+                .is_some_and(|(agent, slot)| agents.contains(agent) && SLOTS.contains(&slot))
+    }
+
+    /// Named template, overridden or built-in, with its placeholders filled
+    pub fn render(&self, name: &str, variables: &[(&str, String)]) -> String {
+        let template = self
+            .overrides
+            .get(name)
+            .map(String::as_str)
+            .or_else(|| builtin_template(name))
+            .unwrap_or_default();
+        fill(template, variables)
+    }
+
+    /// Override the slots of a step-forward agent's template and fill their placeholders
+    pub fn apply(&self, agent: &str, template: &mut PromptTemplate, variables: &[(&str, String)]) {
+        for (slot, text) in [
+            ("system", &mut template.system_prompt),
+            ("opening", &mut template.opening_instruction),
+            ("closing", &mut template.closing_instruction),
+        ] {
+            if let Some(content) = self.overrides.get(&slot_file_name(agent, slot)) {
+                *text = content.clone();
+            }
+            *text = fill(text, variables);
+        }
+    }
+
+    /// Version of the templates a prompt is built from, a hash of those that differ from the built-in ones.
+    /// None if the prompt uses built-in templates only
+    pub fn version(&self, names: &[&str]) -> Option<String> {
+        let mut hasher = Md5::new();
+        let mut overridden = false;
+        for name in names {
+            if let Some(content) = self.overrides.get(*name)
+                && builtin_template(name) != Some(content.as_str())
+            {
+                hasher.update(name.as_bytes());
+                hasher.update([0]);
+                hasher.update(content.as_bytes());
+                overridden = true;
+            }
+        }
+        overridden.then(|| format!("{:x}", hasher.finalize())[..12].to_string())
+    }
+
+    /// Version of the slot templates of a step-forward agent, see `version`
+    pub fn agent_version(&self, agent: &str, builtin: &PromptTemplate) -> Option<String> {
+        let mut hasher = Md5::new();
+        let mut overridden = false;
+        for (slot, text) in [
+            ("system", &builtin.system_prompt),
+            ("opening", &builtin.opening_instruction),
+            ("closing", &builtin.closing_instruction),
+        ] {
+            let name = slot_file_name(agent, slot);
+            if let Some(content) = self.overrides.get(&name)
+                && content != text
+            {
+                hasher.update(name.as_bytes());
+                hasher.update([0]);
+                hasher.update(content.as_bytes());
+                overridden = true;
+            }
+        }
+        overridden.then(|| format!("{:x}", hasher.finalize())[..12].to_string())
+    }
+
+    /// Every template version the prompts of a configuration are built from, built-in templates have none
+    pub fn versions(&self, config: &Config) -> BTreeSet<String> {
+        let custom_agents = config.custom_agents.iter().map(|agent| {
+            let agent = CustomAgent::new(agent.clone());
+            (agent.agent_name(), agent.prompt_template())
+        });
+        let agents = agent_templates()
+            .into_iter()
+            .chain(custom_agents)
+            .filter_map(|(agent, template)| self.agent_version(&agent, &template));
+        [
+            CODE_ANALYZE_TEMPLATES,
+            CODE_PURPOSE_ANALYZE_TEMPLATES,
+            KEY_MODULES_INSIGHT_TEMPLATES,
+        ]
+        .into_iter()
+        .filter_map(|names| self.version(names))
+        .chain(agents)
+        .collect()
+    }
+}
+
+fn builtin_template(name: &str) -> Option<&'static str> {
+    NAMED_TEMPLATES
+        .iter()
+        .find(|(builtin, _)| *builtin == name)
+        .map(|(_, content)| *content)
+}
+
+/// File name overriding a slot of a step-forward agent
+pub fn slot_file_name(agent: &str, slot: &str) -> String {
+    format!("{}.{}.tpl", agent, slot)
+}
+
+/// Fill named `{{placeholder}}`s, placeholders without a value are kept as they are
+pub fn fill(template: &str, variables: &[(&str, String)]) -> String {
+    // One pass over the template, so placeholders inside substituted values are left as they are
+    let mut filled = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        filled.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            rest = &rest[start..];
+            break;
+        };
+        let name = &after[..end];
+        match variables.iter().find(|(variable, _)| *variable == name) {
+            Some((_, value)) => filled.push_str(value),
+            None => filled.push_str(&rest[start..start + end + 4]),
+        }
+        rest = &after[end + 2..];
+    }
+    filled.push_str(rest);
+    filled
+}
+
+/// Names of the built-in agents whose templates can be overridden
+pub fn builtin_agent_names() -> Vec<String> {
+    agent_templates().into_iter().map(|(agent, _)| agent).collect()
+}
+
+/// Default templates of the built-in step-forward agents, by agent name
+fn agent_templates() -> Vec<(String, PromptTemplate)> {
+    fn entry(agent: impl StepForwardAgent) -> (String, PromptTemplate) {
+        (agent.agent_name(), agent.prompt_template())
+    }
+    vec![
+        entry(SystemContextResearcher),
+        entry(DomainModulesDetector),
+        entry(ArchitectureResearcher),
+        entry(WorkflowResearcher),
+        entry(BoundaryAnalyzer),
+        entry(DatabaseOverviewAnalyzer),
+        entry(OverviewEditor),
+        entry(ArchitectureEditor),
+        entry(WorkflowEditor),
+        KeyModulesInsightEditor::domain_editor_template(),
+        entry(BoundaryEditor),
+        entry(DatabaseEditor),
+    ]
+}
+
+/// Write the built-in templates to a directory (the configured `prompts_dir` by default) as a starting
+/// point for overrides. Existing files are kept unless `force` is set
+pub fn export(config: &Config, output: Option<PathBuf>, force: bool) -> Result<()> {
+    let dir = output
+        .or_else(|| config.prompts_dir.clone())
+        .unwrap_or_else(|| PathBuf::from("prompts"));
+    std::fs::create_dir_all(&dir)?;
+
+    let mut files: Vec<(String, String)> = NAMED_TEMPLATES
+        .iter()
+        .map(|(name, content)| (name.to_string(), content.to_string()))
+        .collect();
+    for (agent, template) in agent_templates() {
+        files.push((slot_file_name(&agent, "system"), template.system_prompt));
+        files.push((slot_file_name(&agent, "opening"), template.opening_instruction));
+        files.push((slot_file_name(&agent, "closing"), template.closing_instruction));
+    }
+
+    let (mut written, mut skipped) = (0, 0);
+    for (name, content) in files {
+        let path: &Path = &dir.join(&name);
+        if path.exists() && !force {
+            skipped += 1;
+            continue;
+        }
+        std::fs::write(path, content)
+            .with_context(|| format!("Failed to write prompt template {}", path.display()))?;
+        written += 1;
+    }
+
+    println!("✅ Exported {} prompt templates to {}", written, dir.display());
+    if skipped > 0 {
+        println!(
+            "ℹ️  Kept {} existing templates, use --force to overwrite them",
+            skipped
+        );
+    }
+    println!(
+        "💡 Set prompts_dir = \"{}\" in litho.toml and delete the templates you keep unchanged",
+        dir.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::step_forward_agent::{FormatterConfig, LLMCallMode};
+
+    #[test]
+    fn test_overrides_and_versions() {
+        let templates = PromptTemplates {
+            overrides: BTreeMap::from([
+                (CODE_ANALYZE_SYS.to_string(), builtin_template(CODE_ANALYZE_SYS).unwrap().to_string()),
+                (CODE_PURPOSE_ANALYZE_USER.to_string(), "Classify {{file_name}} at {{file_path}}".to_string()),
+                ("Writer.opening.tpl".to_string(), "Document {{domain_name}}".to_string()),
+            ]),
+        };
+
+        let rendered = templates.render(
+            CODE_PURPOSE_ANALYZE_USER,
+            &[("file_path", "src/main.rs".to_string()), ("file_name", "main.rs".to_string())],
+        );
+        assert_eq!(rendered, "Classify main.rs at src/main.rs");
+        // An override identical to the built-in template does not change the version
+        assert_eq!(templates.version(&[CODE_ANALYZE_SYS, CODE_ANALYZE_USER]), None);
+        assert!(templates.version(&[CODE_PURPOSE_ANALYZE_USER]).is_some());
+
+        let builtin = PromptTemplate {
+            system_prompt: "You write documentation".to_string(),
+            opening_instruction: "Document the {{domain_name}} domain".to_string(),
+            closing_instruction: String::new(),
+            llm_call_mode: LLMCallMode::Prompt,
+            formatter_config: FormatterConfig::default(),
+        };
+        let mut template = builtin.clone();
+        templates.apply("Writer", &mut template, &[("domain_name", "Billing".to_string())]);
+        assert_eq!(template.system_prompt, "You write documentation");
+        assert_eq!(template.opening_instruction, "Document Billing");
+        assert!(templates.agent_version("Writer", &builtin).is_some());
+        assert_eq!(templates.agent_version("Reader", &builtin), None);
+
+        let agents = BTreeSet::from(["ArchitectureEditor".to_string(), "SecurityReviewer".to_string()]);
+        assert!(PromptTemplates::is_known_name(CODE_ANALYZE_USER, &agents));
+        assert!(PromptTemplates::is_known_name("ArchitectureEditor.closing.tpl", &agents));
+        assert!(PromptTemplates::is_known_name("SecurityReviewer.system.tpl", &agents));
+        assert!(!PromptTemplates::is_known_name("ArchitectureEditor.footer.tpl", &agents));
+        assert!(!PromptTemplates::is_known_name("ArchitectureEditr.closing.tpl", &agents));
+    }
+
+    #[test]
+    fn test_fill_substitutes_template_placeholders_only() {
+        let variables = [
+            ("code", "let s = \"{{name}}\";".to_string()),
+            ("name", "main.rs".to_string()),
+        ];
+        assert_eq!(
+            fill("{{name}}: {{code}} {{unknown}} {{", &variables),
+            "main.rs: let s = \"{{name}}\"; {{unknown}} {{"
+        );
+        assert!(builtin_agent_names().contains(&"ArchitectureEditor".to_string()));
+    }
+}
@@ -32,6 +32,7 @@ impl StepForwardAgent for ArchitectureResearcher {
             optional_sources: vec![
                 DataSource::PROJECT_STRUCTURE,
                 DataSource::DEPENDENCY_ANALYSIS,
+                DataSource::GIT_HISTORY,
                 // Use architecture, deployment, database and ADR docs for architecture analysis
                 DataSource::knowledge_categories(vec!["architecture", "deployment", "database", "adr"]),
             ],
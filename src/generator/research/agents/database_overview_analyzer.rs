@@ -30,6 +30,10 @@ impl StepForwardAgent for DatabaseOverviewAnalyzer {
         crate::generator::research::memory::MemoryScope::STUDIES_RESEARCH.to_string()
     }
 
+    fn is_optional(&self) -> bool {
+        true
+    }
+
     fn data_config(&self) -> AgentDataConfig {
         AgentDataConfig {
             required_sources: vec![
@@ -6,10 +6,14 @@ use crate::generator::research::types::{
 use crate::generator::{
     agent_executor::{AgentExecuteParams, extract},
     context::GeneratorContext,
+    prompt_templates::{
+        KEY_MODULES_INSIGHT_SYS, KEY_MODULES_INSIGHT_TEMPLATES, KEY_MODULES_INSIGHT_USER, PromptTemplates,
+    },
     step_forward_agent::{
         AgentDataConfig, DataSource, FormatterConfig, LLMCallMode, PromptTemplate, StepForwardAgent,
     },
 };
+use crate::llm::client::budget::BudgetExhausted;
 use crate::types::code::CodeInsight;
 use crate::utils::threads::do_parallel_with_limit;
 use anyhow::{Result, anyhow};
@@ -77,9 +81,26 @@ If available:
 
         Ok(reports)
     }
+
+    /// Its own result and the report of every analyzed domain
+    async fn output_keys(&self, context: &GeneratorContext) -> Vec<String> {
+        let reports: Vec<KeyModuleReport> = context
+            .get_research(&self.agent_type())
+            .await
+            .and_then(|value| serde_json::from_value(value).ok())
+            .unwrap_or_default();
+        std::iter::once(self.agent_type())
+            .chain(reports.iter().map(|report| Self::domain_key(&report.domain_name)))
+            .collect()
+    }
 }
 
 impl KeyModulesInsight {
+    /// Research key of the report of one domain
+    pub fn domain_key(domain_name: &str) -> String {
+        format!("{}_{}", AgentType::KeyModulesInsight, domain_name)
+    }
+
     // Multi-domain analysis main logic
     async fn execute_multi_domain_analysis(
         &self,
@@ -127,11 +148,12 @@ impl KeyModulesInsight {
 
         // Process analysis results
         let mut successful_analyses = 0;
+        let mut budget_error = None;
         for (domain_name, result) in analysis_results {
             match result {
                 Ok(report) => {
                     // Store results for each domain
-                    let storage_key = format!("{}_{}", self.agent_type(), domain_name);
+                    let storage_key = Self::domain_key(&domain_name);
                     context
                         .store_research(&storage_key, serde_json::to_value(&report)?)
                         .await?;
@@ -139,6 +161,7 @@ impl KeyModulesInsight {
                     reports.push(report);
                     println!("✅ Domain module analysis: {} completed and stored", domain_name);
                 }
+                Err(e) if BudgetExhausted::is(&e) => budget_error = Some(e),
                 Err(e) => {
                     let msg = context.config.target_language.msg_domain_analysis_failed();
                     println!("{}", msg.replace("{}", &domain_name).replace("{}", &e.to_string()));
@@ -147,6 +170,10 @@ impl KeyModulesInsight {
             }
         }
 
+        // Stop the run once the budget is used up, finished domains stay in Memory
+        if let Some(e) = budget_error {
+            return Err(e);
+        }
         if successful_analyses == 0 {
             return Err(anyhow!("All domain analyses failed"));
         }
@@ -232,7 +259,9 @@ impl KeyModulesInsight {
             .await?;
 
         // 2. Build domain-specific prompt
-        let (system_prompt, user_prompt) = self.build_domain_prompt(domain, &filtered_insights);
+        let templates = &context.prompt_templates;
+        let (system_prompt, user_prompt) =
+            self.build_domain_prompt(templates, domain, &filtered_insights);
 
         // 3. Use agent_executor::extract for analysis
         let params = AgentExecuteParams {
@@ -244,7 +273,9 @@ impl KeyModulesInsight {
                 self.agent_type(),
                 domain.name
             ),
+            agent: self.agent_name(),
             log_tag: format!("{} domain analysis", domain.name),
+            template_version: templates.version(KEY_MODULES_INSIGHT_TEMPLATES),
         };
 
         println!("🤖 Analyzing '{}' domain...", domain.name);
@@ -263,22 +294,23 @@ impl KeyModulesInsight {
     // Build domain-specific prompt
     fn build_domain_prompt(
         &self,
+        templates: &PromptTemplates,
         domain: &DomainModule,
         insights: &[CodeInsight],
     ) -> (String, String) {
-        let system_prompt =
-            "Based on the information provided by the user, conduct in-depth and rigorous analysis and provide results in the specified format".to_string();
-
-        let user_prompt = format!(
-            "## Domain Analysis Task\nAnalyze the core module technical details of the '{}' domain\n\n### Domain Information\n- Domain Name: {}\n- Domain Type: {}\n- Importance: {:.1}/10\n- Complexity: {:.1}/10\n- Description: {}\n\n### Submodule Overview\n{}\n\n### Related Code Insights\n{}\n",
-            domain.name,
-            domain.name,
-            domain.domain_type,
-            domain.importance,
-            domain.complexity,
-            domain.description,
-            self.format_sub_modules(&domain.sub_modules),
-            self.format_filtered_insights(insights)
+        let system_prompt = templates.render(KEY_MODULES_INSIGHT_SYS, &[]);
+
+        let user_prompt = templates.render(
+            KEY_MODULES_INSIGHT_USER,
+            &[
+                ("domain_name", domain.name.clone()),
+                ("domain_type", domain.domain_type.clone()),
+                ("importance", format!("{:.1}", domain.importance)),
+                ("complexity", format!("{:.1}", domain.complexity)),
+                ("description", domain.description.clone()),
+                ("sub_modules", self.format_sub_modules(&domain.sub_modules)),
+                ("code_insights", self.format_filtered_insights(insights)),
+            ],
         );
 
         (system_prompt, user_prompt)
@@ -0,0 +1 @@
+Based on the information provided by the user, conduct in-depth and rigorous analysis and provide results in the specified format
\ No newline at end of file
@@ -0,0 +1,15 @@
+## Domain Analysis Task
+Analyze the core module technical details of the '{{domain_name}}' domain
+
+### Domain Information
+- Domain Name: {{domain_name}}
+- Domain Type: {{domain_type}}
+- Importance: {{importance}}/10
+- Complexity: {{complexity}}/10
+- Description: {{description}}
+
+### Submodule Overview
+{{sub_modules}}
+
+### Related Code Insights
+{{code_insights}}
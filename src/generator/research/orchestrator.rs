@@ -1,6 +1,12 @@
-use anyhow::Result;
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use futures::stream::{FuturesUnordered, StreamExt};
+use std::collections::VecDeque;
 
+use crate::generator::checkpoint::execute_with_checkpoint;
+use crate::config::CustomAgentStage;
 use crate::generator::context::GeneratorContext;
+use crate::generator::custom_agent::CustomAgent;
 use crate::generator::research::agents::architecture_researcher::ArchitectureResearcher;
 use crate::generator::research::agents::boundary_analyzer::BoundaryAnalyzer;
 use crate::generator::research::agents::database_overview_analyzer::DatabaseOverviewAnalyzer;
@@ -8,50 +14,157 @@ use crate::generator::research::agents::domain_modules_detector::DomainModulesDe
 use crate::generator::research::agents::key_modules_insight::KeyModulesInsight;
 use crate::generator::research::agents::system_context_researcher::SystemContextResearcher;
 use crate::generator::research::agents::workflow_researcher::WorkflowResearcher;
-use crate::generator::step_forward_agent::StepForwardAgent;
+use crate::generator::step_forward_agent::{DataSource, StepForwardAgent};
 use crate::generator::preprocess::memory::{MemoryScope, ScopedKeys};
 use crate::types::code::{CodeInsight, CodePurpose};
 
+/// Research agent with its output type erased, so that different agents can be scheduled together
+#[async_trait]
+trait ResearchTask: Send + Sync {
+    /// Key under which the agent stores its research result
+    fn result_key(&self) -> String;
+
+    /// Research results the agent depends on, as (required, optional)
+    fn research_dependencies(&self) -> (Vec<String>, Vec<String>);
+
+    async fn run(&self, context: &GeneratorContext) -> Result<()>;
+}
+
+#[async_trait]
+impl<T> ResearchTask for T
+where
+    T: StepForwardAgent + Send + Sync,
+{
+    fn result_key(&self) -> String {
+        self.agent_type()
+    }
+
+    fn research_dependencies(&self) -> (Vec<String>, Vec<String>) {
+        let config = self.data_config();
+        let research_keys = |sources: Vec<DataSource>| -> Vec<String> {
+            sources
+                .into_iter()
+                .filter_map(|source| match source {
+                    DataSource::ResearchResult(key) => Some(key),
+                    _ => None,
+                })
+                .collect()
+        };
+        (research_keys(config.required_sources), research_keys(config.optional_sources))
+    }
+
+    async fn run(&self, context: &GeneratorContext) -> Result<()> {
+        // Use localized agent name if available
+        let agent_name = if let Some(agent_enum) = self.agent_type_enum() {
+            agent_enum.display_name(&context.config.target_language)
+        } else {
+            self.agent_type()
+        };
+
+        println!("🤖 Executing {} agent analysis...", agent_name);
+
+        execute_with_checkpoint(self, context).await?;
+        println!("✓ {} analysis completed", agent_name);
+        Ok(())
+    }
+}
+
 /// Multi-agent research orchestrator
 #[derive(Default)]
 pub struct ResearchOrchestrator;
 
 impl ResearchOrchestrator {
-    /// Execute all agent analysis pipelines
+    /// Execute all agent analysis pipelines, agents whose dependencies are satisfied run concurrently
     pub async fn execute_research_pipeline(&self, context: &GeneratorContext) -> Result<()> {
         println!("🚀 Starting Litho Studies Research investigation pipeline...");
 
-        // First layer: Macro analysis (C1)
-        self.execute_agent(&SystemContextResearcher, context)
-            .await?;
-
-        // Second layer: Meso analysis (C2)
-        self.execute_agent(&DomainModulesDetector, context)
-            .await?;
-        self.execute_agent(&ArchitectureResearcher, context)
-            .await?;
-        self.execute_agent(&WorkflowResearcher, context)
-            .await?;
-
-        // Third layer: Micro analysis (C3-C4)
-        self.execute_agent(&KeyModulesInsight, context)
-            .await?;
-
-        // Boundary interface analysis
-        self.execute_agent(&BoundaryAnalyzer::default(), context)
-            .await?;
+        let mut tasks: Vec<Box<dyn ResearchTask>> = vec![
+            // Macro analysis (C1)
+            Box::new(SystemContextResearcher),
+            // Meso analysis (C2)
+            Box::new(DomainModulesDetector),
+            Box::new(ArchitectureResearcher),
+            Box::new(WorkflowResearcher),
+            // Micro analysis (C3-C4)
+            Box::new(KeyModulesInsight),
+            // Boundary interface analysis
+            Box::new(BoundaryAnalyzer::default()),
+        ];
 
         // Database overview analysis (only if database files exist)
         if self.has_database_files(context).await {
-            self.execute_agent(&DatabaseOverviewAnalyzer::default(), context)
-                .await?;
+            tasks.push(Box::new(DatabaseOverviewAnalyzer::default()));
+        }
+
+        // User-defined research agents declared in litho.toml
+        for agent in CustomAgent::for_stage(&context.config.custom_agents, CustomAgentStage::Research) {
+            tasks.push(Box::new(agent));
         }
 
+        // Validate the graph before any tokens are spent
+        let nodes: Vec<_> = tasks
+            .iter()
+            .map(|task| {
+                let (required, optional) = task.research_dependencies();
+                (task.result_key(), required, optional)
+            })
+            .collect();
+        let dependencies = dependency_graph(&nodes)?;
+
+        let max_parallels = context.config.llm.max_parallels.max(1);
+        println!(
+            "🧭 Research plan: {} agents, up to {} in parallel",
+            tasks.len(),
+            max_parallels
+        );
+
+        self.execute_graph(&tasks, &dependencies, max_parallels, context)
+            .await?;
+
         println!("✓ Litho Studies Research pipeline execution completed");
 
         Ok(())
     }
 
+    /// Run tasks as soon as all their dependencies have completed, at most `max_parallels` at a time
+    async fn execute_graph(
+        &self,
+        tasks: &[Box<dyn ResearchTask>],
+        dependencies: &[Vec<usize>],
+        max_parallels: usize,
+        context: &GeneratorContext,
+    ) -> Result<()> {
+        let mut remaining: Vec<usize> = dependencies.iter().map(|deps| deps.len()).collect();
+        let mut ready: VecDeque<usize> = (0..tasks.len()).filter(|&i| remaining[i] == 0).collect();
+        let mut running = FuturesUnordered::new();
+
+        loop {
+            while running.len() < max_parallels {
+                let Some(index) = ready.pop_front() else {
+                    break;
+                };
+                let task = &tasks[index];
+                running.push(async move { (index, task.run(context).await) });
+            }
+
+            let Some((finished, result)) = running.next().await else {
+                break;
+            };
+            result?;
+
+            for (index, deps) in dependencies.iter().enumerate() {
+                if deps.contains(&finished) {
+                    remaining[index] -= 1;
+                    if remaining[index] == 0 {
+                        ready.push_back(index);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     /// Check if the project has database-related files
     async fn has_database_files(&self, context: &GeneratorContext) -> bool {
         if let Some(insights) = context
@@ -67,27 +180,99 @@ impl ResearchOrchestrator {
             false
         }
     }
+}
 
-    /// Execute a single agent
-    async fn execute_agent<T>(
-        &self,
-        agent: &T,
-        context: &GeneratorContext,
-    ) -> Result<()>
-    where
-        T: StepForwardAgent + Send + Sync,
-    {
-        // Use localized agent name if available
-        let agent_name = if let Some(agent_enum) = agent.agent_type_enum() {
-            agent_enum.display_name(&context.config.target_language)
-        } else {
-            agent.agent_type()
-        };
-        
-        println!("🤖 Executing {} agent analysis...", agent_name);
+/// Build the dependency graph of research agents, nodes are (result key, required results, optional results)
+///
+/// Returns the indices each node depends on. Fails if a required result has no producer or the graph has a cycle.
+fn dependency_graph(nodes: &[(String, Vec<String>, Vec<String>)]) -> Result<Vec<Vec<usize>>> {
+    let producer = |key: &str| nodes.iter().position(|(produced, _, _)| produced == key);
 
-        agent.execute(context).await?;
-        println!("✓ {} analysis completed", agent_name);
-        Ok(())
+    let mut dependencies = Vec::with_capacity(nodes.len());
+    for (key, required, optional) in nodes {
+        let mut deps = Vec::new();
+        for required_key in required {
+            match producer(required_key) {
+                Some(index) => deps.push(index),
+                None => {
+                    return Err(anyhow!(
+                        "Research agent [{}] requires [{}], but no agent in the pipeline produces it",
+                        key,
+                        required_key
+                    ));
+                }
+            }
+        }
+        // Optional results only impose an order when their producer takes part in this run
+        deps.extend(optional.iter().filter_map(|optional_key| producer(optional_key)));
+        deps.sort_unstable();
+        deps.dedup();
+        dependencies.push(deps);
+    }
+
+    // Kahn's algorithm, whatever cannot be ordered is part of a cycle
+    let mut remaining: Vec<usize> = dependencies.iter().map(|deps| deps.len()).collect();
+    let mut queue: VecDeque<usize> = (0..nodes.len()).filter(|&i| remaining[i] == 0).collect();
+    let mut ordered = 0;
+    while let Some(current) = queue.pop_front() {
+        ordered += 1;
+        for (index, deps) in dependencies.iter().enumerate() {
+            if deps.contains(&current) {
+                remaining[index] -= 1;
+                if remaining[index] == 0 {
+                    queue.push_back(index);
+                }
+            }
+        }
+    }
+    if ordered < nodes.len() {
+        let cyclic: Vec<&str> = (0..nodes.len())
+            .filter(|&i| remaining[i] > 0)
+            .map(|i| nodes[i].0.as_str())
+            .collect();
+        return Err(anyhow!(
+            "Dependency cycle detected among research agents: {}",
+            cyclic.join(", ")
+        ));
+    }
+
+    Ok(dependencies)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(key: &str, required: &[&str], optional: &[&str]) -> (String, Vec<String>, Vec<String>) {
+        (
+            key.to_string(),
+            required.iter().map(|s| s.to_string()).collect(),
+            optional.iter().map(|s| s.to_string()).collect(),
+        )
+    }
+
+    #[test]
+    fn test_dependency_graph_resolves_producers() {
+        let nodes = vec![
+            node("context", &[], &[]),
+            node("domains", &["context"], &[]),
+            node("architecture", &["context", "domains"], &["database"]),
+        ];
+        let graph = dependency_graph(&nodes).unwrap();
+        assert_eq!(graph, vec![vec![], vec![0], vec![0, 1]]);
+    }
+
+    #[test]
+    fn test_dependency_graph_rejects_missing_producer_and_cycle() {
+        let missing = vec![node("domains", &["context"], &[])];
+        assert!(dependency_graph(&missing).unwrap_err().to_string().contains("no agent"));
+
+        let cyclic = vec![
+            node("context", &[], &[]),
+            node("a", &["b"], &[]),
+            node("b", &[], &["a"]),
+        ];
+        let error = dependency_graph(&cyclic).unwrap_err().to_string();
+        assert!(error.contains("a, b"));
     }
 }
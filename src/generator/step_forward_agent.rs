@@ -10,7 +10,7 @@ use crate::generator::research::memory::MemoryRetriever;
 use crate::{
     generator::context::GeneratorContext,
     types::{
-        code::CodeInsight, code_releationship::RelationshipAnalysis,
+        code::CodeInsight, code_releationship::RelationshipAnalysis, git_history::GitHistory,
         project_structure::ProjectStructure,
     },
     utils::project_structure_formatter::ProjectStructureFormatter,
@@ -61,6 +61,10 @@ impl DataSource {
         scope: MemoryScope::PREPROCESS,
         key: ScopedKeys::ORIGINAL_DOCUMENT,
     };
+    pub const GIT_HISTORY: DataSource = DataSource::MemoryData {
+        scope: MemoryScope::PREPROCESS,
+        key: ScopedKeys::GIT_HISTORY,
+    };
 
     /// Create a data source for specific knowledge categories
     pub fn knowledge_categories(categories: Vec<&str>) -> DataSource {
@@ -253,6 +257,43 @@ impl DataFormatter {
         content
     }
 
+    /// Format version control history
+    pub fn format_git_history(&self, history: &GitHistory) -> String {
+        let mut content = format!(
+            "### Version Control History (latest {} commits)\n#### Most Changed Files\n",
+            history.analyzed_commits
+        );
+        for file in history.files.iter().take(self.config.code_insights_limit) {
+            content.push_str(&format!(
+                "- `{}`: {} commits by {} authors, last changed {} by {}; recent changes: {}\n",
+                file.path,
+                file.commits,
+                file.authors,
+                file.last_changed,
+                file.last_author,
+                file.recent_messages.join(" | ")
+            ));
+        }
+        if !history.co_changes.is_empty() {
+            content.push_str("#### Files Often Changed Together\n");
+            for pair in history.co_changes.iter().take(self.config.dependency_limit) {
+                content.push_str(&format!(
+                    "- `{}` <-> `{}` ({} commits)\n",
+                    pair.first, pair.second, pair.commits
+                ));
+            }
+        }
+        content.push_str("#### Recent Commits\n");
+        for commit in &history.recent_commits {
+            content.push_str(&format!(
+                "- {} {}: {}\n",
+                commit.date, commit.author, commit.subject
+            ));
+        }
+        content.push('\n');
+        content
+    }
+
     /// Emergency content truncation when compression fails
     fn emergency_truncate(&self, content: &str, content_type: &str) -> Result<String> {
         // For code insights, truncate more aggressively
@@ -473,6 +514,16 @@ impl GeneratorPromptBuilder {
                             prompt.push_str(&compressed);
                         }
                     }
+                    ScopedKeys::GIT_HISTORY => {
+                        if let Some(history) = context.get_from_memory::<GitHistory>(scope, key).await {
+                            let formatted = self.formatter.format_git_history(&history);
+                            let compressed = self
+                                .formatter
+                                .compress_content_if_needed(context, &formatted, "Git History")
+                                .await?;
+                            prompt.push_str(&compressed);
+                        }
+                    }
                     _ => {}
                 },
                 DataSource::ResearchResult(agent_type) => {
@@ -535,12 +586,23 @@ pub trait StepForwardAgent: Send + Sync {
 
     fn memory_scope_key(&self) -> String;
 
+    /// Agent name used to route its LLM calls, the type name by default (e.g. `KeyModulesInsight`)
+    fn agent_name(&self) -> String {
+        let type_name = std::any::type_name::<Self>();
+        type_name.rsplit("::").next().unwrap_or(type_name).to_string()
+    }
+
     /// Data source configuration
     fn data_config(&self) -> AgentDataConfig;
 
     /// Prompt template configuration
     fn prompt_template(&self) -> PromptTemplate;
 
+    /// Values of the named `{{placeholder}}`s in the prompt template
+    fn prompt_variables(&self) -> Vec<(&'static str, String)> {
+        Vec::new()
+    }
+
     /// Optional post-processing hook
     fn post_process(&self, _result: &Self::Output, _context: &GeneratorContext) -> Result<()> {
         Ok(())
@@ -558,6 +620,16 @@ pub trait StepForwardAgent: Send + Sync {
         false
     }
 
+    /// Whether the agent may be skipped when the run's LLM budget is nearly used up
+    fn is_optional(&self) -> bool {
+        false
+    }
+
+    /// Memory keys the agent wrote to its scope, checkpointed and reused together. Its own result by default
+    async fn output_keys(&self, _context: &GeneratorContext) -> Vec<String> {
+        vec![self.agent_type()]
+    }
+
     /// Default implementation of execute method - Fully standardized with automatic data validation
     async fn execute(&self, context: &GeneratorContext) -> Result<Self::Output> {
         // 1. Get data configuration
@@ -587,7 +659,18 @@ pub trait StepForwardAgent: Send + Sync {
         let all_sources = [config.required_sources, config.optional_sources].concat();
 
         // 4. Build prompt using standard template and adjust according to target language
-        let template = self.prompt_template();
+        let mut template = self.prompt_template();
+        let agent_name = self.agent_name();
+        let template_version = context.prompt_templates.agent_version(&agent_name, &template);
+        context
+            .prompt_templates
+            .apply(&agent_name, &mut template, &self.prompt_variables());
+
+        // Send fewer code insights once the LLM budget is nearly used up
+        if context.llm_client.budget().is_degraded() {
+            let limit = &mut template.formatter_config.code_insights_limit;
+            *limit = (*limit / 2).max(1);
+        }
 
         // Add language instruction based on configured target language
         let language_instruction = context.config.target_language.prompt_instruction();
@@ -619,7 +702,9 @@ pub trait StepForwardAgent: Send + Sync {
             prompt_sys: system_prompt,
             prompt_user: user_prompt,
             cache_scope: format!("{}/{}", self.memory_scope_key(), agent_type_value.as_str()),
+            agent: agent_name,
             log_tag,
+            template_version,
         };
 
         let result_value = match template.llm_call_mode {
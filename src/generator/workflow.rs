@@ -1,8 +1,15 @@
 use std::sync::Arc;
 use std::time::Instant;
 
+use crate::generator::checkpoint::RunCheckpoint;
 use crate::generator::compose::DocumentationComposer;
+use crate::generator::custom_agent::CustomAgent;
+use crate::generator::incremental::IncrementalRun;
+use crate::generator::compose::memory::MemoryScope as ComposeMemoryScope;
 use crate::generator::outlet::{DiskOutlet, DocTree, Outlet, SummaryOutlet};
+use crate::generator::preprocess::memory::MemoryScope as PreprocessMemoryScope;
+use crate::generator::prompt_templates::PromptTemplates;
+use crate::generator::research::memory::MemoryScope as ResearchMemoryScope;
 use crate::{
     cache::CacheManager,
     config::Config,
@@ -10,10 +17,10 @@ use crate::{
         context::GeneratorContext, preprocess::PreProcessAgent,
         research::orchestrator::ResearchOrchestrator, types::Generator,
     },
-    llm::client::LLMClient,
+    llm::client::{LLMClient, budget::BudgetExhausted},
     memory::Memory,
 };
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use tokio::sync::RwLock;
 
 /// Memory scope and key definitions for workflow timing statistics
@@ -43,21 +50,47 @@ impl TimingKeys {
 }
 
 pub async fn launch(c: &Config) -> Result<()> {
+    let checkpoint = RunCheckpoint::create(c)?;
+    execute_run(c, checkpoint).await
+}
+
+/// Resume an interrupted run from its checkpoint, continuing from the first unfinished agent
+pub async fn resume(c: &Config, run_id: Option<&str>) -> Result<()> {
+    let checkpoint = RunCheckpoint::open(c, run_id)?;
+    execute_run(c, checkpoint).await
+}
+
+async fn execute_run(c: &Config, checkpoint: RunCheckpoint) -> Result<()> {
     let overall_start = Instant::now();
 
+    CustomAgent::validate(&c.custom_agents)?;
+
     let config = c.clone();
     let llm_client = LLMClient::new(config.clone())?;
     let cache_manager = Arc::new(RwLock::new(CacheManager::new(
         config.cache.clone(),
         config.target_language.clone(),
-    )));
-    let memory = Arc::new(RwLock::new(Memory::new()));
+        llm_client.pricing(),
+    )?));
+    let mut memory = Memory::new();
+    checkpoint.restore_into(&mut memory)?;
+    let incremental = if config.incremental {
+        Some(Arc::new(IncrementalRun::prepare(&config)?))
+    } else {
+        None
+    };
 
+    let memory = Arc::new(RwLock::new(memory));
+    let prompt_templates = Arc::new(PromptTemplates::load(&config)?);
     let context = GeneratorContext {
-        llm_client,
+        llm_client: llm_client.with_memory(memory.clone()),
         config,
         cache_manager,
         memory,
+        checkpoint: Arc::new(checkpoint),
+        incremental,
+        dry_run: None,
+        prompt_templates,
     };
 
     // Sync external knowledge if configured
@@ -73,10 +106,84 @@ pub async fn launch(c: &Config) -> Result<()> {
         }
     }
 
+    // Execute preprocessing, research and document generation
+    let mut doc_tree = DocTree::new(&context.config.target_language);
+    let budget_error = match execute_stages(&context, &mut doc_tree).await {
+        Ok(()) => None,
+        Err(e) if BudgetExhausted::is(&e) => {
+            println!("\n🛑 {}, saving partial results", e);
+            DocumentationComposer
+                .restore_doc_tree(&context, &mut doc_tree)
+                .await;
+            Some(e)
+        }
+        Err(e) => return Err(e),
+    };
+
+    let used_cache_entries = context.cache_manager.read().await.used_llm_entries();
+    context.checkpoint.record_cache_entries(&used_cache_entries).await?;
+
+    // Execute document storage
+    let output_start = Instant::now();
+    let outlet = DiskOutlet::new(doc_tree);
+    outlet.save(&context).await?;
+
+    // Generate and save summary report
+    let summary_outlet = SummaryOutlet::new();
+    summary_outlet.save(&context).await?;
+
+    let output_time = output_start.elapsed().as_secs_f64();
+    context
+        .store_to_memory(TimingScope::TIMING, TimingKeys::OUTPUT, output_time)
+        .await?;
+    println!("\n=== Document storage completed (Duration: {:.2}s) ===", output_time);
+
+    // Record total execution time
+    let total_time = overall_start.elapsed().as_secs_f64();
+    context
+        .store_to_memory(TimingScope::TIMING, TimingKeys::TOTAL_EXECUTION, total_time)
+        .await?;
+
+    if let Some(e) = budget_error {
+        println!(
+            "💡 Finished agents are checkpointed, raise [llm.budget] and run `deepwiki-rs resume` to continue"
+        );
+        return Err(e);
+    }
+
+    context.checkpoint.finish().await?;
+
+    println!("\n🎉 All processes execution completed! Total duration: {:.2}s", total_time);
+
+    Ok(())
+}
+
+/// Run the preprocessing, research and document generation stages
+async fn execute_stages(context: &GeneratorContext, doc_tree: &mut DocTree) -> Result<()> {
     // Preprocessing stage
     let preprocess_start = Instant::now();
-    let preprocess_agent = PreProcessAgent::new();
-    preprocess_agent.execute(context.clone()).await?;
+    if context.config.skip_preprocessing {
+        restore_skipped_stage(context, PreprocessMemoryScope::PREPROCESS, "--skip-preprocessing")
+            .await?;
+    } else if context.checkpoint.is_completed(PreprocessMemoryScope::PREPROCESS) {
+        println!("⏭️  Preprocessing restored from checkpoint, skipping");
+    } else {
+        let preprocess_agent = PreProcessAgent::new();
+        preprocess_agent.execute(context.clone()).await?;
+        let keys = context
+            .list_memory_keys(PreprocessMemoryScope::PREPROCESS)
+            .await;
+        context
+            .checkpoint_step(
+                PreprocessMemoryScope::PREPROCESS,
+                PreprocessMemoryScope::PREPROCESS,
+                &keys,
+            )
+            .await?;
+        context
+            .save_memory_snapshot(PreprocessMemoryScope::PREPROCESS)
+            .await?;
+    }
     let preprocess_time = preprocess_start.elapsed().as_secs_f64();
     context
         .store_to_memory(TimingScope::TIMING, TimingKeys::PREPROCESS, preprocess_time)
@@ -88,10 +195,18 @@ pub async fn launch(c: &Config) -> Result<()> {
 
     // Execute multi-agent research stage
     let research_start = Instant::now();
-    let research_orchestrator = ResearchOrchestrator::default();
-    research_orchestrator
-        .execute_research_pipeline(&context)
-        .await?;
+    if context.config.skip_research {
+        restore_skipped_stage(context, ResearchMemoryScope::STUDIES_RESEARCH, "--skip-research")
+            .await?;
+    } else {
+        let research_orchestrator = ResearchOrchestrator::default();
+        research_orchestrator
+            .execute_research_pipeline(context)
+            .await?;
+        context
+            .save_memory_snapshot(ResearchMemoryScope::STUDIES_RESEARCH)
+            .await?;
+    }
     let research_time = research_start.elapsed().as_secs_f64();
     context
         .store_to_memory(TimingScope::TIMING, TimingKeys::RESEARCH, research_time)
@@ -100,39 +215,95 @@ pub async fn launch(c: &Config) -> Result<()> {
 
     // Execute document generation process
     let compose_start = Instant::now();
-    let mut doc_tree = DocTree::new(&context.config.target_language);
     let documentation_orchestrator = DocumentationComposer::default();
-    documentation_orchestrator
-        .execute(&context, &mut doc_tree)
-        .await?;
+    if context.config.skip_documentation {
+        restore_skipped_stage(context, ComposeMemoryScope::DOCUMENTATION, "--skip-documentation")
+            .await?;
+        documentation_orchestrator
+            .restore_doc_tree(context, doc_tree)
+            .await;
+    } else {
+        documentation_orchestrator
+            .execute(context, doc_tree)
+            .await?;
+        context
+            .save_memory_snapshot(ComposeMemoryScope::DOCUMENTATION)
+            .await?;
+    }
     let compose_time = compose_start.elapsed().as_secs_f64();
     context
         .store_to_memory(TimingScope::TIMING, TimingKeys::COMPOSE, compose_time)
         .await?;
     println!("\n=== Document generation completed (Duration: {:.2}s) ===", compose_time);
 
-    // Execute document storage
-    let output_start = Instant::now();
-    let outlet = DiskOutlet::new(doc_tree);
-    outlet.save(&context).await?;
+    Ok(())
+}
 
-    // Generate and save summary report
-    let summary_outlet = SummaryOutlet::new();
-    summary_outlet.save(&context).await?;
+/// Restore the outputs of a skipped stage from the snapshot written by a previous run
+async fn restore_skipped_stage(context: &GeneratorContext, scope: &str, flag: &str) -> Result<()> {
+    if !context.restore_memory_snapshot(scope).await? {
+        return Err(anyhow!(
+            "{} requires a snapshot of [{}] in {}, please run once without this flag first",
+            flag,
+            scope,
+            context.snapshot_dir().display()
+        ));
+    }
+    Ok(())
+}
 
-    let output_time = output_start.elapsed().as_secs_f64();
-    context
-        .store_to_memory(TimingScope::TIMING, TimingKeys::OUTPUT, output_time)
-        .await?;
-    println!("\n=== Document storage completed (Duration: {:.2}s) ===", output_time);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::preprocess::memory::ScopedKeys as PreprocessKeys;
 
-    // Record total execution time
-    let total_time = overall_start.elapsed().as_secs_f64();
-    context
-        .store_to_memory(TimingScope::TIMING, TimingKeys::TOTAL_EXECUTION, total_time)
-        .await?;
+    #[tokio::test]
+    async fn test_skipped_stages_restore_the_snapshots_of_the_last_run() {
+        let root = std::env::temp_dir().join(format!("litho-snapshots-{}", uuid::Uuid::new_v4()));
+        let mut config = Config {
+            project_path: root.clone(),
+            internal_path: root.join(".litho"),
+            ..Config::default()
+        };
+        config.cache.cache_dir = root.join("cache");
 
-    println!("\n🎉 All processes execution completed! Total duration: {:.2}s", total_time);
+        // A stage without a snapshot cannot be skipped
+        let skipping = Config {
+            skip_preprocessing: true,
+            skip_research: true,
+            skip_documentation: true,
+            ..config.clone()
+        };
+        let error = execute_stages(
+            &GeneratorContext::for_test(skipping.clone()),
+            &mut DocTree::new(&skipping.target_language),
+        )
+        .await
+        .unwrap_err();
+        assert!(error.to_string().contains("--skip-preprocessing"), "{}", error);
 
-    Ok(())
+        let previous = GeneratorContext::for_test(config.clone());
+        let stages = [
+            (PreprocessMemoryScope::PREPROCESS, PreprocessKeys::PROJECT_STRUCTURE),
+            (ResearchMemoryScope::STUDIES_RESEARCH, "SystemContextResearcher"),
+            (ComposeMemoryScope::DOCUMENTATION, "Overview"),
+        ];
+        for (scope, key) in stages {
+            previous.store_to_memory(scope, key, format!("{} of the last run", key)).await.unwrap();
+            previous.save_memory_snapshot(scope).await.unwrap();
+        }
+
+        let context = GeneratorContext::for_test(skipping.clone());
+        execute_stages(&context, &mut DocTree::new(&skipping.target_language))
+            .await
+            .unwrap();
+        for (scope, key) in stages {
+            assert_eq!(
+                context.get_from_memory::<String>(scope, key).await,
+                Some(format!("{} of the last run", key))
+            );
+        }
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
 }
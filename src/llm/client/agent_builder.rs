@@ -1,21 +1,39 @@
 //! Agent builder - Responsible for building and configuring LLM Agent
 
+use std::sync::Arc;
+
+use tokio::sync::RwLock;
+
 use crate::{
     config::Config,
     llm::client::providers::{ProviderAgent, ProviderClient},
-    llm::tools::{file_explorer::AgentToolFileExplorer, file_reader::AgentToolFileReader},
+    llm::tools::{
+        PresetTools, code_search::AgentToolCodeSearch, file_explorer::AgentToolFileExplorer,
+        file_reader::AgentToolFileReader, git_history::AgentToolGitHistory,
+        insight_lookup::AgentToolInsightLookup,
+    },
+    memory::Memory,
 };
 
 /// Agent builder
 pub struct AgentBuilder<'a> {
     client: &'a ProviderClient,
     config: &'a Config,
+    memory: Option<&'a Arc<RwLock<Memory>>>,
 }
 
 impl<'a> AgentBuilder<'a> {
     /// Create a new Agent builder
-    pub fn new(client: &'a ProviderClient, config: &'a Config) -> Self {
-        Self { client, config }
+    pub fn new(
+        client: &'a ProviderClient,
+        config: &'a Config,
+        memory: Option<&'a Arc<RwLock<Memory>>>,
+    ) -> Self {
+        Self {
+            client,
+            config,
+            memory,
+        }
     }
 
     /// Build Agent with built-in preset tools
@@ -23,11 +41,16 @@ impl<'a> AgentBuilder<'a> {
         let llm_config = &self.config.llm;
 
         if !llm_config.disable_preset_tools {
-            let file_explorer = AgentToolFileExplorer::new(self.config.clone());
-            let file_reader = AgentToolFileReader::new(self.config.clone());
+            let tools = PresetTools {
+                file_explorer: AgentToolFileExplorer::new(self.config.clone()),
+                file_reader: AgentToolFileReader::new(self.config.clone()),
+                code_search: AgentToolCodeSearch::new(self.config.clone()),
+                insight_lookup: AgentToolInsightLookup::new(self.memory.cloned()),
+                git_history: AgentToolGitHistory::new(self.config.clone()),
+            };
 
             let system_prompt_with_tools = format!(
-                "{}\nDo not fabricate non-existent code. If you need to learn more about the project structure and source code content, actively call tools to obtain more contextual information",
+                "{}\nDo not fabricate non-existent code. If you need to learn more about the project structure and source code content, actively call tools to obtain more contextual information. Only the most important code insights are listed in the prompt, query the insight_lookup tool for the analysis of other files",
                 system_prompt
             );
 
@@ -35,8 +58,7 @@ impl<'a> AgentBuilder<'a> {
                 &llm_config.model_efficient,
                 &system_prompt_with_tools,
                 llm_config,
-                &file_explorer,
-                &file_reader,
+                &tools,
             )
         } else {
             self.client
@@ -0,0 +1,187 @@
+//! Token and cost budget of a run
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::BudgetConfig;
+use crate::llm::client::pricing::PricingTable;
+use crate::llm::client::types::TokenUsage;
+
+/// Error returned by LLM calls once the budget is used up
+#[derive(Debug, Clone)]
+pub struct BudgetExhausted {
+    pub reason: String,
+}
+
+impl fmt::Display for BudgetExhausted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "LLM budget exhausted: {}", self.reason)
+    }
+}
+
+impl std::error::Error for BudgetExhausted {}
+
+impl BudgetExhausted {
+    /// Whether an error was caused by an exhausted budget
+    pub fn is(err: &anyhow::Error) -> bool {
+        err.chain().any(|e| e.downcast_ref::<BudgetExhausted>().is_some())
+    }
+}
+
+/// How much of the budget a run has spent
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BudgetUsage {
+    pub calls: usize,
+    pub input_tokens: usize,
+    pub output_tokens: usize,
+    pub cost: f64,
+    /// Whether the run switched to degraded mode
+    pub degraded: bool,
+    /// Whether the run stopped because the budget was used up
+    pub exhausted: bool,
+}
+
+/// Tracks LLM usage of a run against the configured budget
+pub struct BudgetTracker {
+    config: BudgetConfig,
+    pricing: Arc<PricingTable>,
+    usage: Mutex<BudgetUsage>,
+}
+
+impl BudgetTracker {
+    pub fn new(config: BudgetConfig, pricing: Arc<PricingTable>) -> Self {
+        Self {
+            config,
+            pricing,
+            usage: Mutex::new(BudgetUsage::default()),
+        }
+    }
+
+    /// Whether any limit is configured
+    pub fn is_limited(&self) -> bool {
+        self.config.max_input_tokens.is_some()
+            || self.config.max_output_tokens.is_some()
+            || self.config.max_cost.is_some()
+    }
+
+    pub fn config(&self) -> &BudgetConfig {
+        &self.config
+    }
+
+    pub fn usage(&self) -> BudgetUsage {
+        self.usage.lock().unwrap().clone()
+    }
+
+    /// Record the usage of a finished call
+    pub fn record(&self, usage: &TokenUsage, provider: &str, model: &str) {
+        let mut current = self.usage.lock().unwrap();
+        current.calls += 1;
+        current.input_tokens += usage.input_tokens;
+        current.output_tokens += usage.output_tokens;
+        current.cost += self.pricing.cost(usage, provider, model);
+    }
+
+    /// Fail with `BudgetExhausted` if any limit is reached
+    pub fn ensure_available(&self) -> anyhow::Result<()> {
+        let mut usage = self.usage.lock().unwrap();
+        if let Some(reason) = self.exceeded_limit(&usage) {
+            if !usage.exhausted {
+                usage.exhausted = true;
+                eprintln!("🛑 LLM budget exhausted: {}", reason);
+            }
+            return Err(BudgetExhausted { reason }.into());
+        }
+        Ok(())
+    }
+
+    /// Whether the run is past the degrade threshold and should save tokens
+    pub fn is_degraded(&self) -> bool {
+        let mut usage = self.usage.lock().unwrap();
+        if usage.degraded {
+            return true;
+        }
+        let share = self.used_share(&usage);
+        if share >= self.config.degrade_threshold {
+            usage.degraded = true;
+            println!(
+                "⚠️  {:.0}% of the LLM budget used, degrading: fewer code insights, optional agents skipped, efficient model only",
+                share * 100.0
+            );
+        }
+        usage.degraded
+    }
+
+    /// Largest used share across the configured limits
+    fn used_share(&self, usage: &BudgetUsage) -> f64 {
+        [
+            self.config
+                .max_input_tokens
+                .map(|max| usage.input_tokens as f64 / max.max(1) as f64),
+            self.config
+                .max_output_tokens
+                .map(|max| usage.output_tokens as f64 / max.max(1) as f64),
+            self.config
+                .max_cost
+                .map(|max| if max > 0.0 { usage.cost / max } else { f64::INFINITY }),
+        ]
+        .into_iter()
+        .flatten()
+        .fold(0.0, f64::max)
+    }
+
+    fn exceeded_limit(&self, usage: &BudgetUsage) -> Option<String> {
+        if let Some(max) = self.config.max_input_tokens.filter(|max| usage.input_tokens >= *max) {
+            return Some(format!("{} of {} input tokens used", usage.input_tokens, max));
+        }
+        if let Some(max) = self.config.max_output_tokens.filter(|max| usage.output_tokens >= *max) {
+            return Some(format!("{} of {} output tokens used", usage.output_tokens, max));
+        }
+        if let Some(max) = self.config.max_cost.filter(|max| usage.cost >= *max) {
+            return Some(format!("${:.4} of ${:.4} spent", usage.cost, max));
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::LLMConfig;
+
+    fn pricing() -> Arc<PricingTable> {
+        Arc::new(PricingTable::new(&LLMConfig::default()))
+    }
+
+    #[test]
+    fn test_budget_degrades_then_exhausts() {
+        let tracker = BudgetTracker::new(
+            BudgetConfig {
+                max_input_tokens: Some(1000),
+                ..BudgetConfig::default()
+            },
+            pricing(),
+        );
+        assert!(tracker.ensure_available().is_ok());
+        assert!(!tracker.is_degraded());
+
+        tracker.record(&TokenUsage::new(850, 10), "openai", "model");
+        assert!(tracker.is_degraded());
+        assert!(tracker.ensure_available().is_ok());
+
+        tracker.record(&TokenUsage::new(200, 10), "openai", "model");
+        let err = tracker.ensure_available().unwrap_err();
+        assert!(BudgetExhausted::is(&err));
+        assert!(tracker.usage().exhausted);
+    }
+
+    #[test]
+    fn test_unlimited_budget_never_degrades() {
+        let tracker = BudgetTracker::new(BudgetConfig::default(), pricing());
+        tracker.record(&TokenUsage::new(1_000_000, 1_000_000), "openai", "model");
+        assert!(!tracker.is_limited());
+        assert!(!tracker.is_degraded());
+        assert!(tracker.ensure_available().is_ok());
+    }
+}
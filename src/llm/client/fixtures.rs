@@ -0,0 +1,595 @@
+//! Record/replay of LLM traffic - Fixtures for deterministic offline pipeline runs
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, LazyLock};
+
+use anyhow::{Context, Result, anyhow};
+use regex::Regex;
+use rig::OneOrMany;
+use rig::agent::Agent;
+use rig::completion::{CompletionError, CompletionModel, CompletionRequest, CompletionResponse, Message, PromptError, Usage};
+use rig::message::{AssistantContent, UserContent};
+use rig::streaming::StreamingCompletionResponse;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::config::{FixtureConfig, FixtureMode};
+use crate::utils::project_fingerprint::hash_bytes;
+
+use super::providers::{ProviderAgent, ProviderExtractor, prompt_with_usage};
+use super::rate_limit::TurnRateLimit;
+use super::types::TokenUsage;
+use super::usage::UsageMeter;
+
+static TIMESTAMP: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}:\d{2}(\.\d+)?(Z|[+-]\d{2}:\d{2}| ?UTC)?").unwrap()
+});
+static LAST_MODIFIED: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#""last_modified":\s*"[^"]*""#).unwrap());
+
+/// Kind of LLM call a fixture was recorded from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FixtureKind {
+    /// Structured extraction
+    Extract,
+    /// Single-turn agent prompt
+    Prompt,
+    /// Multi-turn agent prompt with tool calls
+    MultiTurn,
+}
+
+/// Recorded response of an LLM call
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FixtureReply {
+    Text {
+        text: String,
+    },
+    Value {
+        value: Value,
+    },
+    /// Multi-turn conversation stopped by the iteration limit
+    MaxDepth { max_depth: usize },
+}
+
+/// One model turn of a recorded multi-turn conversation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FixtureTurn {
+    /// Tool calls or answer of the model
+    pub reply: Vec<AssistantContent>,
+    /// Results of the called tools when recording, replay runs the tools again
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_results: Vec<UserContent>,
+}
+
+/// A recorded LLM call
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fixture {
+    pub kind: FixtureKind,
+    pub model: String,
+    pub system_prompt: String,
+    pub prompt: String,
+    pub reply: FixtureReply,
+    /// Token usage reported when recording
+    #[serde(default)]
+    pub usage: Option<TokenUsage>,
+    /// Model turns of a multi-turn conversation, replayed one completion at a time
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub turns: Vec<FixtureTurn>,
+}
+
+/// Directory of fixtures, one JSON file per model and normalized prompt hash
+#[derive(Debug)]
+pub struct FixtureStore {
+    dir: PathBuf,
+}
+
+impl FixtureStore {
+    pub fn new(config: &FixtureConfig) -> Result<Self> {
+        match config.mode {
+            FixtureMode::Record => fs::create_dir_all(&config.dir)?,
+            FixtureMode::Replay if !config.dir.is_dir() => {
+                return Err(anyhow!(
+                    "Fixture directory {} does not exist, record it first",
+                    config.dir.display()
+                ));
+            }
+            FixtureMode::Replay => {}
+        }
+        Ok(Self {
+            dir: config.dir.clone(),
+        })
+    }
+
+    /// Save a recorded call, a later call with the same model and prompts replaces it
+    pub fn save(&self, fixture: &Fixture) -> Result<()> {
+        let key = Self::key(fixture.kind, &fixture.model, &fixture.system_prompt, &fixture.prompt);
+        let path = self.dir.join(format!("{}.json", key));
+        fs::write(&path, serde_json::to_string_pretty(fixture)?)
+            .with_context(|| format!("Failed to write fixture {}", path.display()))
+    }
+
+    /// Load the fixture recorded for a call
+    pub fn load(&self, kind: FixtureKind, model: &str, system_prompt: &str, prompt: &str) -> Result<Fixture> {
+        let key = Self::key(kind, model, system_prompt, prompt);
+        let path = self.dir.join(format!("{}.json", key));
+        let content = fs::read_to_string(&path).map_err(|_| {
+            anyhow!(
+                "No fixture recorded for this {:?} call to {} ({}), the model or prompt changed since recording; record the fixtures again",
+                kind,
+                model,
+                path.display()
+            )
+        })?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse fixture {}", path.display()))
+    }
+
+    /// Load a recorded text reply
+    pub fn load_text(
+        &self,
+        kind: FixtureKind,
+        model: &str,
+        system_prompt: &str,
+        prompt: &str,
+    ) -> Result<(String, Option<TokenUsage>)> {
+        let fixture = self.load(kind, model, system_prompt, prompt)?;
+        match fixture.reply {
+            FixtureReply::Text { text } => Ok((text, fixture.usage)),
+            reply => Err(anyhow!("Fixture holds {:?} instead of a text reply", reply)),
+        }
+    }
+
+    /// Fixture key, stable across runs for the same model and prompts
+    fn key(kind: FixtureKind, model: &str, system_prompt: &str, prompt: &str) -> String {
+        let normalized = format!(
+            "{:?}\n{}\n{}\n{}",
+            kind,
+            model,
+            normalize_prompt(system_prompt),
+            normalize_prompt(prompt)
+        );
+        hash_bytes(normalized.as_bytes())
+    }
+}
+
+/// Where a fixture agent takes its replies from
+pub enum FixtureSource {
+    /// A provider agent, whose calls are recorded
+    Record(Box<ProviderAgent>),
+    /// The fixtures, through an agent with the same tools so that replayed tool calls run
+    Replay(Agent<FixtureModel>),
+}
+
+/// Agent that records the calls of a provider agent, or replays them
+pub struct FixtureAgent {
+    pub source: FixtureSource,
+    pub store: Arc<FixtureStore>,
+    pub model: String,
+    pub system_prompt: String,
+}
+
+impl FixtureAgent {
+    pub async fn prompt(&self, prompt: &str) -> Result<(String, Option<TokenUsage>)> {
+        let FixtureSource::Record(inner) = &self.source else {
+            return self
+                .store
+                .load_text(FixtureKind::Prompt, &self.model, &self.system_prompt, prompt);
+        };
+        let (text, usage) = Box::pin(inner.prompt(prompt)).await?;
+        self.store.save(&Fixture {
+            kind: FixtureKind::Prompt,
+            model: self.model.clone(),
+            system_prompt: self.system_prompt.clone(),
+            prompt: prompt.to_string(),
+            reply: FixtureReply::Text { text: text.clone() },
+            usage: usage.clone(),
+            turns: Vec::new(),
+        })?;
+        Ok((text, usage))
+    }
+
+    pub async fn multi_turn(
+        &self,
+        prompt: &str,
+        max_iterations: usize,
+        history: &mut Vec<Message>,
+        turns: Option<&TurnRateLimit>,
+    ) -> Result<(String, Option<TokenUsage>), PromptError> {
+        let inner = match &self.source {
+            FixtureSource::Record(inner) => inner,
+            FixtureSource::Replay(agent) => {
+                return Box::pin(prompt_with_usage(agent, prompt, max_iterations, history, None)).await;
+            }
+        };
+
+        let start = history.len();
+        let result = Box::pin(inner.multi_turn(prompt, max_iterations, history, turns)).await;
+        let (reply, usage) = match &result {
+            Ok((text, usage)) => (FixtureReply::Text { text: text.clone() }, usage.clone()),
+            Err(PromptError::MaxDepthError { max_depth, .. }) => (
+                FixtureReply::MaxDepth {
+                    max_depth: *max_depth,
+                },
+                None,
+            ),
+            // Failed calls are retried, only their eventual outcome is recorded
+            Err(_) => return result,
+        };
+        self.store
+            .save(&Fixture {
+                kind: FixtureKind::MultiTurn,
+                model: self.model.clone(),
+                system_prompt: self.system_prompt.clone(),
+                prompt: prompt.to_string(),
+                reply,
+                usage,
+                turns: recorded_turns(&history[start..]),
+            })
+            .map_err(replay_error)?;
+        result
+    }
+}
+
+/// Completion model that answers every turn of a multi-turn conversation from its fixture
+#[derive(Clone)]
+pub struct FixtureModel {
+    store: Arc<FixtureStore>,
+    model: String,
+    system_prompt: String,
+}
+
+impl FixtureModel {
+    pub fn new(store: Arc<FixtureStore>, model: &str, system_prompt: &str) -> Self {
+        Self {
+            store,
+            model: model.to_string(),
+            system_prompt: system_prompt.to_string(),
+        }
+    }
+}
+
+impl CompletionModel for FixtureModel {
+    type Response = ();
+    type StreamingResponse = ();
+
+    async fn completion(&self, request: CompletionRequest) -> Result<CompletionResponse<()>, CompletionError> {
+        let messages: Vec<Message> = request.chat_history.into_iter().collect();
+        let (prompt, turn) = conversation_position(&messages)
+            .ok_or_else(|| CompletionError::ResponseError("Fixture replay found no prompt in the conversation".to_string()))?;
+        let fixture = self
+            .store
+            .load(FixtureKind::MultiTurn, &self.model, &self.system_prompt, &prompt)
+            .map_err(|e| CompletionError::ResponseError(e.to_string()))?;
+        let reply = fixture
+            .turns
+            .get(turn)
+            .and_then(|recorded| OneOrMany::many(recorded.reply.clone()).ok())
+            .ok_or_else(|| {
+                CompletionError::ResponseError(format!(
+                    "Fixture recorded {} turns and no reply for turn {}, the tool results changed since recording; record the fixtures again",
+                    fixture.turns.len(),
+                    turn + 1
+                ))
+            })?;
+
+        // The recorded usage covers the whole conversation, it is reported with the last turn
+        let usage = match &fixture.usage {
+            Some(usage) if turn + 1 == fixture.turns.len() => Usage {
+                input_tokens: usage.input_tokens as u64,
+                output_tokens: usage.output_tokens as u64,
+                total_tokens: usage.total_tokens as u64,
+            },
+            _ => Usage::new(),
+        };
+        Ok(CompletionResponse {
+            choice: reply,
+            usage,
+            raw_response: (),
+        })
+    }
+
+    async fn stream(&self, _request: CompletionRequest) -> Result<StreamingCompletionResponse<()>, CompletionError> {
+        Err(CompletionError::ProviderError("Fixtures do not replay streamed completions".to_string()))
+    }
+}
+
+/// Prompt of the conversation a request continues and the number of model turns since that prompt
+fn conversation_position(messages: &[Message]) -> Option<(String, usize)> {
+    let start = messages.iter().rposition(|message| match message {
+        Message::User { content } => content.iter().any(|c| matches!(c, UserContent::Text(_))),
+        Message::Assistant { .. } => false,
+    })?;
+    let Message::User { content } = &messages[start] else {
+        return None;
+    };
+    let prompt = content
+        .iter()
+        .filter_map(|c| match c {
+            UserContent::Text(text) => Some(text.text.as_str()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let turn = messages[start..]
+        .iter()
+        .filter(|message| matches!(message, Message::Assistant { .. }))
+        .count();
+    Some((prompt, turn))
+}
+
+/// Model turns of a conversation with the tool results that followed them
+fn recorded_turns(messages: &[Message]) -> Vec<FixtureTurn> {
+    let mut turns: Vec<FixtureTurn> = Vec::new();
+    for message in messages {
+        match message {
+            Message::Assistant { content, .. } => turns.push(FixtureTurn {
+                reply: content.iter().cloned().collect(),
+                tool_results: Vec::new(),
+            }),
+            Message::User { content } => {
+                if let Some(turn) = turns.last_mut() {
+                    turn.tool_results.extend(content.iter().cloned());
+                }
+            }
+        }
+    }
+    turns
+}
+
+/// Extractor that records the calls of a provider extractor, or replays them when it has none
+pub struct FixtureExtractor<T>
+where
+    T: JsonSchema + for<'a> Deserialize<'a> + Serialize + Send + Sync + 'static,
+{
+    pub inner: Option<Box<ProviderExtractor<T>>>,
+    pub store: Arc<FixtureStore>,
+    pub model: String,
+    pub system_prompt: String,
+}
+
+impl<T> FixtureExtractor<T>
+where
+    T: JsonSchema + for<'a> Deserialize<'a> + Serialize + Send + Sync + 'static,
+{
+    pub async fn extract(
+        &self,
+        prompt: &str,
+        repair_attempts: u32,
+        calls: &TurnRateLimit,
+        meter: &UsageMeter<'_>,
+    ) -> Result<(T, Option<TokenUsage>)> {
+        let Some(inner) = &self.inner else {
+            let fixture = self
+                .store
+                .load(FixtureKind::Extract, &self.model, &self.system_prompt, prompt)?;
+            return match fixture.reply {
+                FixtureReply::Value { value } => {
+                    // The replayed call is billed with its recorded usage, as it was when recording
+                    meter.record(prompt, &value.to_string(), fixture.usage.as_ref());
+                    Ok((serde_json::from_value(value)?, fixture.usage))
+                }
+                reply => Err(anyhow!("Fixture holds {:?} instead of an extracted value", reply)),
+            };
+        };
+
+        let (result, usage) = Box::pin(inner.extract(prompt, repair_attempts, calls, meter)).await?;
+        self.store.save(&Fixture {
+            kind: FixtureKind::Extract,
+            model: self.model.clone(),
+            system_prompt: self.system_prompt.clone(),
+            prompt: prompt.to_string(),
+            reply: FixtureReply::Value {
+                value: serde_json::to_value(&result)?,
+            },
+            usage: usage.clone(),
+            turns: Vec::new(),
+        })?;
+        Ok((result, usage))
+    }
+}
+
+fn replay_error(e: anyhow::Error) -> PromptError {
+    PromptError::CompletionError(CompletionError::ResponseError(e.to_string()))
+}
+
+/// Drop the parts of a prompt that change between runs of the same project: timestamps, file modification times and line endings
+fn normalize_prompt(prompt: &str) -> String {
+    let prompt = LAST_MODIFIED.replace_all(prompt, r#""last_modified":"""#);
+    let prompt = TIMESTAMP.replace_all(&prompt, "<timestamp>");
+    prompt
+        .lines()
+        .map(str::trim_end)
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalized_prompts_share_a_fixture() {
+        let dir = std::env::temp_dir().join(format!("litho-fixtures-{}", uuid::Uuid::new_v4()));
+        let store = FixtureStore::new(&FixtureConfig {
+            mode: FixtureMode::Record,
+            dir: dir.clone(),
+        })
+        .unwrap();
+
+        store
+            .save(&Fixture {
+                kind: FixtureKind::Prompt,
+                model: "model".to_string(),
+                system_prompt: "system".to_string(),
+                prompt: "Generated at 2025-01-01 10:00:00 UTC\r\n{\"last_modified\": \"1700000000\"}".to_string(),
+                reply: FixtureReply::Text {
+                    text: "reply".to_string(),
+                },
+                usage: None,
+                turns: Vec::new(),
+            })
+            .unwrap();
+
+        let prompt = "Generated at 2025-06-30 23:59:59 UTC  \n{\"last_modified\": \"1800000000\"}";
+        assert_eq!(store.load_text(FixtureKind::Prompt, "model", "system", prompt).unwrap().0, "reply");
+        assert!(store.load(FixtureKind::Extract, "model", "system", prompt).is_err());
+        assert!(store.load(FixtureKind::Prompt, "other-model", "system", prompt).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_replay_runs_the_recorded_conversation() {
+        use crate::config::Config;
+        use super::super::budget::BudgetTracker;
+        use super::super::pricing::PricingTable;
+        use super::super::providers::ProviderClient;
+        use super::super::rate_limit::RateLimiter;
+        use super::super::usage::UsageLedger;
+        use crate::llm::tools::{
+            PresetTools, code_search::AgentToolCodeSearch, file_explorer::AgentToolFileExplorer,
+            file_reader::AgentToolFileReader, git_history::AgentToolGitHistory,
+            insight_lookup::AgentToolInsightLookup,
+        };
+        use rig::message::{Text, ToolCall, ToolFunction};
+
+        let root = std::env::temp_dir().join(format!("litho-replay-{}", uuid::Uuid::new_v4()));
+        let dir = root.join("fixtures");
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/lib.rs"), "pub fn answer() -> u32 {\n    42\n}\n").unwrap();
+        let fixtures = FixtureConfig {
+            mode: FixtureMode::Record,
+            dir: dir.clone(),
+        };
+        let store = FixtureStore::new(&fixtures).unwrap();
+
+        let reply = |content: AssistantContent| FixtureTurn {
+            reply: vec![content],
+            tool_results: Vec::new(),
+        };
+        let turns = vec![
+            reply(AssistantContent::ToolCall(ToolCall {
+                id: "call-1".to_string(),
+                call_id: None,
+                function: ToolFunction {
+                    name: "file_reader".to_string(),
+                    arguments: serde_json::json!({ "file_path": "src/lib.rs" }),
+                },
+            })),
+            reply(AssistantContent::Text(Text {
+                text: "answer() returns 42".to_string(),
+            })),
+        ];
+        let fixture = |kind, prompt: &str, reply, turns| Fixture {
+            kind,
+            model: "model".to_string(),
+            system_prompt: "system".to_string(),
+            prompt: prompt.to_string(),
+            reply,
+            usage: Some(TokenUsage::new(100, 20)),
+            turns,
+        };
+        for recorded in [
+            fixture(
+                FixtureKind::MultiTurn,
+                "What does answer return?",
+                FixtureReply::Text {
+                    text: "answer() returns 42".to_string(),
+                },
+                turns.clone(),
+            ),
+            fixture(
+                FixtureKind::Prompt,
+                "Name the function",
+                FixtureReply::Text {
+                    text: "answer".to_string(),
+                },
+                Vec::new(),
+            ),
+            fixture(
+                FixtureKind::Extract,
+                "List the functions",
+                FixtureReply::Value {
+                    value: serde_json::json!(["answer"]),
+                },
+                Vec::new(),
+            ),
+        ] {
+            store.save(&recorded).unwrap();
+        }
+
+        let mut config = Config {
+            project_path: root.clone(),
+            ..Default::default()
+        };
+        config.llm.fixtures = Some(FixtureConfig {
+            mode: FixtureMode::Replay,
+            ..fixtures
+        });
+        let client = ProviderClient::new(&config.llm).unwrap();
+        let tools = PresetTools {
+            file_explorer: AgentToolFileExplorer::new(config.clone()),
+            file_reader: AgentToolFileReader::new(config.clone()),
+            code_search: AgentToolCodeSearch::new(config.clone()),
+            insight_lookup: AgentToolInsightLookup::new(None),
+            git_history: AgentToolGitHistory::new(config.clone()),
+        };
+
+        // The recorded tool call runs the real tool, its result is part of the replayed history
+        let agent = client.create_agent_with_tools("model", "system", &config.llm, &tools);
+        let mut history = Vec::new();
+        let (text, usage) = agent
+            .multi_turn("What does answer return?", 5, &mut history, None)
+            .await
+            .unwrap();
+        assert_eq!(text, "answer() returns 42");
+        assert_eq!(usage.map(|usage| usage.total_tokens), Some(120));
+        let replayed = recorded_turns(&history);
+        assert_eq!(
+            replayed.iter().map(|turn| &turn.reply).collect::<Vec<_>>(),
+            turns.iter().map(|turn| &turn.reply).collect::<Vec<_>>()
+        );
+        assert!(serde_json::to_string(&replayed[0].tool_results).unwrap().contains("42"));
+
+        let agent = client.create_agent("model", "system", &config.llm);
+        assert_eq!(agent.prompt("Name the function").await.unwrap().0, "answer");
+        let extractor = client.create_extractor::<Vec<String>>("model", "system", &config.llm);
+        let calls = TurnRateLimit::new(Arc::new(RateLimiter::new(&config.llm)), "openai", "system");
+        let pricing = Arc::new(PricingTable::new(&config.llm));
+        let budget = BudgetTracker::new(Default::default(), pricing.clone());
+        let ledger = UsageLedger::new(pricing);
+        let meter = UsageMeter {
+            budget: &budget,
+            ledger: &ledger,
+            agent: "studies_research",
+            provider: "openai",
+            model: "model",
+            system_prompt: "system",
+        };
+        assert_eq!(
+            extractor.extract("List the functions", 0, &calls, &meter).await.unwrap().0,
+            vec!["answer"]
+        );
+        // The replayed call is charged with its recorded usage
+        assert_eq!(budget.usage().input_tokens, 100);
+        assert_eq!(ledger.total().output_tokens, 20);
+        assert_eq!(ledger.total().estimated_calls, 0);
+
+        // Fixtures recorded with another model are not replayed
+        let agent = client.create_agent_with_tools("other-model", "system", &config.llm, &tools);
+        assert!(
+            agent
+                .multi_turn("What does answer return?", 5, &mut Vec::new(), None)
+                .await
+                .is_err()
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -3,59 +3,272 @@
 use anyhow::Result;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::RwLock;
 
-use crate::{config::Config, llm::client::utils::evaluate_befitting_model};
+use crate::{
+    cache::CacheIdentity,
+    config::{Config, LLMConfig},
+    memory::Memory,
+    llm::client::utils::{
+        DEFAULT_POWERFUL_THRESHOLD_BYTES, estimate_token_usage, evaluate_befitting_model,
+    },
+};
 
 mod agent_builder;
+pub mod budget;
+pub mod fixtures;
 mod ollama_extractor;
+pub mod pricing;
 mod providers;
+pub mod rate_limit;
 mod react;
 mod react_executor;
+mod structured;
 mod summary_reasoner;
+pub mod trace;
 pub mod types;
+pub mod usage;
 pub mod utils;
 
 pub use react::{ReActConfig, ReActResponse};
 
 use agent_builder::AgentBuilder;
+use budget::{BudgetExhausted, BudgetTracker};
+use pricing::PricingTable;
+use rate_limit::{RateLimiter, TurnRateLimit};
+use types::LLMResponse;
+use usage::{UsageLedger, UsageMeter};
 use providers::ProviderClient;
 use react_executor::ReActExecutor;
 use summary_reasoner::SummaryReasoner;
+use trace::{ConversationTrace, TraceOutcome, TraceWriter};
 
 /// LLM client - Provides unified LLM service interface
 #[derive(Clone)]
 pub struct LLMClient {
     config: Config,
     client: ProviderClient,
+    budget: Arc<BudgetTracker>,
+    usage: Arc<UsageLedger>,
+    pricing: Arc<PricingTable>,
+    rate_limiter: Arc<RateLimiter>,
+    traces: Arc<TraceWriter>,
+    /// Memory of the documentation run, queried by the insight lookup tool
+    memory: Option<Arc<RwLock<Memory>>>,
+    /// Provider clients of the routes that use their own provider
+    route_clients: Arc<BTreeMap<String, ProviderClient>>,
+    /// Provider clients of the fallback backends, in the order of `llm.fallback`
+    fallback_clients: Arc<Vec<ProviderClient>>,
+    /// Agent the client is bound to, see `for_agent`
+    agent: String,
+    /// Cache scope of the agent, its calls are recorded under it in the usage ledger
+    cache_scope: String,
+    /// Route matching the agent
+    route: Option<String>,
 }
 
 impl LLMClient {
     /// Create a new LLM client
     pub fn new(config: Config) -> Result<Self> {
         let client = ProviderClient::new(&config.llm)?;
-        Ok(Self { client, config })
+        let mut route_clients = BTreeMap::new();
+        for (key, route) in &config.llm.routing.routes {
+            if route.has_own_provider() {
+                let route_client = ProviderClient::new(&route.apply(&config.llm))
+                    .map_err(|e| anyhow::anyhow!("Invalid provider of route {}: {}", key, e))?;
+                route_clients.insert(key.clone(), route_client);
+            }
+        }
+        let fallback_clients = config
+            .llm
+            .fallback
+            .iter()
+            .map(|backend| {
+                ProviderClient::new(&backend.apply(&config.llm)).map_err(|e| {
+                    anyhow::anyhow!(
+                        "Invalid fallback backend {}/{}: {}",
+                        backend.provider,
+                        backend.model,
+                        e
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let pricing = Arc::new(PricingTable::new(&config.llm));
+        let budget = Arc::new(BudgetTracker::new(config.llm.budget.clone(), pricing.clone()));
+        let rate_limiter = Arc::new(RateLimiter::new(&config.llm));
+        let traces = Arc::new(TraceWriter::new(&config));
+        Ok(Self {
+            client,
+            config,
+            budget,
+            usage: Arc::new(UsageLedger::new(pricing.clone())),
+            pricing,
+            rate_limiter,
+            traces,
+            memory: None,
+            route_clients: Arc::new(route_clients),
+            fallback_clients: Arc::new(fallback_clients),
+            agent: String::new(),
+            cache_scope: String::new(),
+            route: None,
+        })
+    }
+
+    /// Let the agent tools query the memory of a documentation run
+    pub fn with_memory(mut self, memory: Arc<RwLock<Memory>>) -> Self {
+        self.memory = Some(memory);
+        self
+    }
+
+    /// Client for the calls of an agent, using the model settings of the route matching its name or cache scope
+    pub fn for_agent(&self, agent: &str, cache_scope: &str) -> Self {
+        let mut client = self.clone();
+        client.agent = agent.to_string();
+        client.cache_scope = cache_scope.to_string();
+        if let Some((key, route)) = self.config.llm.routing.find(agent, cache_scope) {
+            client.config.llm = route.apply(&self.config.llm);
+            if let Some(route_client) = self.route_clients.get(key) {
+                client.client = route_client.clone();
+            }
+            client.route = Some(key.clone());
+        }
+        client
+    }
+
+    /// Clients of the fallback backends, keeping the agent and route settings of this client
+    fn fallback_backends(&self) -> Vec<Self> {
+        self.config
+            .llm
+            .fallback
+            .iter()
+            .zip(self.fallback_clients.iter())
+            .map(|(backend, client)| {
+                let mut fallback = self.clone();
+                fallback.config.llm = backend.apply(&self.config.llm);
+                fallback.client = client.clone();
+                fallback
+            })
+            .collect()
+    }
+
+    /// Provider, and the model if the backend has a single one
+    fn backend_label(&self) -> String {
+        let llm = &self.config.llm;
+        if llm.model_efficient == llm.model_powerful {
+            format!("{}/{}", llm.provider, llm.model_efficient)
+        } else {
+            llm.provider.to_string()
+        }
+    }
+
+    /// Run a call on this client's backend, then on each fallback backend until one succeeds
+    async fn with_fallback<R, F, Fut>(&self, call: F) -> Result<R>
+    where
+        F: Fn(Self) -> Fut,
+        Fut: Future<Output = Result<R>>,
+    {
+        let mut last_error = match call(self.clone()).await {
+            Ok(result) => return Ok(result),
+            Err(e) => e,
+        };
+        let mut failed = self.backend_label();
+        for backend in self.fallback_backends() {
+            if BudgetExhausted::is(&last_error) {
+                break;
+            }
+            let next = backend.backend_label();
+            eprintln!("⚠️  {} failed: {}, falling back to {}", failed, last_error, next);
+            match call(backend).await {
+                Ok(result) => return Ok(result),
+                Err(e) => last_error = e,
+            }
+            failed = next;
+        }
+        Err(last_error)
+    }
+
+    /// LLM settings of this client, including its route
+    pub fn llm_config(&self) -> &LLMConfig {
+        &self.config.llm
+    }
+
+    /// Provider, model and temperature a call on this client is answered with, for its cache key.
+    /// Extractions pick the model by prompt size, prompts use the efficient model
+    pub fn cache_identity(&self, system_prompt: &str, user_prompt: &str, extraction: bool) -> CacheIdentity {
+        let llm_config = &self.config.llm;
+        let model = if extraction {
+            evaluate_befitting_model(llm_config, system_prompt, user_prompt).0
+        } else {
+            llm_config.model_efficient.clone()
+        };
+        CacheIdentity {
+            provider: llm_config.provider.to_string(),
+            model,
+            temperature: llm_config.temperature,
+            template_version: None,
+            schema_hash: None,
+        }
+    }
+
+    /// Log which provider and model serve a call, and why
+    fn log_routing(&self, model: &str, reason: &str) {
+        let agent = if self.agent.is_empty() { "LLM call" } else { &self.agent };
+        let reason = match &self.route {
+            Some(route) => format!("route {}, {}", route, reason),
+            None => reason.to_string(),
+        };
+        println!("   🧭 {} -> {}/{} ({})", agent, self.config.llm.provider, model, reason);
+    }
+
+    /// Token and cost budget of the run, shared by all clones of this client
+    pub fn budget(&self) -> &BudgetTracker {
+        &self.budget
+    }
+
+    /// Token usage of the run per agent and model, shared by all clones of this client
+    pub fn usage(&self) -> &UsageLedger {
+        &self.usage
+    }
+
+    /// Model prices used for cost reporting, shared by all clones of this client
+    pub fn pricing(&self) -> Arc<PricingTable> {
+        self.pricing.clone()
+    }
+
+    /// Rate limiter of the run, shared by all clones of this client
+    pub fn rate_limiter(&self) -> &RateLimiter {
+        &self.rate_limiter
     }
 
     /// Get Agent builder
     fn get_agent_builder(&self) -> AgentBuilder<'_> {
-        AgentBuilder::new(&self.client, &self.config)
+        AgentBuilder::new(&self.client, &self.config, self.memory.as_ref())
     }
 
-    /// Generic retry logic for handling async operation retry mechanism
-    async fn retry_with_backoff<T, F, Fut>(&self, operation: F) -> Result<T>
+    /// Generic retry logic with exponential backoff, every attempt waits for the provider's rate limit.
+    /// `estimated_tokens` is None for operations taking the rate limit themselves, once per model turn
+    async fn retry_with_backoff<T, F, Fut>(&self, estimated_tokens: Option<usize>, operation: F) -> Result<T>
     where
         F: Fn() -> Fut,
         Fut: Future<Output = Result<T, anyhow::Error>>,
     {
         let llm_config = &self.config.llm;
+        let provider = llm_config.provider.to_string();
         let max_retries = llm_config.retry_attempts;
-        let retry_delay_ms = llm_config.retry_delay_ms;
         let mut retries = 0;
 
         loop {
+            if let Some(estimated_tokens) = estimated_tokens {
+                self.rate_limiter.acquire(&provider, estimated_tokens).await;
+            }
             match operation().await {
                 Ok(result) => return Ok(result),
+                // Retrying cannot help once the budget is used up, the run stops to save its results
+                Err(err) if BudgetExhausted::is(&err) => return Err(err),
                 Err(err) => {
                     retries += 1;
                     eprintln!(
@@ -65,20 +278,71 @@ impl LLMClient {
                     if retries >= max_retries {
                         return Err(err);
                     }
-                    tokio::time::sleep(std::time::Duration::from_millis(retry_delay_ms)).await;
+                    let delay = self.rate_limiter.backoff(&provider, &err, retries, llm_config);
+                    tokio::time::sleep(delay).await;
                 }
             }
         }
     }
 
+    /// Estimated input tokens of a call, taken from the rate limit before it is sent
+    fn estimate_input_tokens(system_prompt: &str, user_prompt: &str) -> usize {
+        estimate_token_usage(&format!("{} {}", system_prompt, user_prompt), "").input_tokens
+    }
+
+    /// Count a finished call against the budget and settle its tokens with the rate limit
+    fn record_call(&self, estimated_tokens: usize, usage: &types::TokenUsage, model: &str) {
+        self.budget.record(usage, &self.config.llm.provider.to_string(), model);
+        self.rate_limiter.settle(
+            &self.config.llm.provider.to_string(),
+            estimated_tokens,
+            usage.total_tokens,
+        );
+    }
+
     /// Data extraction method
-    pub async fn extract<T>(&self, system_prompt: &str, user_prompt: &str) -> Result<T>
+    pub async fn extract<T>(&self, system_prompt: &str, user_prompt: &str) -> Result<LLMResponse<T>>
+    where
+        T: JsonSchema + for<'a> Deserialize<'a> + Serialize + Send + Sync + 'static,
+    {
+        self.with_fallback(|client| async move {
+            client.extract_on_backend(system_prompt, user_prompt).await
+        })
+        .await
+    }
+
+    /// Extraction on this client's backend, falling over from the efficient to the powerful model
+    async fn extract_on_backend<T>(&self, system_prompt: &str, user_prompt: &str) -> Result<LLMResponse<T>>
     where
         T: JsonSchema + for<'a> Deserialize<'a> + Serialize + Send + Sync + 'static,
     {
-        let (befitting_model, fallover_model) =
-            evaluate_befitting_model(&self.config.llm, system_prompt, user_prompt);
+        self.budget.ensure_available()?;
 
+        let llm_config = &self.config.llm;
+        let (befitting_model, fallover_model) = if self.budget.is_degraded() {
+            self.log_routing(&llm_config.model_efficient, "budget degraded");
+            (llm_config.model_efficient.clone(), None)
+        } else {
+            let (model, fallover_model) =
+                evaluate_befitting_model(llm_config, system_prompt, user_prompt);
+            let reason = if llm_config.model_efficient == llm_config.model_powerful {
+                "single model".to_string()
+            } else {
+                format!(
+                    "{}KB prompt, powerful model from {}KB",
+                    (system_prompt.len() + user_prompt.len()) / 1024,
+                    llm_config
+                        .routing
+                        .powerful_threshold_bytes
+                        .unwrap_or(DEFAULT_POWERFUL_THRESHOLD_BYTES)
+                        / 1024
+                )
+            };
+            self.log_routing(&model, &reason);
+            (model, fallover_model)
+        };
+
+        // Every call of the extraction settles its rate limit and is charged as soon as it finishes
         self.extract_inner(system_prompt, user_prompt, befitting_model, fallover_model)
             .await
     }
@@ -89,7 +353,7 @@ impl LLMClient {
         user_prompt: &str,
         befitting_model: String,
         fallover_model: Option<String>,
-    ) -> Result<T>
+    ) -> Result<LLMResponse<T>>
     where
         T: JsonSchema + for<'a> Deserialize<'a> + Serialize + Send + Sync + 'static,
     {
@@ -99,9 +363,30 @@ impl LLMClient {
             self.client
                 .create_extractor::<T>(&befitting_model, system_prompt, llm_config);
 
-        self.retry_with_backoff(|| async {
-            match extractor.extract(user_prompt).await {
-                Ok(r) => Ok(r),
+        // Submit retries and repairs are requests of their own, each takes the rate limit and the budget
+        let provider = llm_config.provider.to_string();
+        let calls = TurnRateLimit::new(self.rate_limiter.clone(), &provider, system_prompt);
+        let meter = UsageMeter {
+            budget: &self.budget,
+            ledger: &self.usage,
+            agent: &self.cache_scope,
+            provider: &provider,
+            model: &befitting_model,
+            system_prompt,
+        };
+        self.retry_with_backoff(None, || async {
+            match extractor
+                .extract(user_prompt, llm_config.repair_attempts, &calls, &meter)
+                .await
+            {
+                Ok((content, usage)) => Ok(LLMResponse {
+                    content,
+                    usage,
+                    model: befitting_model.clone(),
+                    provider: llm_config.provider.to_string(),
+                }),
+                // The fallover model would only hit the same budget
+                Err(e) if BudgetExhausted::is(&e) => Err(e),
                 Err(e) => match fallover_model {
                     Some(ref model) => {
                         let msg = self.config.target_language.msg_ai_service_error()
@@ -122,7 +407,7 @@ impl LLMClient {
                             .replacen("{}", &llm_config.retry_attempts.to_string(), 1)
                             .replacen("{}", &e.to_string(), 1);
                         eprintln!("{}", msg);
-                        Err(e.into())
+                        Err(e)
                     }
                 },
             }
@@ -131,12 +416,19 @@ impl LLMClient {
     }
 
     /// Intelligent dialogue method (using default ReAct configuration)
-    pub async fn prompt(&self, system_prompt: &str, user_prompt: &str) -> Result<String> {
-        let react_config = ReActConfig::default();
-        let response = self
-            .prompt_with_react(system_prompt, user_prompt, react_config)
-            .await?;
-        Ok(response.content)
+    pub async fn prompt(&self, system_prompt: &str, user_prompt: &str) -> Result<LLMResponse<String>> {
+        self.with_fallback(|client| async move {
+            let response = client
+                .prompt_with_react(system_prompt, user_prompt, ReActConfig::default())
+                .await?;
+            Ok(LLMResponse {
+                content: response.content,
+                usage: response.token_usage,
+                model: client.config.llm.model_efficient.clone(),
+                provider: client.config.llm.provider.to_string(),
+            })
+        })
+        .await
     }
 
     /// Multi-turn dialogue using ReAct mode
@@ -146,15 +438,34 @@ impl LLMClient {
         user_prompt: &str,
         react_config: ReActConfig,
     ) -> Result<ReActResponse> {
+        self.budget.ensure_available()?;
+
         let agent_builder = self.get_agent_builder();
         let agent = agent_builder.build_agent_with_tools(system_prompt);
         let model_name = self.config.llm.model_efficient.clone();
+        self.log_routing(&model_name, "agent prompts use the efficient model");
 
-        let response = self
-            .retry_with_backoff(|| async {
-                ReActExecutor::execute(&agent, user_prompt, &react_config, &self.config.target_language, &model_name)
-                    .await
-                    .map_err(|e| e.into())
+        // Every turn of the conversation is a request, each takes the rate limit and settles its usage
+        let turns = TurnRateLimit::new(
+            self.rate_limiter.clone(),
+            &self.config.llm.provider.to_string(),
+            system_prompt,
+        );
+        let mut response = self
+            .retry_with_backoff(None, || async {
+                let mut history = Vec::new();
+                let result = ReActExecutor::execute(
+                    &agent,
+                    user_prompt,
+                    &react_config,
+                    &self.config.target_language,
+                    &model_name,
+                    &mut history,
+                    &turns,
+                )
+                .await;
+                self.write_trace(system_prompt, &react_config, &model_name, history, &result);
+                result
             })
             .await?;
 
@@ -175,7 +486,7 @@ impl LLMClient {
                     if react_config.verbose {
                         println!("✅ Summary reasoning completed");
                     }
-                    return Ok(summary_response);
+                    response = summary_response;
                 }
                 Err(e) => {
                     if react_config.verbose {
@@ -187,9 +498,60 @@ impl LLMClient {
             }
         }
 
+        let usage = match &response.token_usage {
+            Some(usage) => usage.clone(),
+            None => {
+                // Every ReAct iteration resends the prompt
+                let estimate = estimate_token_usage(
+                    &format!("{} {}", system_prompt, user_prompt),
+                    &response.content,
+                );
+                types::TokenUsage::new(
+                    estimate.input_tokens * response.iterations_used.max(1),
+                    estimate.output_tokens,
+                )
+            }
+        };
+        self.budget.record(&usage, &self.config.llm.provider.to_string(), &model_name);
+
         Ok(response)
     }
 
+    /// Persist the transcript of a conversation attempt, failed attempts included
+    fn write_trace(
+        &self,
+        system_prompt: &str,
+        react_config: &ReActConfig,
+        model_name: &str,
+        messages: Vec<rig::completion::Message>,
+        result: &Result<ReActResponse>,
+    ) {
+        let (outcome, error, usage) = match result {
+            Ok(response) if response.stopped_by_max_depth => {
+                (TraceOutcome::MaxDepth, None, response.token_usage.clone())
+            }
+            Ok(response) => (TraceOutcome::Completed, None, response.token_usage.clone()),
+            Err(e) => (TraceOutcome::Failed, Some(e.to_string()), None),
+        };
+        let trace = ConversationTrace {
+            timestamp: chrono::Utc::now(),
+            agent: self.agent.clone(),
+            provider: self.config.llm.provider.to_string(),
+            model: model_name.to_string(),
+            max_iterations: react_config.max_iterations,
+            iterations: ConversationTrace::iterations(&messages),
+            outcome,
+            error,
+            usage,
+            system_prompt: system_prompt.to_string(),
+            tool_calls: ConversationTrace::tool_calls(&messages),
+            messages,
+        };
+        if let Err(e) = self.traces.write(&trace) {
+            eprintln!("⚠️  Failed to save the trace of agent {}: {}", self.agent, e);
+        }
+    }
+
     /// Attempt summary reasoning fallover
     async fn try_summary_reasoning(
         &self,
@@ -205,8 +567,9 @@ impl LLMClient {
             .as_ref()
             .ok_or_else(|| anyhow::anyhow!("Missing chat history"))?;
 
-        let summary_result = self
-            .retry_with_backoff(|| async {
+        let estimated_tokens = Self::estimate_input_tokens(system_prompt, user_prompt);
+        let (summary_result, summary_usage) = self
+            .retry_with_backoff(Some(estimated_tokens), || async {
                 SummaryReasoner::summarize_and_reason(
                     &agent_without_tools,
                     system_prompt,
@@ -215,16 +578,32 @@ impl LLMClient {
                     &original_response.tool_calls_history,
                 )
                 .await
-                .map_err(|e| e.into())
             })
             .await?;
+        if let Some(summary_usage) = &summary_usage {
+            self.rate_limiter.settle(
+                &self.config.llm.provider.to_string(),
+                estimated_tokens,
+                summary_usage.total_tokens,
+            );
+        }
 
-        Ok(ReActResponse::from_summary_reasoning(
+        let mut response = ReActResponse::from_summary_reasoning(
             summary_result,
             original_response.iterations_used,
             original_response.tool_calls_history.clone(),
             chat_history.clone(),
-        ))
+        );
+        // Usage is only known if both the interrupted conversation and the summary reported it
+        response.token_usage = original_response
+            .token_usage
+            .clone()
+            .zip(summary_usage)
+            .map(|(mut usage, summary)| {
+                usage.add(&summary);
+                usage
+            });
+        Ok(response)
     }
 
     /// Simplified single-turn dialogue method (without tools)
@@ -232,11 +611,65 @@ impl LLMClient {
         &self,
         system_prompt: &str,
         user_prompt: &str,
-    ) -> Result<String> {
+    ) -> Result<LLMResponse<String>> {
+        self.with_fallback(|client| async move {
+            client.prompt_without_react_on_backend(system_prompt, user_prompt).await
+        })
+        .await
+    }
+
+    async fn prompt_without_react_on_backend(
+        &self,
+        system_prompt: &str,
+        user_prompt: &str,
+    ) -> Result<LLMResponse<String>> {
+        self.budget.ensure_available()?;
+
         let agent_builder = self.get_agent_builder();
         let agent = agent_builder.build_agent_without_tools(system_prompt);
+        self.log_routing(
+            &self.config.llm.model_efficient,
+            "agent prompts use the efficient model",
+        );
 
-        self.retry_with_backoff(|| async { agent.prompt(user_prompt).await.map_err(|e| e.into()) })
-            .await
+        let estimated_tokens = Self::estimate_input_tokens(system_prompt, user_prompt);
+        let (content, usage) = self
+            .retry_with_backoff(Some(estimated_tokens), || async { agent.prompt(user_prompt).await })
+            .await?;
+        let response = LLMResponse {
+            content,
+            usage,
+            model: self.config.llm.model_efficient.clone(),
+            provider: self.config.llm.provider.to_string(),
+        };
+        self.record_call(
+            estimated_tokens,
+            &response.usage_or_estimate(&format!("{} {}", system_prompt, user_prompt), &response.content),
+            &response.model,
+        );
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_exhausted_budget_is_not_retried() {
+        let client = LLMClient::new(Config::default()).unwrap();
+        let calls = AtomicUsize::new(0);
+        let result: Result<()> = client
+            .retry_with_backoff(None, || async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Err(BudgetExhausted {
+                    reason: "token limit reached".to_string(),
+                }
+                .into())
+            })
+            .await;
+        assert!(BudgetExhausted::is(&result.unwrap_err()));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
     }
 }
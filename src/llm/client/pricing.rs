@@ -0,0 +1,237 @@
+//! Model pricing - Prices per model used to report the cost of LLM calls
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::sync::Mutex;
+
+use crate::config::{LLMConfig, LLMProvider, ModelPricing};
+use crate::llm::client::types::TokenUsage;
+
+/// Price of models missing from the table
+const DEFAULT_PRICING: ModelPricing = ModelPricing {
+    input: 0.00025,
+    output: 0.002,
+};
+
+/// Built-in prices of common models: name prefix, input and output in USD per 1k tokens
+const BUILTIN_PRICING: &[(&str, f64, f64)] = &[
+    ("gpt-4o", 0.0025, 0.01),
+    ("gpt-4o-mini", 0.00015, 0.0006),
+    ("gpt-4.1", 0.002, 0.008),
+    ("gpt-4.1-mini", 0.0004, 0.0016),
+    ("gpt-4.1-nano", 0.0001, 0.0004),
+    ("o3", 0.002, 0.008),
+    ("o3-mini", 0.0011, 0.0044),
+    ("o4-mini", 0.0011, 0.0044),
+    ("claude-3-5-haiku", 0.0008, 0.004),
+    ("claude-3-5-sonnet", 0.003, 0.015),
+    ("claude-3-7-sonnet", 0.003, 0.015),
+    ("claude-sonnet-4", 0.003, 0.015),
+    ("claude-opus-4", 0.015, 0.075),
+    ("gemini-2.0-flash", 0.0001, 0.0004),
+    ("gemini-2.5-flash", 0.0003, 0.0025),
+    ("gemini-2.5-pro", 0.00125, 0.01),
+    ("deepseek-chat", 0.00027, 0.0011),
+    ("deepseek-reasoner", 0.00055, 0.00219),
+    ("mistral-large", 0.002, 0.006),
+    ("mistral-small", 0.0002, 0.0006),
+    ("kimi-k2", 0.0006, 0.0025),
+    ("moonshot-v1-8k", 0.0002, 0.002),
+    ("moonshot-v1-32k", 0.001, 0.003),
+    ("moonshot-v1-128k", 0.002, 0.005),
+];
+
+/// Where the price of a model comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PriceSource {
+    /// `[llm.pricing]` in the config
+    Config,
+    /// Built-in table
+    BuiltIn,
+    /// Model served locally, free of charge
+    Local,
+    /// Unknown model, priced with the default price
+    Default,
+}
+
+impl fmt::Display for PriceSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PriceSource::Config => write!(f, "config"),
+            PriceSource::BuiltIn => write!(f, "built-in"),
+            PriceSource::Local => write!(f, "local"),
+            PriceSource::Default => write!(f, "default"),
+        }
+    }
+}
+
+impl ModelPricing {
+    /// Cost of a call in USD
+    pub fn cost(&self, usage: &TokenUsage) -> f64 {
+        (usage.input_tokens as f64 * self.input + usage.output_tokens as f64 * self.output) / 1000.0
+    }
+}
+
+/// Prices of the models of a run, configured prices first, then the built-in table
+pub struct PricingTable {
+    configured: BTreeMap<String, ModelPricing>,
+    builtin: BTreeMap<String, ModelPricing>,
+    /// Models served locally by Ollama, through the main provider, a route or a fallback backend. Prices
+    /// models whose provider is not known, such as those of cache entries written by older versions
+    local_models: BTreeSet<String>,
+    /// Unknown models that were already warned about
+    warned: Mutex<BTreeSet<String>>,
+}
+
+impl PricingTable {
+    pub fn new(llm: &LLMConfig) -> Self {
+        let builtin = BUILTIN_PRICING
+            .iter()
+            .map(|(name, input, output)| {
+                (
+                    name.to_string(),
+                    ModelPricing {
+                        input: *input,
+                        output: *output,
+                    },
+                )
+            })
+            .collect();
+        Self {
+            configured: llm
+                .pricing
+                .iter()
+                .map(|(name, price)| (name.to_lowercase(), price.clone()))
+                .collect(),
+            builtin,
+            local_models: Self::local_models(llm),
+            warned: Mutex::new(BTreeSet::new()),
+        }
+    }
+
+    /// Price of a model served by a provider, empty if unknown, and where the price comes from
+    pub fn price(&self, provider: &str, model: &str) -> (ModelPricing, PriceSource) {
+        if let Some(price) = Self::lookup(&self.configured, model) {
+            return (price.clone(), PriceSource::Config);
+        }
+        let local = match provider {
+            "" => self.local_models.contains(model),
+            provider => provider == LLMProvider::Ollama.to_string(),
+        };
+        if local {
+            let free = ModelPricing {
+                input: 0.0,
+                output: 0.0,
+            };
+            return (free, PriceSource::Local);
+        }
+        if let Some(price) = Self::lookup(&self.builtin, model) {
+            return (price.clone(), PriceSource::BuiltIn);
+        }
+        (DEFAULT_PRICING, PriceSource::Default)
+    }
+
+    /// Cost of a call in USD, warns once per model priced with the default price
+    pub fn cost(&self, usage: &TokenUsage, provider: &str, model: &str) -> f64 {
+        let (price, source) = self.price(provider, model);
+        if source == PriceSource::Default
+            && !model.is_empty()
+            && self.warned.lock().unwrap().insert(model.to_string())
+        {
+            eprintln!(
+                "⚠️  No price known for model {}, using the default price; add it to [llm.pricing] for accurate costs",
+                model
+            );
+        }
+        price.cost(usage)
+    }
+
+    fn local_models(llm: &LLMConfig) -> BTreeSet<String> {
+        let mut models = BTreeSet::new();
+        if llm.provider == LLMProvider::Ollama {
+            models.insert(llm.model_efficient.clone());
+            models.insert(llm.model_powerful.clone());
+        }
+        for route in llm.routing.routes.values() {
+            let routed = route.apply(llm);
+            if routed.provider == LLMProvider::Ollama {
+                models.insert(routed.model_efficient);
+                models.insert(routed.model_powerful);
+            }
+        }
+        for backend in &llm.fallback {
+            if backend.provider == LLMProvider::Ollama {
+                models.insert(backend.model.clone());
+            }
+        }
+        models
+    }
+
+    /// Exact name first, then the longest matching prefix, with and without a `vendor/` prefix
+    fn lookup<'a>(prices: &'a BTreeMap<String, ModelPricing>, model: &str) -> Option<&'a ModelPricing> {
+        let model = model.to_lowercase();
+        let names = [model.as_str(), model.rsplit('/').next().unwrap_or_default()];
+        names.iter().find_map(|name| {
+            prices.get(*name).or_else(|| {
+                prices
+                    .iter()
+                    .filter(|(prefix, _)| name.starts_with(prefix.as_str()))
+                    .max_by_key(|(prefix, _)| prefix.len())
+                    .map(|(_, price)| price)
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::FallbackBackend;
+
+    #[test]
+    fn test_lookup_prefers_config_then_longest_prefix() {
+        let mut llm = LLMConfig::default();
+        llm.pricing.insert(
+            "Qwen/Qwen3-Next-80B-A3B-Instruct".to_string(),
+            ModelPricing {
+                input: 0.001,
+                output: 0.004,
+            },
+        );
+        let table = PricingTable::new(&llm);
+
+        let (price, source) = table.price("openai", "gpt-4o-mini-2024-07-18");
+        assert_eq!(source, PriceSource::BuiltIn);
+        assert_eq!(price.input, 0.00015);
+        assert_eq!(table.price("openrouter", "openai/gpt-4o").1, PriceSource::BuiltIn);
+        assert_eq!(table.price("openai", "qwen/qwen3-next-80b-a3b-instruct").1, PriceSource::Config);
+        assert_eq!(table.price("openai", "unknown-model").1, PriceSource::Default);
+    }
+
+    #[test]
+    fn test_ollama_models_are_free() {
+        let llm = LLMConfig {
+            fallback: vec![FallbackBackend {
+                provider: LLMProvider::Ollama,
+                model: "llama3.1".to_string(),
+                api_key: None,
+                api_base_url: None,
+            }],
+            ..LLMConfig::default()
+        };
+        let table = PricingTable::new(&llm);
+
+        assert_eq!(table.price("ollama", "gpt-4o").1, PriceSource::Local);
+        assert_eq!(table.price("openai", "gpt-4o").1, PriceSource::BuiltIn);
+        // Without the provider, the models of Ollama backends in the configuration are known to be local
+        assert_eq!(table.price("", "llama3.1").1, PriceSource::Local);
+        assert_eq!(table.price("", "gpt-4o").1, PriceSource::BuiltIn);
+    }
+
+    #[test]
+    fn test_cost_uses_input_and_output_price() {
+        let table = PricingTable::new(&LLMConfig::default());
+        // 2k input at 0.0025, 1k output at 0.01
+        assert!((table.cost(&TokenUsage::new(2000, 1000), "openai", "gpt-4o") - 0.015).abs() < 1e-9);
+    }
+}
@@ -1,22 +1,30 @@
 //! LLM Provider support module
 
 use anyhow::Result;
+use anyhow::anyhow;
 use rig::{
-    agent::Agent,
+    agent::{Agent, AgentBuilder},
     client::CompletionClient,
-    completion::{Prompt, PromptError},
+    completion::{AssistantContent, Completion, CompletionModel, Message, Prompt, PromptError},
     extractor::Extractor,
     providers::gemini::completion::gemini_api_types::{AdditionalParameters, GenerationConfig},
 };
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
 
 use crate::{
-    config::{LLMConfig, LLMProvider},
-    llm::tools::time::AgentToolTime,
+    config::{FixtureMode, LLMConfig, LLMProvider},
+    llm::tools::{PresetTools, time::AgentToolTime},
 };
 
+use super::fixtures::{FixtureAgent, FixtureExtractor, FixtureModel, FixtureSource, FixtureStore};
 use super::ollama_extractor::OllamaExtractorWrapper;
+use super::rate_limit::TurnRateLimit;
+use super::structured::{conform, repair_prompt};
+use super::types::TokenUsage;
+use super::usage::UsageMeter;
 
 /// Unified Provider client enum
 #[derive(Clone)]
@@ -29,11 +37,30 @@ pub enum ProviderClient {
     Anthropic(rig::providers::anthropic::Client),
     Gemini(rig::providers::gemini::Client),
     Ollama(rig::providers::ollama::Client),
+    /// Wraps a provider and records every call to fixtures
+    Recording(Box<ProviderClient>, Arc<FixtureStore>),
+    /// Serves every call from recorded fixtures without contacting a provider
+    Replay(Arc<FixtureStore>),
 }
 
 impl ProviderClient {
     /// Create corresponding provider client based on configuration
     pub fn new(config: &LLMConfig) -> Result<Self> {
+        let Some(fixtures) = &config.fixtures else {
+            return Self::new_provider(config);
+        };
+        let store = Arc::new(FixtureStore::new(fixtures)?);
+        match fixtures.mode {
+            FixtureMode::Record => Ok(ProviderClient::Recording(
+                Box::new(Self::new_provider(config)?),
+                store,
+            )),
+            FixtureMode::Replay => Ok(ProviderClient::Replay(store)),
+        }
+    }
+
+    /// Create the client of the configured provider
+    fn new_provider(config: &LLMConfig) -> Result<Self> {
         match config.provider {
             LLMProvider::OpenAI => {
                 let client = rig::providers::openai::Client::builder(&config.api_key)
@@ -195,6 +222,22 @@ impl ProviderClient {
                 let agent = builder.build();
                 ProviderAgent::Ollama(agent)
             }
+            ProviderClient::Recording(client, store) => ProviderAgent::Fixture(FixtureAgent {
+                source: FixtureSource::Record(Box::new(client.create_agent(model, system_prompt, config))),
+                store: store.clone(),
+                model: model.to_string(),
+                system_prompt: system_prompt.to_string(),
+            }),
+            ProviderClient::Replay(store) => ProviderAgent::Fixture(FixtureAgent {
+                source: FixtureSource::Replay(
+                    AgentBuilder::new(FixtureModel::new(store.clone(), model, system_prompt))
+                        .preamble(system_prompt)
+                        .build(),
+                ),
+                store: store.clone(),
+                model: model.to_string(),
+                system_prompt: system_prompt.to_string(),
+            }),
         }
     }
 
@@ -204,8 +247,7 @@ impl ProviderClient {
         model: &str,
         system_prompt: &str,
         config: &LLMConfig,
-        file_explorer: &crate::llm::tools::file_explorer::AgentToolFileExplorer,
-        file_reader: &crate::llm::tools::file_reader::AgentToolFileReader,
+        tools: &PresetTools,
     ) -> ProviderAgent {
         let tool_time = AgentToolTime::new();
 
@@ -223,8 +265,11 @@ impl ProviderClient {
                 }
 
                 let agent = builder
-                    .tool(file_explorer.clone())
-                    .tool(file_reader.clone())
+                    .tool(tools.file_explorer.clone())
+                    .tool(tools.file_reader.clone())
+                    .tool(tools.code_search.clone())
+                    .tool(tools.insight_lookup.clone())
+                    .tool(tools.git_history.clone())
                     .tool(tool_time)
                     .build();
                 ProviderAgent::OpenAI(agent)
@@ -240,8 +285,11 @@ impl ProviderClient {
                 }
 
                 let agent = builder
-                    .tool(file_explorer.clone())
-                    .tool(file_reader.clone())
+                    .tool(tools.file_explorer.clone())
+                    .tool(tools.file_reader.clone())
+                    .tool(tools.code_search.clone())
+                    .tool(tools.insight_lookup.clone())
+                    .tool(tools.git_history.clone())
                     .tool(tool_time)
                     .build();
                 ProviderAgent::Moonshot(agent)
@@ -257,8 +305,11 @@ impl ProviderClient {
                 }
 
                 let agent = builder
-                    .tool(file_explorer.clone())
-                    .tool(file_reader.clone())
+                    .tool(tools.file_explorer.clone())
+                    .tool(tools.file_reader.clone())
+                    .tool(tools.code_search.clone())
+                    .tool(tools.insight_lookup.clone())
+                    .tool(tools.git_history.clone())
                     .tool(tool_time)
                     .build();
                 ProviderAgent::DeepSeek(agent)
@@ -273,8 +324,11 @@ impl ProviderClient {
                 }
 
                 let agent = builder
-                    .tool(file_explorer.clone())
-                    .tool(file_reader.clone())
+                    .tool(tools.file_explorer.clone())
+                    .tool(tools.file_reader.clone())
+                    .tool(tools.code_search.clone())
+                    .tool(tools.insight_lookup.clone())
+                    .tool(tools.git_history.clone())
                     .tool(tool_time)
                     .build();
                 ProviderAgent::Mistral(agent)
@@ -289,8 +343,11 @@ impl ProviderClient {
                 }
 
                 let agent = builder
-                    .tool(file_explorer.clone())
-                    .tool(file_reader.clone())
+                    .tool(tools.file_explorer.clone())
+                    .tool(tools.file_reader.clone())
+                    .tool(tools.code_search.clone())
+                    .tool(tools.insight_lookup.clone())
+                    .tool(tools.git_history.clone())
                     .tool(tool_time)
                     .build();
                 ProviderAgent::OpenRouter(agent)
@@ -306,8 +363,11 @@ impl ProviderClient {
                 }
 
                 let agent = builder
-                    .tool(file_explorer.clone())
-                    .tool(file_reader.clone())
+                    .tool(tools.file_explorer.clone())
+                    .tool(tools.file_reader.clone())
+                    .tool(tools.code_search.clone())
+                    .tool(tools.insight_lookup.clone())
+                    .tool(tools.git_history.clone())
                     .tool(tool_time)
                     .build();
                 ProviderAgent::Anthropic(agent)
@@ -326,8 +386,11 @@ impl ProviderClient {
                 }
 
                 let agent = builder
-                    .tool(file_explorer.clone())
-                    .tool(file_reader.clone())
+                    .tool(tools.file_explorer.clone())
+                    .tool(tools.file_reader.clone())
+                    .tool(tools.code_search.clone())
+                    .tool(tools.insight_lookup.clone())
+                    .tool(tools.git_history.clone())
                     .tool(tool_time)
                     .additional_params(serde_json::to_value(cfg).unwrap())
                     .build();
@@ -344,12 +407,39 @@ impl ProviderClient {
                 }
 
                 let agent = builder
-                    .tool(file_explorer.clone())
-                    .tool(file_reader.clone())
+                    .tool(tools.file_explorer.clone())
+                    .tool(tools.file_reader.clone())
+                    .tool(tools.code_search.clone())
+                    .tool(tools.insight_lookup.clone())
+                    .tool(tools.git_history.clone())
                     .tool(tool_time)
                     .build();
                 ProviderAgent::Ollama(agent)
             }
+            ProviderClient::Recording(client, store) => ProviderAgent::Fixture(FixtureAgent {
+                source: FixtureSource::Record(Box::new(
+                    client.create_agent_with_tools(model, system_prompt, config, tools),
+                )),
+                store: store.clone(),
+                model: model.to_string(),
+                system_prompt: system_prompt.to_string(),
+            }),
+            ProviderClient::Replay(store) => ProviderAgent::Fixture(FixtureAgent {
+                source: FixtureSource::Replay(
+                    AgentBuilder::new(FixtureModel::new(store.clone(), model, system_prompt))
+                        .preamble(system_prompt)
+                        .tool(tools.file_explorer.clone())
+                        .tool(tools.file_reader.clone())
+                        .tool(tools.code_search.clone())
+                        .tool(tools.insight_lookup.clone())
+                        .tool(tools.git_history.clone())
+                        .tool(tool_time)
+                        .build(),
+                ),
+                store: store.clone(),
+                model: model.to_string(),
+                system_prompt: system_prompt.to_string(),
+            }),
         }
     }
 
@@ -438,10 +528,24 @@ impl ProviderClient {
                 let agent = builder.build();
 
                 // Wrap with OllamaExtractorWrapper to handle structured output
-                let wrapper = OllamaExtractorWrapper::new(agent, config.retry_attempts);
+                let wrapper = OllamaExtractorWrapper::new(agent);
 
                 ProviderExtractor::Ollama(wrapper)
             }
+            ProviderClient::Recording(client, store) => {
+                ProviderExtractor::Fixture(FixtureExtractor {
+                    inner: Some(Box::new(client.create_extractor(model, system_prompt, config))),
+                    store: store.clone(),
+                    model: model.to_string(),
+                    system_prompt: system_prompt.to_string(),
+                })
+            }
+            ProviderClient::Replay(store) => ProviderExtractor::Fixture(FixtureExtractor {
+                inner: None,
+                store: store.clone(),
+                model: model.to_string(),
+                system_prompt: system_prompt.to_string(),
+            }),
         }
     }
 }
@@ -456,46 +560,75 @@ pub enum ProviderAgent {
     Moonshot(Agent<rig::providers::moonshot::CompletionModel>),
     DeepSeek(Agent<rig::providers::deepseek::CompletionModel>),
     Ollama(Agent<rig::providers::ollama::CompletionModel<reqwest::Client>>),
+    Fixture(FixtureAgent),
 }
 
 impl ProviderAgent {
-    /// Execute prompt
-    pub async fn prompt(&self, prompt: &str) -> Result<String> {
+    /// Execute prompt, returns the reply and the token usage reported by the provider
+    pub async fn prompt(&self, prompt: &str) -> Result<(String, Option<TokenUsage>)> {
         match self {
-            ProviderAgent::OpenAI(agent) => agent.prompt(prompt).await.map_err(|e| e.into()),
-            ProviderAgent::Moonshot(agent) => agent.prompt(prompt).await.map_err(|e| e.into()),
-            ProviderAgent::DeepSeek(agent) => agent.prompt(prompt).await.map_err(|e| e.into()),
-            ProviderAgent::Mistral(agent) => agent.prompt(prompt).await.map_err(|e| e.into()),
-            ProviderAgent::OpenRouter(agent) => agent.prompt(prompt).await.map_err(|e| e.into()),
-            ProviderAgent::Anthropic(agent) => agent.prompt(prompt).await.map_err(|e| e.into()),
-            ProviderAgent::Gemini(agent) => agent.prompt(prompt).await.map_err(|e| e.into()),
-            ProviderAgent::Ollama(agent) => agent.prompt(prompt).await.map_err(|e| e.into()),
+            ProviderAgent::Fixture(agent) => agent.prompt(prompt).await,
+            _ => self
+                .multi_turn(prompt, 0, &mut Vec::new(), None)
+                .await
+                .map_err(|e| e.into()),
         }
     }
 
-    /// Execute multi-turn dialogue
+    /// Execute multi-turn dialogue, returns the reply and the token usage of all turns.
+    /// Every message of the conversation is appended to `history`, also when it fails.
+    /// With `turns`, every model turn waits for the rate limit
     pub async fn multi_turn(
         &self,
         prompt: &str,
         max_iterations: usize,
-    ) -> Result<String, PromptError> {
+        history: &mut Vec<Message>,
+        turns: Option<&TurnRateLimit>,
+    ) -> Result<(String, Option<TokenUsage>), PromptError> {
         match self {
-            ProviderAgent::OpenAI(agent) => agent.prompt(prompt).multi_turn(max_iterations).await,
-            ProviderAgent::Moonshot(agent) => agent.prompt(prompt).multi_turn(max_iterations).await,
-            ProviderAgent::DeepSeek(agent) => agent.prompt(prompt).multi_turn(max_iterations).await,
-            ProviderAgent::Mistral(agent) => agent.prompt(prompt).multi_turn(max_iterations).await,
+            ProviderAgent::OpenAI(agent) => prompt_with_usage(agent, prompt, max_iterations, history, turns).await,
+            ProviderAgent::Moonshot(agent) => prompt_with_usage(agent, prompt, max_iterations, history, turns).await,
+            ProviderAgent::DeepSeek(agent) => prompt_with_usage(agent, prompt, max_iterations, history, turns).await,
+            ProviderAgent::Mistral(agent) => prompt_with_usage(agent, prompt, max_iterations, history, turns).await,
             ProviderAgent::OpenRouter(agent) => {
-                agent.prompt(prompt).multi_turn(max_iterations).await
+                prompt_with_usage(agent, prompt, max_iterations, history, turns).await
             }
             ProviderAgent::Anthropic(agent) => {
-                agent.prompt(prompt).multi_turn(max_iterations).await
+                prompt_with_usage(agent, prompt, max_iterations, history, turns).await
             }
-            ProviderAgent::Gemini(agent) => agent.prompt(prompt).multi_turn(max_iterations).await,
-            ProviderAgent::Ollama(agent) => agent.prompt(prompt).multi_turn(max_iterations).await,
+            ProviderAgent::Gemini(agent) => prompt_with_usage(agent, prompt, max_iterations, history, turns).await,
+            ProviderAgent::Ollama(agent) => prompt_with_usage(agent, prompt, max_iterations, history, turns).await,
+            ProviderAgent::Fixture(agent) => agent.multi_turn(prompt, max_iterations, history, turns).await,
         }
     }
 }
 
+/// Prompt an agent, keeping the token usage summed over all turns and the messages in `history`
+pub(super) async fn prompt_with_usage<M>(
+    agent: &Agent<M>,
+    prompt: &str,
+    max_iterations: usize,
+    history: &mut Vec<Message>,
+    turns: Option<&TurnRateLimit>,
+) -> Result<(String, Option<TokenUsage>), PromptError>
+where
+    M: CompletionModel + 'static,
+{
+    let request = agent
+        .prompt(prompt)
+        .multi_turn(max_iterations)
+        .with_history(history)
+        .extended_details();
+    let response = match turns {
+        Some(turns) => request.with_hook(turns.clone()).await?,
+        None => request.await?,
+    };
+    Ok((response.output, TokenUsage::from_reported(&response.total_usage)))
+}
+
+/// Extra extraction attempts when the model answers without calling the submit tool
+const SUBMIT_RETRIES: u32 = 2;
+
 /// Unified Extractor enum
 pub enum ProviderExtractor<T>
 where
@@ -509,39 +642,143 @@ where
     Moonshot(Extractor<rig::providers::moonshot::CompletionModel, T>),
     DeepSeek(Extractor<rig::providers::deepseek::CompletionModel, T>),
     Ollama(OllamaExtractorWrapper<T>),
+    Fixture(FixtureExtractor<T>),
 }
 
 impl<T> ProviderExtractor<T>
 where
     T: JsonSchema + for<'a> Deserialize<'a> + Serialize + Send + Sync + 'static,
 {
-    /// Execute extraction
-    pub async fn extract(&self, prompt: &str) -> Result<T> {
-        match self {
-            ProviderExtractor::OpenAI(extractor) => {
-                extractor.extract(prompt).await.map_err(|e| e.into())
-            }
-            ProviderExtractor::Moonshot(extractor) => {
-                extractor.extract(prompt).await.map_err(|e| e.into())
-            }
-            ProviderExtractor::DeepSeek(extractor) => {
-                extractor.extract(prompt).await.map_err(|e| e.into())
-            }
-            ProviderExtractor::Mistral(extractor) => {
-                extractor.extract(prompt).await.map_err(|e| e.into())
-            }
-            ProviderExtractor::OpenRouter(extractor) => {
-                extractor.extract(prompt).await.map_err(|e| e.into())
-            }
-            ProviderExtractor::Anthropic(extractor) => {
-                extractor.extract(prompt).await.map_err(|e| e.into())
-            }
-            ProviderExtractor::Gemini(extractor) => {
-                extractor.extract(prompt).await.map_err(|e| e.into())
+    /// Execute extraction, returns the value and the token usage of all attempts.
+    /// The output is validated against the schema of T and near misses are repaired locally, remaining
+    /// schema violations are sent back to the model at most `repair_attempts` times.
+    /// Every call to the model checks the budget, waits for the rate limit and is charged by `meter` once it finishes
+    pub async fn extract(
+        &self,
+        prompt: &str,
+        repair_attempts: u32,
+        calls: &TurnRateLimit,
+        meter: &UsageMeter<'_>,
+    ) -> Result<(T, Option<TokenUsage>)> {
+        if let ProviderExtractor::Fixture(extractor) = self {
+            return extractor.extract(prompt, repair_attempts, calls, meter).await;
+        }
+
+        let mut usage: Option<TokenUsage> = None;
+        let mut attempt_prompt = prompt.to_string();
+        let mut attempt = 0;
+        loop {
+            let output = self
+                .submitted_output(&attempt_prompt, &mut usage, calls, meter)
+                .await?;
+
+            let previous_output = serde_json::to_string_pretty(&output).unwrap_or_default();
+            let conformed = conform::<T>(output);
+            if !conformed.repairs.is_empty() {
+                println!(
+                    "   🩹 Repaired structured output locally: {}",
+                    conformed.repairs.join("; ")
+                );
+            }
+            let violations = match conformed.value {
+                Ok(value) => return Ok((value, usage)),
+                Err(violations) => violations,
+            };
+            if attempt == repair_attempts {
+                return Err(anyhow!(
+                    "Structured output does not match the schema after {} repair attempts: {}",
+                    repair_attempts,
+                    violations.join("; ")
+                ));
+            }
+            eprintln!(
+                "   🔁 Structured output does not match the schema, asking for a repair: {}",
+                violations.join("; ")
+            );
+            attempt_prompt = repair_prompt(prompt, &previous_output, &violations);
+            attempt += 1;
+        }
+    }
+
+    /// Run the extraction until the model calls the submit tool, at most `SUBMIT_RETRIES` more times, as rig's
+    /// extractor does. Returns the unvalidated JSON output, the usage of every call is added to `usage`
+    async fn submitted_output(
+        &self,
+        prompt: &str,
+        usage: &mut Option<TokenUsage>,
+        calls: &TurnRateLimit,
+        meter: &UsageMeter<'_>,
+    ) -> Result<Value> {
+        for attempt in 0..=SUBMIT_RETRIES {
+            meter.ensure_available()?;
+            calls.acquire(prompt).await;
+            let result = self.extract_output(prompt).await;
+            calls.settle(result.as_ref().ok().and_then(|(_, reported)| reported.as_ref()));
+            let (output, reported) = result?;
+            let output_text = output.as_ref().map(Value::to_string).unwrap_or_default();
+            meter.record(prompt, &output_text, reported.as_ref());
+            if let Some(reported) = reported {
+                usage.get_or_insert_with(|| TokenUsage::new(0, 0)).add(&reported);
+            }
+            if let Some(output) = output {
+                return Ok(output);
+            }
+            if attempt < SUBMIT_RETRIES {
+                eprintln!("   🔁 The model did not call the submit tool, retrying the extraction");
             }
+        }
+        Err(anyhow!("No data extracted, the model did not call the submit tool"))
+    }
+
+    /// Run the extraction once, returns the unvalidated JSON output, None if the model did not submit any
+    async fn extract_output(&self, prompt: &str) -> Result<(Option<Value>, Option<TokenUsage>)> {
+        match self {
+            ProviderExtractor::OpenAI(extractor) => extract_with_usage(extractor, prompt).await,
+            ProviderExtractor::Moonshot(extractor) => extract_with_usage(extractor, prompt).await,
+            ProviderExtractor::DeepSeek(extractor) => extract_with_usage(extractor, prompt).await,
+            ProviderExtractor::Mistral(extractor) => extract_with_usage(extractor, prompt).await,
+            ProviderExtractor::OpenRouter(extractor) => extract_with_usage(extractor, prompt).await,
+            ProviderExtractor::Anthropic(extractor) => extract_with_usage(extractor, prompt).await,
+            ProviderExtractor::Gemini(extractor) => extract_with_usage(extractor, prompt).await,
             ProviderExtractor::Ollama(extractor) => {
-                extractor.extract(prompt).await.map_err(|e| e.into())
+                let (output, usage) = extractor.extract(prompt).await?;
+                Ok((Some(output), usage))
             }
+            ProviderExtractor::Fixture(_) => Err(anyhow!("Fixture extractors replay validated values")),
         }
     }
 }
+
+/// Run an extractor once, keeping the token usage of the completion. The output is None if the model did
+/// not call the submit tool
+///
+/// Same as one attempt of `Extractor::extract` without validation, which does not expose the usage
+async fn extract_with_usage<M, T>(
+    extractor: &Extractor<M, T>,
+    prompt: &str,
+) -> Result<(Option<Value>, Option<TokenUsage>)>
+where
+    M: CompletionModel,
+    T: JsonSchema + for<'a> Deserialize<'a> + Serialize + Send + Sync + 'static,
+{
+    let response = extractor
+        .get_inner()
+        .await
+        .completion(prompt, vec![])
+        .await?
+        .send()
+        .await?;
+    let usage = TokenUsage::from_reported(&response.usage);
+
+    let arguments = response
+        .choice
+        .into_iter()
+        .find_map(|content| match content {
+            AssistantContent::ToolCall(call) if call.function.name == "submit" => {
+                Some(call.function.arguments)
+            }
+            _ => None,
+        });
+
+    Ok((arguments, usage))
+}
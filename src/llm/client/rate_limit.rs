@@ -0,0 +1,342 @@
+//! Rate limiting - Token buckets shared by every LLM call of a run, and backoff between retries
+
+use std::collections::BTreeMap;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use regex::Regex;
+use rig::agent::{CancelSignal, PromptHook};
+use rig::completion::{CompletionModel, CompletionResponse, Message};
+use serde::Serialize;
+
+use super::types::TokenUsage;
+use super::utils::estimate_token_usage;
+use crate::config::{LLMConfig, RateLimitConfig};
+
+/// Longest pause honored from a provider's retry hint
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(600);
+
+static RETRY_AFTER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"retry[-_ ]?after\W{0,4}(\d+(?:\.\d+)?)|try again in (\d+(?:\.\d+)?)\s*(ms|s)|retrydelay\W{0,4}(\d+(?:\.\d+)?)s"#)
+        .unwrap()
+});
+
+/// Throttling of the LLM calls of a run
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ThrottleStats {
+    /// Calls held back by the configured rate limits or a provider's retry hint
+    pub throttled_calls: usize,
+    /// Time spent waiting for the rate limits (seconds)
+    pub throttled_seconds: f64,
+    /// Rate limit errors returned by providers
+    pub rate_limit_errors: usize,
+    /// Retried calls, for any error
+    pub retries: usize,
+    /// Time spent in backoff between retries (seconds)
+    pub backoff_seconds: f64,
+}
+
+/// One token bucket, refilled continuously up to its per-minute capacity
+#[derive(Debug)]
+struct Limit {
+    capacity: f64,
+    available: f64,
+    per_second: f64,
+    updated: Instant,
+}
+
+impl Limit {
+    fn new(per_minute: u32, now: Instant) -> Self {
+        let capacity = per_minute.max(1) as f64;
+        Self {
+            capacity,
+            available: capacity,
+            per_second: capacity / 60.0,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.available = (self.available + elapsed * self.per_second).min(self.capacity);
+        self.updated = now;
+    }
+
+    /// Time until `amount` is available, a single request never needs more than the capacity
+    fn wait_for(&self, amount: f64) -> Duration {
+        let missing = amount.min(self.capacity) - self.available;
+        if missing <= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(missing / self.per_second)
+        }
+    }
+}
+
+/// Limits of one provider
+#[derive(Debug, Default)]
+struct ProviderLimits {
+    requests: Option<Limit>,
+    tokens: Option<Limit>,
+    /// Calls are held until then after the provider asked to slow down
+    paused_until: Option<Instant>,
+}
+
+/// Rate limiter of a run, shared by every clone of the LLM client
+pub struct RateLimiter {
+    limits: Mutex<BTreeMap<String, ProviderLimits>>,
+    config: BTreeMap<String, RateLimitConfig>,
+    stats: Mutex<ThrottleStats>,
+}
+
+impl RateLimiter {
+    pub fn new(llm: &LLMConfig) -> Self {
+        Self {
+            limits: Mutex::new(BTreeMap::new()),
+            config: llm.rate_limits.clone(),
+            stats: Mutex::new(ThrottleStats::default()),
+        }
+    }
+
+    /// Whether any provider has a configured limit
+    pub fn is_limited(&self) -> bool {
+        self.config
+            .values()
+            .any(|limit| limit.requests_per_minute.is_some() || limit.tokens_per_minute.is_some())
+    }
+
+    pub fn stats(&self) -> ThrottleStats {
+        self.stats.lock().unwrap().clone()
+    }
+
+    /// Wait until the provider's limits allow a request of about `estimated_tokens`, then take them
+    pub async fn acquire(&self, provider: &str, estimated_tokens: usize) {
+        let mut waited = Duration::ZERO;
+        loop {
+            let wait = self.try_acquire(provider, estimated_tokens, Instant::now());
+            if wait.is_zero() {
+                break;
+            }
+            tokio::time::sleep(wait).await;
+            waited += wait;
+        }
+        if !waited.is_zero() {
+            let mut stats = self.stats.lock().unwrap();
+            stats.throttled_calls += 1;
+            stats.throttled_seconds += waited.as_secs_f64();
+        }
+    }
+
+    /// Correct the tokens taken for a finished call once its real usage is known
+    pub fn settle(&self, provider: &str, estimated_tokens: usize, actual_tokens: usize) {
+        let mut limits = self.limits.lock().unwrap();
+        if let Some(tokens) = limits.get_mut(provider).and_then(|limits| limits.tokens.as_mut()) {
+            tokens.available = (tokens.available + estimated_tokens as f64 - actual_tokens as f64)
+                .min(tokens.capacity);
+        }
+    }
+
+    /// Delay before retrying a failed call; a provider's retry hint pauses every call to it
+    pub fn backoff(&self, provider: &str, err: &anyhow::Error, attempt: u32, llm: &LLMConfig) -> Duration {
+        let message = err.to_string();
+        let mut stats = self.stats.lock().unwrap();
+        stats.retries += 1;
+
+        let retry_after = if is_rate_limit_error(&message) {
+            stats.rate_limit_errors += 1;
+            retry_after_hint(&message).map(|delay| delay.min(MAX_RETRY_AFTER))
+        } else {
+            None
+        };
+        let delay = match retry_after {
+            Some(delay) => {
+                let until = Instant::now() + delay;
+                let mut limits = self.limits.lock().unwrap();
+                let paused_until = &mut limits.entry(provider.to_string()).or_default().paused_until;
+                *paused_until = Some(paused_until.map_or(until, |current| current.max(until)));
+                delay
+            }
+            None => backoff_delay(attempt, llm.retry_delay_ms, llm.max_retry_delay_ms, jitter()),
+        };
+        stats.backoff_seconds += delay.as_secs_f64();
+        delay
+    }
+
+    /// Take the request and tokens if available, otherwise return how long to wait
+    fn try_acquire(&self, provider: &str, estimated_tokens: usize, now: Instant) -> Duration {
+        let mut limits = self.limits.lock().unwrap();
+        let limits = limits.entry(provider.to_string()).or_insert_with(|| {
+            let config = self.config.get(provider).cloned().unwrap_or_default();
+            ProviderLimits {
+                requests: config.requests_per_minute.map(|rpm| Limit::new(rpm, now)),
+                tokens: config.tokens_per_minute.map(|tpm| Limit::new(tpm, now)),
+                paused_until: None,
+            }
+        });
+
+        if let Some(until) = limits.paused_until.filter(|until| *until > now) {
+            return until - now;
+        }
+        for limit in [&mut limits.requests, &mut limits.tokens].into_iter().flatten() {
+            limit.refill(now);
+        }
+        let wait = [
+            limits.requests.as_ref().map(|limit| limit.wait_for(1.0)),
+            limits.tokens.as_ref().map(|limit| limit.wait_for(estimated_tokens as f64)),
+        ]
+        .into_iter()
+        .flatten()
+        .max()
+        .unwrap_or_default();
+        if wait.is_zero() {
+            if let Some(requests) = &mut limits.requests {
+                requests.available -= 1.0;
+            }
+            if let Some(tokens) = &mut limits.tokens {
+                tokens.available -= estimated_tokens as f64;
+            }
+        }
+        wait
+    }
+}
+
+/// Takes the rate limit before every model turn of a multi-turn conversation. Each turn is a request of its
+/// own and resends the conversation so far
+#[derive(Clone)]
+pub struct TurnRateLimit {
+    limiter: Arc<RateLimiter>,
+    provider: String,
+    /// Estimated tokens of the system prompt, sent with every turn
+    system_tokens: usize,
+    /// Tokens taken for the turn in flight, settled once its usage is known
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl TurnRateLimit {
+    pub fn new(limiter: Arc<RateLimiter>, provider: &str, system_prompt: &str) -> Self {
+        Self {
+            limiter,
+            provider: provider.to_string(),
+            system_tokens: estimate_token_usage(system_prompt, "").input_tokens,
+            in_flight: Arc::default(),
+        }
+    }
+
+    /// Wait for the rate limit of one request sending `conversation` with the system prompt
+    pub async fn acquire(&self, conversation: &str) {
+        let estimated_tokens = self.system_tokens + estimate_token_usage(conversation, "").input_tokens;
+        self.limiter.acquire(&self.provider, estimated_tokens).await;
+        self.in_flight.store(estimated_tokens, Ordering::SeqCst);
+    }
+
+    /// Settle the request in flight with its reported usage
+    pub fn settle(&self, usage: Option<&TokenUsage>) {
+        let estimated_tokens = self.in_flight.swap(0, Ordering::SeqCst);
+        if let Some(usage) = usage {
+            self.limiter.settle(&self.provider, estimated_tokens, usage.total_tokens);
+        }
+    }
+}
+
+impl<M: CompletionModel> PromptHook<M> for TurnRateLimit {
+    async fn on_completion_call(&self, prompt: &Message, history: &[Message], _cancel_sig: CancelSignal) {
+        self.acquire(&serde_json::to_string(&(history, prompt)).unwrap_or_default())
+            .await;
+    }
+
+    async fn on_completion_response(
+        &self,
+        _prompt: &Message,
+        response: &CompletionResponse<M::Response>,
+        _cancel_sig: CancelSignal,
+    ) {
+        self.settle(TokenUsage::from_reported(&response.usage).as_ref());
+    }
+}
+
+/// Whether an error message reports a provider rate limit
+pub fn is_rate_limit_error(message: &str) -> bool {
+    let message = message.to_lowercase();
+    ["429", "rate limit", "rate_limit", "ratelimit", "too many requests", "resource_exhausted"]
+        .iter()
+        .any(|pattern| message.contains(pattern))
+}
+
+/// Retry delay asked for by a provider. The HTTP client does not expose response headers,
+/// so this reads the `Retry-After` value or retry hint that providers repeat in the error body
+pub fn retry_after_hint(message: &str) -> Option<Duration> {
+    let message = message.to_lowercase();
+    let captures = RETRY_AFTER.captures(&message)?;
+    if let Some(seconds) = captures.get(1).or(captures.get(4)) {
+        return seconds.as_str().parse().ok().map(Duration::from_secs_f64);
+    }
+    let value: f64 = captures.get(2)?.as_str().parse().ok()?;
+    match captures.get(3).map(|unit| unit.as_str()) {
+        Some("ms") => Some(Duration::from_secs_f64(value / 1000.0)),
+        _ => Some(Duration::from_secs_f64(value)),
+    }
+}
+
+/// Exponential backoff from `base_ms`, capped at `max_ms`, with `jitter` in [0, 1) spreading it over its upper half
+pub fn backoff_delay(attempt: u32, base_ms: u64, max_ms: u64, jitter: f64) -> Duration {
+    let exponential = base_ms.saturating_mul(1u64 << attempt.saturating_sub(1).min(20));
+    let capped = exponential.min(max_ms.max(base_ms)) as f64;
+    Duration::from_millis((capped * (0.5 + jitter / 2.0)) as u64)
+}
+
+/// Random value in [0, 1)
+fn jitter() -> f64 {
+    let random = RandomState::new().build_hasher().finish();
+    (random >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_after_hint() {
+        assert_eq!(
+            retry_after_hint("429 Too Many Requests, Retry-After: 12"),
+            Some(Duration::from_secs(12))
+        );
+        assert_eq!(
+            retry_after_hint("Rate limit reached for gpt-4o. Please try again in 850ms."),
+            Some(Duration::from_millis(850))
+        );
+        assert_eq!(
+            retry_after_hint(r#"{"status": "RESOURCE_EXHAUSTED", "retryDelay": "13s"}"#),
+            Some(Duration::from_secs(13))
+        );
+        assert_eq!(retry_after_hint("connection reset by peer"), None);
+    }
+
+    #[test]
+    fn test_backoff_grows_and_is_capped() {
+        assert_eq!(backoff_delay(1, 1000, 60_000, 0.0), Duration::from_millis(500));
+        assert_eq!(backoff_delay(3, 1000, 60_000, 0.0), Duration::from_millis(2000));
+        assert_eq!(backoff_delay(30, 1000, 60_000, 0.99), Duration::from_millis(59_700));
+    }
+
+    #[test]
+    fn test_requests_per_minute_limit() {
+        let mut llm = LLMConfig::default();
+        llm.rate_limits.insert(
+            "openai".to_string(),
+            RateLimitConfig {
+                requests_per_minute: Some(2),
+                tokens_per_minute: None,
+            },
+        );
+        let limiter = RateLimiter::new(&llm);
+        let now = Instant::now();
+        assert!(limiter.try_acquire("openai", 100, now).is_zero());
+        assert!(limiter.try_acquire("openai", 100, now).is_zero());
+        assert_eq!(limiter.try_acquire("openai", 100, now), Duration::from_secs(30));
+        assert!(limiter.try_acquire("openai", 100, now + Duration::from_secs(30)).is_zero());
+        assert!(limiter.try_acquire("deepseek", 100, now).is_zero());
+    }
+}
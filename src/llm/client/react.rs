@@ -2,6 +2,8 @@
 
 use rig::completion::Message;
 
+use super::types::TokenUsage;
+
 /// ReAct mode configuration
 #[derive(Debug, Clone)]
 pub struct ReActConfig {
@@ -39,6 +41,8 @@ pub struct ReActResponse {
     pub tool_calls_history: Vec<String>,
     /// Chat history (only included when max depth reached)
     pub chat_history: Option<Vec<Message>>,
+    /// Token usage reported by the provider, None if it reported none
+    pub token_usage: Option<TokenUsage>,
 }
 
 impl ReActResponse {
@@ -56,12 +60,13 @@ impl ReActResponse {
             stopped_by_max_depth,
             tool_calls_history,
             chat_history,
+            token_usage: None,
         }
     }
 
     /// Create successfully completed response
-    pub fn success(content: String, iterations_used: usize) -> Self {
-        Self::new(content, iterations_used, false, Vec::new(), None)
+    pub fn success(content: String, iterations_used: usize, tool_calls_history: Vec<String>) -> Self {
+        Self::new(content, iterations_used, false, tool_calls_history, None)
     }
 
     /// Create response stopped by max depth (with chat history)
@@ -6,18 +6,22 @@ use rig::completion::{AssistantContent, Message, PromptError};
 use crate::i18n::TargetLanguage;
 use super::react::{ReActConfig, ReActResponse};
 use super::providers::ProviderAgent;
+use super::rate_limit::TurnRateLimit;
+use super::trace::ConversationTrace;
 
 /// ReAct executor
 pub struct ReActExecutor;
 
 impl ReActExecutor {
-    /// Execute ReAct loop logic
+    /// Execute ReAct loop logic, the messages of the conversation are appended to `history`
     pub async fn execute(
         agent: &ProviderAgent,
         user_prompt: &str,
         config: &ReActConfig,
         target_language: &TargetLanguage,
         model_name: &str,
+        history: &mut Vec<Message>,
+        turns: &TurnRateLimit,
     ) -> Result<ReActResponse> {
         if config.verbose {
             println!(
@@ -28,13 +32,19 @@ impl ReActExecutor {
 
         let mut tool_calls_history = Vec::new();
 
-        match agent.multi_turn(user_prompt, config.max_iterations).await {
-            Ok(response) => {
+        match agent.multi_turn(user_prompt, config.max_iterations, history, Some(turns)).await {
+            Ok((response, token_usage)) => {
                 if config.verbose {
                     println!("   ✅ ReAct Agent task completed");
                 }
 
-                Ok(ReActResponse::success(response, config.max_iterations))
+                let mut response = ReActResponse::success(
+                    response,
+                    ConversationTrace::iterations(history).max(1),
+                    Self::extract_tool_calls(history),
+                );
+                response.token_usage = token_usage;
+                Ok(response)
             }
             Err(PromptError::MaxDepthError {
                 max_depth,
@@ -77,8 +87,6 @@ impl ReActExecutor {
 
     /// Extract partial result from chat history
     fn extract_partial_result(chat_history: &[Message]) -> (String, Vec<String>) {
-        let mut tool_calls = Vec::new();
-
         // Try to extract the last assistant response from chat history
         let last_assistant_message = chat_history
             .iter()
@@ -111,7 +119,12 @@ impl ReActExecutor {
                 "ReAct Agent interrupted due to reaching max iterations, unable to obtain complete response.".to_string()
             });
 
-        // Extract tool call information from chat history
+        (last_assistant_message, Self::extract_tool_calls(chat_history))
+    }
+
+    /// Extract tool call information from chat history
+    fn extract_tool_calls(chat_history: &[Message]) -> Vec<String> {
+        let mut tool_calls = Vec::new();
         for msg in chat_history {
             if let Message::Assistant { content, .. } = msg {
                 for c in content.iter() {
@@ -124,7 +137,6 @@ impl ReActExecutor {
                 }
             }
         }
-
-        (last_assistant_message, tool_calls)
+        tool_calls
     }
 }
@@ -0,0 +1,466 @@
+//! Structured output - Validates extracted JSON against the schema of the target type, repairs near misses
+//! locally and describes the remaining schema violations for a repair prompt
+
+use std::sync::LazyLock;
+
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+/// JSON code block regex pattern
+static JSON_CODE_BLOCK_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"```(?:json)?\s*(\{[\s\S]*?\})\s*```").unwrap());
+
+/// Comma before a closing bracket
+static TRAILING_COMMA_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r",(\s*[}\]])").unwrap());
+
+/// Schema accepting anything, for references that cannot be resolved
+static ANY: Value = Value::Bool(true);
+
+/// Characters of the previous output quoted in a repair prompt
+const MAX_PREVIOUS_OUTPUT_CHARS: usize = 8000;
+
+/// Outcome of conforming an output to a schema
+pub struct Conformed<T> {
+    pub value: Result<T, Vec<String>>,
+    /// Local repairs applied, for logging
+    pub repairs: Vec<String>,
+}
+
+/// Validate an output against the schema of T and deserialize it. Near misses (enum spelling, stringified
+/// numbers or objects, null optional fields) are repaired first, the remaining schema violations are returned
+pub fn conform<T>(mut output: Value) -> Conformed<T>
+where
+    T: JsonSchema + for<'a> Deserialize<'a>,
+{
+    let schema = schemars::schema_for!(T);
+    let mut conformer = Conformer {
+        root: schema.as_value(),
+        violations: Vec::new(),
+        repairs: Vec::new(),
+    };
+    conformer.conform(schema.as_value(), &mut output, "$");
+    let Conformer {
+        violations, repairs, ..
+    } = conformer;
+
+    let value = if violations.is_empty() {
+        serde_json::from_value(output).map_err(|e| vec![format!("$: {}", e)])
+    } else {
+        Err(violations)
+    };
+    Conformed { value, repairs }
+}
+
+/// Find the JSON object in a text reply: the whole reply, a code block or the first balanced object,
+/// tolerating trailing commas
+pub fn parse_json(text: &str) -> Option<Value> {
+    let candidates = [
+        Some(text.trim().to_string()),
+        JSON_CODE_BLOCK_REGEX
+            .captures(text)
+            .and_then(|cap| cap.get(1))
+            .map(|m| m.as_str().to_string()),
+        first_json_object(text),
+    ];
+    candidates.into_iter().flatten().find_map(|candidate| {
+        serde_json::from_str::<Value>(&candidate)
+            .or_else(|_| serde_json::from_str(&TRAILING_COMMA_REGEX.replace_all(&candidate, "$1")))
+            .ok()
+    })
+}
+
+/// Prompt asking the model to fix the listed schema violations of its previous output
+pub fn repair_prompt(prompt: &str, previous_output: &str, violations: &[String]) -> String {
+    let mut previous: String = previous_output.chars().take(MAX_PREVIOUS_OUTPUT_CHARS).collect();
+    if previous.len() < previous_output.len() {
+        previous.push_str("\n...");
+    }
+    let mut repair = format!(
+        "{}\n\n**Your previous result did not match the required schema.**\n\nPrevious result:\n```json\n{}\n```\n\nSchema violations:\n",
+        prompt, previous
+    );
+    for violation in violations {
+        repair.push_str(&format!("- {}\n", violation));
+    }
+    repair.push_str("\nReturn the complete result again with exactly these violations fixed, keep everything else unchanged.\n");
+    repair
+}
+
+/// First balanced JSON object of a text
+fn first_json_object(text: &str) -> Option<String> {
+    let start = text.find('{')?;
+    let mut depth = 0;
+    for (i, c) in text[start..].char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(text[start..start + i + 1].to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Lowercase alphanumerics, so that `Specific Feature`, `specific_feature` and `specificFeature` compare equal
+fn normalize(text: &str) -> String {
+    text.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_i64() || n.is_u64() => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn allows_null(schema: &Value) -> bool {
+    match schema.get("type") {
+        Some(Value::String(t)) => t == "null",
+        Some(Value::Array(types)) => types.iter().any(|t| t == "null"),
+        _ => ["anyOf", "oneOf"].iter().any(|key| {
+            schema
+                .get(*key)
+                .and_then(Value::as_array)
+                .is_some_and(|branches| branches.iter().any(|branch| branch.get("type") == Some(&Value::from("null"))))
+        }),
+    }
+}
+
+struct Conformer<'a> {
+    root: &'a Value,
+    violations: Vec<String>,
+    repairs: Vec<String>,
+}
+
+impl<'a> Conformer<'a> {
+    fn resolve(&self, schema: &'a Value) -> &'a Value {
+        match schema.get("$ref").and_then(Value::as_str) {
+            Some(reference) => reference
+                .strip_prefix('#')
+                .and_then(|pointer| self.root.pointer(pointer))
+                .unwrap_or(&ANY),
+            None => schema,
+        }
+    }
+
+    fn conform(&mut self, schema: &'a Value, value: &mut Value, path: &str) {
+        let schema = self.resolve(schema);
+        if !schema.is_object() {
+            return;
+        }
+
+        if let Some(variants) = Self::enum_variants(schema) {
+            self.conform_enum(&variants, value, path);
+            return;
+        }
+        for key in ["anyOf", "oneOf"] {
+            if let Some(branches) = schema.get(key).and_then(Value::as_array) {
+                self.conform_branches(branches, value, path);
+                return;
+            }
+        }
+        if let Some(all) = schema.get("allOf").and_then(Value::as_array) {
+            for branch in all {
+                self.conform(branch, value, path);
+            }
+        }
+
+        if let Some(expected) = schema.get("type") {
+            let expected: Vec<&str> = match expected {
+                Value::String(t) => vec![t.as_str()],
+                Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+                _ => Vec::new(),
+            };
+            if !expected.is_empty() && !self.conform_type(&expected, value, path) {
+                return;
+            }
+        }
+
+        match value {
+            Value::Object(object) => self.conform_object(schema, object, path),
+            Value::Array(items) => {
+                if let Some(item_schema) = schema.get("items") {
+                    for (i, item) in items.iter_mut().enumerate() {
+                        self.conform(item_schema, item, &format!("{}[{}]", path, i));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Allowed values of a string enum with their descriptions, from `enum` or from `oneOf` constants
+    fn enum_variants(schema: &Value) -> Option<Vec<(Value, Option<&str>)>> {
+        if let Some(values) = schema.get("enum").and_then(Value::as_array) {
+            return Some(values.iter().map(|v| (v.clone(), None)).collect());
+        }
+        if let Some(value) = schema.get("const") {
+            return Some(vec![(value.clone(), None)]);
+        }
+        let branches = schema
+            .get("oneOf")
+            .or_else(|| schema.get("anyOf"))
+            .and_then(Value::as_array)?;
+        branches
+            .iter()
+            .map(|branch| {
+                let description = branch.get("description").and_then(Value::as_str);
+                match (branch.get("const"), branch.get("enum").and_then(Value::as_array)) {
+                    (Some(value), _) => Some((value.clone(), description)),
+                    (None, Some(values)) if values.len() == 1 => Some((values[0].clone(), description)),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    fn conform_enum(&mut self, variants: &[(Value, Option<&str>)], value: &mut Value, path: &str) {
+        if variants.iter().any(|(allowed, _)| allowed == value) {
+            return;
+        }
+        let allowed: Vec<String> = variants.iter().map(|(v, _)| v.to_string()).collect();
+        if let Value::String(given) = value {
+            let given_normalized = normalize(given);
+            let exact: Vec<&Value> = variants
+                .iter()
+                .filter(|(allowed, description)| {
+                    allowed.as_str().is_some_and(|a| normalize(a) == given_normalized)
+                        || description.is_some_and(|d| normalize(d) == given_normalized)
+                })
+                .map(|(allowed, _)| allowed)
+                .collect();
+            let partial: Vec<&Value> = variants
+                .iter()
+                .filter(|(allowed, _)| {
+                    allowed.as_str().is_some_and(|a| {
+                        let a = normalize(a);
+                        !a.is_empty() && !given_normalized.is_empty()
+                            && (a.contains(&given_normalized) || given_normalized.contains(&a))
+                    })
+                })
+                .map(|(allowed, _)| allowed)
+                .collect();
+            let matched = match (exact.as_slice(), partial.as_slice()) {
+                ([single], _) | ([], [single]) => Some((*single).clone()),
+                _ => None,
+            };
+            if let Some(matched) = matched {
+                self.repairs.push(format!("{}: \"{}\" -> {}", path, given, matched));
+                *value = matched;
+                return;
+            }
+        }
+        self.violations.push(format!(
+            "{}: {} is not one of the allowed values {}",
+            path,
+            value,
+            allowed.join(", ")
+        ));
+    }
+
+    /// Keep the branch the value matches, or the one needing the fewest fixes
+    fn conform_branches(&mut self, branches: &'a [Value], value: &mut Value, path: &str) {
+        let mut best: Option<(Value, Vec<String>, Vec<String>)> = None;
+        for branch in branches {
+            let mut candidate = value.clone();
+            let mut conformer = Conformer {
+                root: self.root,
+                violations: Vec::new(),
+                repairs: Vec::new(),
+            };
+            conformer.conform(branch, &mut candidate, path);
+            let better = best
+                .as_ref()
+                .is_none_or(|(_, violations, repairs)| {
+                    (conformer.violations.len(), conformer.repairs.len()) < (violations.len(), repairs.len())
+                });
+            if better {
+                best = Some((candidate, conformer.violations, conformer.repairs));
+            }
+        }
+        if let Some((candidate, violations, repairs)) = best {
+            *value = candidate;
+            self.violations.extend(violations);
+            self.repairs.extend(repairs);
+        }
+    }
+
+    /// Check the type of a value, converting obvious near misses. False if the value has the wrong type
+    fn conform_type(&mut self, expected: &[&str], value: &mut Value, path: &str) -> bool {
+        let actual = type_name(value);
+        if expected.contains(&actual) || (actual == "integer" && expected.contains(&"number")) {
+            return true;
+        }
+
+        let converted = expected.iter().find_map(|expected| match (*expected, &*value) {
+            ("integer", Value::String(s)) => s.trim().parse::<i64>().ok().map(Value::from),
+            ("integer", Value::Number(n)) => n
+                .as_f64()
+                .filter(|f| f.fract() == 0.0)
+                .map(|f| Value::from(f as i64)),
+            ("number", Value::String(s)) => s.trim().parse::<f64>().ok().map(Value::from),
+            ("boolean", Value::String(s)) => match s.trim().to_lowercase().as_str() {
+                "true" | "yes" => Some(Value::Bool(true)),
+                "false" | "no" => Some(Value::Bool(false)),
+                _ => None,
+            },
+            ("string", Value::Number(n)) => Some(Value::String(n.to_string())),
+            ("string", Value::Bool(b)) => Some(Value::String(b.to_string())),
+            ("object" | "array", Value::String(s)) => {
+                parse_json(s).filter(|parsed| type_name(parsed) == *expected)
+            }
+            ("array", v) if !v.is_null() => Some(Value::Array(vec![v.clone()])),
+            _ => None,
+        });
+        match converted {
+            Some(converted) => {
+                self.repairs
+                    .push(format!("{}: {} converted to {}", path, actual, type_name(&converted)));
+                *value = converted;
+                true
+            }
+            None => {
+                self.violations.push(format!(
+                    "{}: expected {}, got {}",
+                    path,
+                    expected.join(" or "),
+                    actual
+                ));
+                false
+            }
+        }
+    }
+
+    fn conform_object(&mut self, schema: &'a Value, object: &mut Map<String, Value>, path: &str) {
+        let Some(properties) = schema.get("properties").and_then(Value::as_object) else {
+            return;
+        };
+        let required: Vec<&str> = schema
+            .get("required")
+            .and_then(Value::as_array)
+            .map(|required| required.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default();
+
+        // Misspelled keys, e.g. `codePurpose` for `code_purpose`
+        let unknown: Vec<String> = object
+            .keys()
+            .filter(|key| !properties.contains_key(*key))
+            .cloned()
+            .collect();
+        for key in unknown {
+            let matches: Vec<&String> = properties
+                .keys()
+                .filter(|property| !object.contains_key(*property) && normalize(property) == normalize(&key))
+                .collect();
+            if let [property] = matches.as_slice()
+                && let Some(value) = object.remove(&key)
+            {
+                self.repairs.push(format!("{}: key {} renamed to {}", path, key, property));
+                object.insert((*property).clone(), value);
+            }
+        }
+
+        for (name, property_schema) in properties {
+            let field_path = format!("{}.{}", path, name);
+            let is_required = required.contains(&name.as_str());
+            let property_schema = self.resolve(property_schema);
+            match object.get_mut(name) {
+                Some(Value::Null) if !is_required && !allows_null(property_schema) => {
+                    object.remove(name);
+                    self.repairs.push(format!("{}: null dropped for the default", field_path));
+                }
+                Some(value) => self.conform(property_schema, value, &field_path),
+                None if is_required => match property_schema.get("default") {
+                    Some(default) => {
+                        object.insert(name.clone(), default.clone());
+                        self.repairs.push(format!("{}: missing, default used", field_path));
+                    }
+                    None => self.violations.push(format!("{}: missing required field", field_path)),
+                },
+                None => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use schemars::JsonSchema;
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[derive(Debug, Deserialize, JsonSchema, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum Purpose {
+        /// Project execution entry
+        Entry,
+        /// Code module for implementing specific logical functionality
+        SpecificFeature,
+    }
+
+    #[derive(Debug, Deserialize, JsonSchema, PartialEq)]
+    struct Report {
+        purpose: Purpose,
+        importance: f64,
+        lines: u32,
+        #[serde(default)]
+        tags: Vec<String>,
+        note: Option<String>,
+        children: Option<Vec<Report>>,
+    }
+
+    #[test]
+    fn test_near_misses_are_repaired_locally() {
+        let output = json!({
+            "purpose": "Specific_Feature",
+            "importance": "0.8",
+            "lines": 12.0,
+            "tags": null,
+            "note": null,
+            "children": "[{\"purpose\": \"Project execution entry\", \"importance\": 1, \"lines\": 3}]"
+        });
+        let conformed = conform::<Report>(output);
+
+        let report = conformed.value.unwrap();
+        assert_eq!(report.purpose, Purpose::SpecificFeature);
+        assert_eq!(report.importance, 0.8);
+        assert_eq!(report.lines, 12);
+        assert!(report.tags.is_empty());
+        assert_eq!(report.children.unwrap()[0].purpose, Purpose::Entry);
+        assert_eq!(conformed.repairs.len(), 6);
+    }
+
+    #[test]
+    fn test_remaining_violations_are_reported() {
+        let output = json!({ "purpose": "database", "importance": "high", "children": [{}] });
+        let violations = conform::<Report>(output).value.unwrap_err();
+
+        assert!(violations.iter().any(|v| v.starts_with("$.purpose: \"database\" is not one of")));
+        assert!(violations.contains(&"$.importance: expected number, got string".to_string()));
+        assert!(violations.contains(&"$.lines: missing required field".to_string()));
+        assert!(violations.contains(&"$.children[0].purpose: missing required field".to_string()));
+    }
+
+    #[test]
+    fn test_parse_json_from_text_reply() {
+        let reply = "Here is the result:\n```json\n{\"lines\": [1, 2,], \"note\": \"x\",}\n```";
+        assert_eq!(parse_json(reply), Some(json!({ "lines": [1, 2], "note": "x" })));
+        assert_eq!(parse_json("no json here"), None);
+    }
+}
@@ -0,0 +1,373 @@
+//! Conversation traces - Full transcripts of the multi-turn agent conversations, stored under `.litho/traces/<agent>/`
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Utc};
+use rig::completion::{AssistantContent, Message};
+use rig::message::{ToolResultContent, UserContent};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::config::Config;
+
+use super::types::TokenUsage;
+
+/// Characters of a message shown by `trace show` unless the full transcript is asked for
+const PREVIEW_CHARS: usize = 400;
+
+/// A tool call made during a conversation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCallTrace {
+    /// Model turn that made the call, from 1
+    pub iteration: usize,
+    pub tool: String,
+    pub arguments: Value,
+    /// Tool output, None if the conversation stopped before the tool returned
+    pub result: Option<String>,
+}
+
+/// How a conversation ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TraceOutcome {
+    Completed,
+    /// Stopped by the iteration limit
+    MaxDepth,
+    Failed,
+}
+
+/// Trace of one multi-turn conversation, one JSONL line per conversation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationTrace {
+    pub timestamp: DateTime<Utc>,
+    pub agent: String,
+    pub provider: String,
+    pub model: String,
+    pub max_iterations: usize,
+    /// Model turns used
+    pub iterations: usize,
+    pub outcome: TraceOutcome,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<TokenUsage>,
+    pub system_prompt: String,
+    pub tool_calls: Vec<ToolCallTrace>,
+    /// Every message of the conversation, starting with the user prompt
+    pub messages: Vec<Message>,
+}
+
+impl ConversationTrace {
+    /// Number of model turns of a transcript
+    pub fn iterations(messages: &[Message]) -> usize {
+        messages
+            .iter()
+            .filter(|message| matches!(message, Message::Assistant { .. }))
+            .count()
+    }
+
+    /// Tool calls of a transcript, paired with the results returned to the model
+    pub fn tool_calls(messages: &[Message]) -> Vec<ToolCallTrace> {
+        let mut calls: Vec<(String, ToolCallTrace)> = Vec::new();
+        let mut iteration = 0;
+        for message in messages {
+            match message {
+                Message::Assistant { content, .. } => {
+                    iteration += 1;
+                    for item in content.iter() {
+                        if let AssistantContent::ToolCall(call) = item {
+                            calls.push((
+                                call.id.clone(),
+                                ToolCallTrace {
+                                    iteration,
+                                    tool: call.function.name.clone(),
+                                    arguments: call.function.arguments.clone(),
+                                    result: None,
+                                },
+                            ));
+                        }
+                    }
+                }
+                Message::User { content } => {
+                    for item in content.iter() {
+                        if let UserContent::ToolResult(result) = item
+                            && let Some((_, call)) = calls
+                                .iter_mut()
+                                .find(|(id, call)| *id == result.id && call.result.is_none())
+                        {
+                            call.result = Some(
+                                result
+                                    .content
+                                    .iter()
+                                    .map(tool_result_text)
+                                    .collect::<Vec<_>>()
+                                    .join("\n"),
+                            );
+                        }
+                    }
+                }
+            }
+        }
+        calls.into_iter().map(|(_, call)| call).collect()
+    }
+}
+
+/// Appends the conversations of a run to `<agent>/<session>.jsonl` in the traces directory
+pub struct TraceWriter {
+    dir: PathBuf,
+    /// Start time of the process, names the trace files of this run
+    session: String,
+    /// Conversations of the same agent run concurrently, their lines must not interleave
+    lock: Mutex<()>,
+}
+
+impl TraceWriter {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            dir: Self::traces_dir(config),
+            session: Utc::now().format("%Y%m%d-%H%M%S").to_string(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Directory holding the traces of all agents
+    pub fn traces_dir(config: &Config) -> PathBuf {
+        config.internal_path.join("traces")
+    }
+
+    pub fn write(&self, trace: &ConversationTrace) -> Result<()> {
+        let dir = self.dir.join(agent_dir_name(&trace.agent));
+        let mut line = serde_json::to_string(trace)?;
+        line.push('\n');
+
+        let _guard = self.lock.lock().unwrap();
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("{}.jsonl", self.session));
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .with_context(|| format!("Failed to write trace {}", path.display()))
+    }
+}
+
+/// Trace files of an agent, oldest first
+pub fn trace_files(config: &Config, agent: &str) -> Result<Vec<PathBuf>> {
+    let dir = TraceWriter::traces_dir(config).join(agent_dir_name(agent));
+    if !dir.is_dir() {
+        let known = fs::read_dir(TraceWriter::traces_dir(config))
+            .map(|entries| {
+                let mut names: Vec<_> = entries
+                    .flatten()
+                    .map(|entry| entry.file_name().to_string_lossy().to_string())
+                    .collect();
+                names.sort();
+                names.join(", ")
+            })
+            .unwrap_or_default();
+        return Err(anyhow!(
+            "No traces recorded for agent {} in {} (agents with traces: {})",
+            agent,
+            dir.display(),
+            if known.is_empty() { "none" } else { &known }
+        ));
+    }
+    let mut files: Vec<_> = fs::read_dir(&dir)?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "jsonl"))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Read the conversations of a trace file
+pub fn read_traces(path: &Path) -> Result<Vec<ConversationTrace>> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read trace {}", path.display()))?;
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .enumerate()
+        .map(|(index, line)| {
+            serde_json::from_str(line)
+                .with_context(|| format!("Invalid trace on line {} of {}", index + 1, path.display()))
+        })
+        .collect()
+}
+
+/// Print the traces of an agent, from its latest run unless `all` is set
+pub fn show(config: &Config, agent: &str, all: bool, full: bool) -> Result<()> {
+    let files = trace_files(config, agent)?;
+    let files = if all {
+        &files[..]
+    } else {
+        &files[files.len().saturating_sub(1)..]
+    };
+
+    let mut conversations = 0;
+    let mut tool_calls = 0;
+    let mut iterations = 0;
+    let mut max_depth = 0;
+    for path in files {
+        println!("🧵 {}", path.display());
+        for trace in read_traces(path)? {
+            conversations += 1;
+            tool_calls += trace.tool_calls.len();
+            iterations += trace.iterations;
+            if trace.outcome == TraceOutcome::MaxDepth {
+                max_depth += 1;
+            }
+            print_trace(conversations, &trace, full);
+        }
+    }
+
+    if conversations == 0 {
+        println!("No conversations recorded for agent {}", agent);
+    } else {
+        println!(
+            "📊 {} conversations, {} tool calls, {:.1} iterations on average, {} stopped by max iterations",
+            conversations,
+            tool_calls,
+            iterations as f64 / conversations as f64,
+            max_depth
+        );
+    }
+    Ok(())
+}
+
+fn print_trace(number: usize, trace: &ConversationTrace, full: bool) {
+    println!();
+    println!(
+        "── #{} {} · {}/{} · {}/{} iterations · {:?}",
+        number,
+        trace.timestamp.format("%Y-%m-%d %H:%M:%S"),
+        trace.provider,
+        trace.model,
+        trace.iterations,
+        trace.max_iterations,
+        trace.outcome
+    );
+    if let Some(error) = &trace.error {
+        println!("   ❌ {}", error);
+    }
+    if full {
+        println!("   ⚙️  system: {}", trace.system_prompt);
+    }
+    for message in &trace.messages {
+        match message {
+            Message::User { content } => {
+                for item in content.iter() {
+                    match item {
+                        UserContent::Text(text) => {
+                            println!("   👤 user: {}", preview(&text.text, full))
+                        }
+                        UserContent::ToolResult(result) => {
+                            let text = result
+                                .content
+                                .iter()
+                                .map(tool_result_text)
+                                .collect::<Vec<_>>()
+                                .join("\n");
+                            println!(
+                                "   📎 result ({} chars): {}",
+                                text.chars().count(),
+                                preview(&text, full)
+                            )
+                        }
+                        _ => println!("   👤 user: [media]"),
+                    }
+                }
+            }
+            Message::Assistant { content, .. } => {
+                for item in content.iter() {
+                    match item {
+                        AssistantContent::Text(text) => {
+                            println!("   🤖 assistant: {}", preview(&text.text, full))
+                        }
+                        AssistantContent::ToolCall(call) => println!(
+                            "   🛠️  {}({})",
+                            call.function.name, call.function.arguments
+                        ),
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn tool_result_text(content: &ToolResultContent) -> String {
+    match content {
+        ToolResultContent::Text(text) => text.text.clone(),
+        ToolResultContent::Image(_) => "[image]".to_string(),
+    }
+}
+
+fn preview(text: &str, full: bool) -> String {
+    if full || text.chars().count() <= PREVIEW_CHARS {
+        return text.to_string();
+    }
+    let head: String = text.chars().take(PREVIEW_CHARS).collect();
+    format!("{}… ({} chars)", head.replace('\n', " "), text.chars().count())
+}
+
+/// Directory name of an agent, agent names of custom agents may hold any character
+fn agent_dir_name(agent: &str) -> String {
+    let name: String = agent
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    if name.is_empty() {
+        "unnamed".to_string()
+    } else {
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rig::OneOrMany;
+    use serde_json::json;
+
+    #[test]
+    fn test_tool_calls_are_paired_with_results() {
+        let messages = vec![
+            Message::user("Describe the project"),
+            Message::Assistant {
+                id: None,
+                content: OneOrMany::many(vec![
+                    AssistantContent::tool_call("a", "file_reader", json!({"file_path": "src/main.rs"})),
+                    AssistantContent::tool_call("b", "file_explorer", json!({"path": "src"})),
+                ])
+                .unwrap(),
+            },
+            Message::tool_result("b", "main.rs lib.rs"),
+            Message::tool_result("a", "fn main() {}"),
+            Message::Assistant {
+                id: None,
+                content: OneOrMany::one(AssistantContent::tool_call(
+                    "c",
+                    "file_reader",
+                    json!({"file_path": "src/lib.rs"}),
+                )),
+            },
+        ];
+
+        assert_eq!(ConversationTrace::iterations(&messages), 2);
+        let calls = ConversationTrace::tool_calls(&messages);
+        assert_eq!(calls.len(), 3);
+        assert_eq!(calls[0].tool, "file_reader");
+        assert_eq!(calls[0].result.as_deref(), Some("fn main() {}"));
+        assert_eq!(calls[1].result.as_deref(), Some("main.rs lib.rs"));
+        assert_eq!(calls[2].iteration, 2);
+        assert_eq!(calls[2].result, None);
+        assert_eq!(agent_dir_name("My Agent/v2"), "My_Agent_v2");
+    }
+}
@@ -20,11 +20,42 @@ impl TokenUsage {
         }
     }
 
-    /// Estimate cost (based on different model pricing)
-    pub fn estimate_cost(&self, _model_name: &str) -> f64 {
-        let (input_cost_per_1k, output_cost_per_1k) = (0.00025, 0.002);
+    /// Usage reported by a provider, None when the provider reported none
+    pub fn from_reported(usage: &rig::completion::Usage) -> Option<Self> {
+        if usage.input_tokens == 0 && usage.output_tokens == 0 {
+            return None;
+        }
+        Some(Self::new(
+            usage.input_tokens as usize,
+            usage.output_tokens as usize,
+        ))
+    }
+
+    /// Add the usage of another call
+    pub fn add(&mut self, other: &TokenUsage) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.total_tokens += other.total_tokens;
+    }
+}
+
+/// Reply of an LLM call
+#[derive(Debug, Clone)]
+pub struct LLMResponse<T> {
+    pub content: T,
+    /// Token usage reported by the provider, None if it reported none
+    pub usage: Option<TokenUsage>,
+    /// Model that produced the reply
+    pub model: String,
+    /// Provider that produced the reply
+    pub provider: String,
+}
 
-        (self.input_tokens as f64 / 1000.0) * input_cost_per_1k
-            + (self.output_tokens as f64 / 1000.0) * output_cost_per_1k
+impl<T> LLMResponse<T> {
+    /// Reported usage, or an estimate from the prompt and reply text if the provider reported none
+    pub fn usage_or_estimate(&self, input_text: &str, output_text: &str) -> TokenUsage {
+        self.usage
+            .clone()
+            .unwrap_or_else(|| super::utils::estimate_token_usage(input_text, output_text))
     }
 }
@@ -0,0 +1,195 @@
+//! Token usage ledger - Usage of the LLM calls of a run, per agent and per provider and model
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+
+use crate::llm::client::budget::BudgetTracker;
+use crate::llm::client::pricing::PricingTable;
+use crate::llm::client::types::TokenUsage;
+use crate::llm::client::utils::estimate_token_usage;
+
+/// Summed usage of a group of LLM calls
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UsageTotals {
+    pub calls: usize,
+    pub input_tokens: usize,
+    pub output_tokens: usize,
+    pub cost: f64,
+    /// Calls whose usage was estimated because the provider reported none
+    pub estimated_calls: usize,
+}
+
+impl UsageTotals {
+    fn add(&mut self, other: &UsageTotals) {
+        self.calls += other.calls;
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cost += other.cost;
+        self.estimated_calls += other.estimated_calls;
+    }
+}
+
+/// Provider and model of a group of calls
+pub type ModelKey = (String, String);
+
+/// Records the usage of every LLM call, keyed by agent, provider and model
+pub struct UsageLedger {
+    pricing: Arc<PricingTable>,
+    entries: Mutex<BTreeMap<(String, ModelKey), UsageTotals>>,
+}
+
+impl UsageLedger {
+    pub fn new(pricing: Arc<PricingTable>) -> Self {
+        Self {
+            pricing,
+            entries: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Record a finished call, `reported` tells whether the usage came from the provider
+    pub fn record(&self, agent: &str, provider: &str, model: &str, usage: &TokenUsage, reported: bool) {
+        let mut entries = self.entries.lock().unwrap();
+        let totals = entries
+            .entry((agent.to_string(), (provider.to_string(), model.to_string())))
+            .or_default();
+        totals.calls += 1;
+        totals.input_tokens += usage.input_tokens;
+        totals.output_tokens += usage.output_tokens;
+        totals.cost += self.pricing.cost(usage, provider, model);
+        if !reported {
+            totals.estimated_calls += 1;
+        }
+    }
+
+    pub fn by_agent(&self) -> BTreeMap<String, UsageTotals> {
+        self.group_by(|(agent, _)| agent)
+    }
+
+    pub fn by_model(&self) -> BTreeMap<ModelKey, UsageTotals> {
+        self.group_by(|(_, model)| model)
+    }
+
+    pub fn total(&self) -> UsageTotals {
+        let mut total = UsageTotals::default();
+        for totals in self.entries.lock().unwrap().values() {
+            total.add(totals);
+        }
+        total
+    }
+
+    fn group_by<K: Ord + Clone>(
+        &self,
+        key: impl Fn(&(String, ModelKey)) -> &K,
+    ) -> BTreeMap<K, UsageTotals> {
+        let mut groups: BTreeMap<K, UsageTotals> = BTreeMap::new();
+        for (entry_key, totals) in self.entries.lock().unwrap().iter() {
+            groups.entry(key(entry_key).clone()).or_default().add(totals);
+        }
+        groups
+    }
+}
+
+/// Charges every call of an agent to the run's budget and usage ledger as soon as it finishes,
+/// so that the calls of failed and retried attempts are billed too
+pub struct UsageMeter<'a> {
+    pub budget: &'a BudgetTracker,
+    pub ledger: &'a UsageLedger,
+    /// Ledger entry of the calls, the cache scope of the agent
+    pub agent: &'a str,
+    pub provider: &'a str,
+    pub model: &'a str,
+    /// System prompt sent with every call, part of the estimate when the provider reports no usage
+    pub system_prompt: &'a str,
+}
+
+impl UsageMeter<'_> {
+    /// Fail with `BudgetExhausted` if any limit of the budget is reached
+    pub fn ensure_available(&self) -> anyhow::Result<()> {
+        self.budget.ensure_available()
+    }
+
+    /// Record a finished call, its usage is estimated from the prompt and the output if the provider reported none
+    pub fn record(&self, prompt: &str, output: &str, reported: Option<&TokenUsage>) {
+        let usage = reported
+            .cloned()
+            .unwrap_or_else(|| estimate_token_usage(&format!("{} {}", self.system_prompt, prompt), output));
+        self.budget.record(&usage, self.provider, self.model);
+        self.ledger
+            .record(self.agent, self.provider, self.model, &usage, reported.is_some());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::LLMConfig;
+
+    #[test]
+    fn test_usage_is_summed_per_agent_and_model() {
+        let ledger = UsageLedger::new(Arc::new(PricingTable::new(&LLMConfig::default())));
+        ledger.record("ai_code_insight", "openai", "gpt-4o-mini", &TokenUsage::new(1000, 200), true);
+        ledger.record("ai_code_insight", "openai", "gpt-4o-mini", &TokenUsage::new(3000, 400), false);
+        ledger.record("studies_research", "openai", "gpt-4o", &TokenUsage::new(2000, 1000), true);
+        ledger.record("studies_research", "ollama", "gpt-4o", &TokenUsage::new(500, 100), true);
+
+        let total = ledger.total();
+        assert_eq!(total.calls, 4);
+        assert_eq!(total.input_tokens, 6500);
+        assert_eq!(total.output_tokens, 1700);
+        assert_eq!(total.estimated_calls, 1);
+
+        let by_agent = ledger.by_agent();
+        assert_eq!(by_agent["ai_code_insight"].calls, 2);
+        assert_eq!(by_agent["ai_code_insight"].input_tokens, 4000);
+        assert_eq!(by_agent["ai_code_insight"].estimated_calls, 1);
+        assert_eq!(by_agent["studies_research"].output_tokens, 1100);
+
+        // The same model name served by another provider is a group of its own, priced by that provider
+        let by_model = ledger.by_model();
+        let openai = &by_model[&("openai".to_string(), "gpt-4o".to_string())];
+        let ollama = &by_model[&("ollama".to_string(), "gpt-4o".to_string())];
+        assert_eq!(openai.calls, 1);
+        assert!(openai.cost > 0.0);
+        assert_eq!(ollama.cost, 0.0);
+        assert!((total.cost - by_agent.values().map(|totals| totals.cost).sum::<f64>()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_meter_charges_each_call_as_it_finishes() {
+        use crate::config::BudgetConfig;
+        use crate::llm::client::budget::BudgetExhausted;
+
+        let pricing = Arc::new(PricingTable::new(&LLMConfig::default()));
+        let budget = BudgetTracker::new(
+            BudgetConfig {
+                max_input_tokens: Some(150),
+                ..Default::default()
+            },
+            pricing.clone(),
+        );
+        let ledger = UsageLedger::new(pricing);
+        let meter = UsageMeter {
+            budget: &budget,
+            ledger: &ledger,
+            agent: "studies_research",
+            provider: "openai",
+            model: "gpt-4o-mini",
+            system_prompt: "system",
+        };
+
+        // A failed attempt is charged before the repair, the next check sees it
+        meter.record("prompt", "invalid", Some(&TokenUsage::new(100, 10)));
+        assert!(meter.ensure_available().is_ok());
+        meter.record("prompt", "repaired", None);
+        meter.record("prompt", "repaired", Some(&TokenUsage::new(100, 10)));
+        assert!(BudgetExhausted::is(&meter.ensure_available().unwrap_err()));
+
+        let total = ledger.total();
+        assert_eq!(total.calls, 3);
+        assert_eq!(total.estimated_calls, 1);
+        assert!(total.input_tokens > 200);
+        assert_eq!(budget.usage().input_tokens, total.input_tokens);
+    }
+}
@@ -6,16 +6,23 @@ use std::sync::LazyLock;
 
 static TOKEN_ESTIMATOR: LazyLock<TokenEstimator> = LazyLock::new(|| TokenEstimator::new());
 
+/// Prompt size in bytes from which the powerful model is chosen, unless configured
+pub const DEFAULT_POWERFUL_THRESHOLD_BYTES: usize = 32 * 1024;
+
 pub fn evaluate_befitting_model(
     llm_config: &LLMConfig,
     system_prompt: &str,
     user_prompt: &str,
 ) -> (String, Option<String>) {
-    if system_prompt.len() + user_prompt.len() <= 32 * 1024 {
-        return (
-            llm_config.model_efficient.clone(),
-            Some(llm_config.model_powerful.clone()),
-        );
+    let threshold = llm_config
+        .routing
+        .powerful_threshold_bytes
+        .unwrap_or(DEFAULT_POWERFUL_THRESHOLD_BYTES);
+    if system_prompt.len() + user_prompt.len() <= threshold {
+        // A route pinning one model has nothing to fall back to
+        let fallover_model = (llm_config.model_powerful != llm_config.model_efficient)
+            .then(|| llm_config.model_powerful.clone());
+        return (llm_config.model_efficient.clone(), fallover_model);
     }
     return (llm_config.model_powerful.clone(), None);
 }
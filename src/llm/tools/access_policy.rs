@@ -0,0 +1,222 @@
+//! Access policy of the agent tools - Confines them to the project root and to the files the analysis covers
+
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+
+use glob::{MatchOptions, Pattern};
+
+use crate::config::Config;
+use crate::utils::ignore_matcher::{Exclusion, IgnoreMatcher};
+
+/// Why a path was denied
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccessDenied {
+    pub path: String,
+    pub reason: String,
+}
+
+impl fmt::Display for AccessDenied {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Access denied to {}: {}", self.path, self.reason)
+    }
+}
+
+/// Paths the agent tools may access, shared by every tool reading the project
+#[derive(Debug, Clone)]
+pub struct AccessPolicy {
+    config: Config,
+    /// Canonical project root, symbolic links are resolved against it
+    root: PathBuf,
+    denied_files: Vec<Pattern>,
+    ignore_matcher: IgnoreMatcher,
+}
+
+impl AccessPolicy {
+    pub fn new(config: &Config) -> Self {
+        let root = config
+            .project_path
+            .canonicalize()
+            .unwrap_or_else(|_| config.project_path.clone());
+        let denied_files = config
+            .tool_denied_files
+            .iter()
+            .filter_map(|pattern| match Pattern::new(pattern) {
+                Ok(pattern) => Some(pattern),
+                Err(e) => {
+                    eprintln!("⚠️  Ignoring invalid tool_denied_files pattern {}: {}", pattern, e);
+                    None
+                }
+            })
+            .collect();
+        Self {
+            config: config.clone(),
+            root,
+            denied_files,
+            ignore_matcher: IgnoreMatcher::new(config),
+        }
+    }
+
+    /// Resolve a path given by the model relative to the project root, denials are logged.
+    /// The returned path is below `project_path` as configured, not canonicalized
+    pub fn resolve(&self, tool: &str, requested: &str) -> Result<PathBuf, AccessDenied> {
+        let result = self.confine(requested).and_then(|relative| {
+            let path = self.config.project_path.join(&relative);
+            self.check(&path, &relative).map(|_| path)
+        });
+        if let Err(denied) = &result {
+            eprintln!("   🚫 {} denied: {}", tool, denied);
+        }
+        result
+    }
+
+    /// Whether a path found while walking the project may be shown to the model
+    pub fn allows(&self, path: &Path) -> bool {
+        let relative = path
+            .strip_prefix(&self.config.project_path)
+            .unwrap_or(path)
+            .to_path_buf();
+        self.check(path, &relative).is_ok()
+    }
+
+    /// Lexically normalized path relative to the project root, denied if it leaves the root
+    fn confine(&self, requested: &str) -> Result<PathBuf, AccessDenied> {
+        let denied = |reason: &str| AccessDenied {
+            path: requested.to_string(),
+            reason: reason.to_string(),
+        };
+        let requested_path = Path::new(requested);
+        let requested_path = if requested_path.is_absolute() {
+            requested_path
+                .strip_prefix(&self.root)
+                .or_else(|_| requested_path.strip_prefix(&self.config.project_path))
+                .map_err(|_| denied("absolute path outside the project root"))?
+        } else {
+            requested_path
+        };
+
+        let mut relative = PathBuf::new();
+        for component in requested_path.components() {
+            match component {
+                Component::Normal(part) => relative.push(part),
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    if !relative.pop() {
+                        return Err(denied("path leaves the project root"));
+                    }
+                }
+                Component::RootDir | Component::Prefix(_) => {
+                    return Err(denied("absolute path outside the project root"));
+                }
+            }
+        }
+        Ok(relative)
+    }
+
+    /// Check symbolic links and the exclusion rules of a path inside the project
+    fn check(&self, path: &Path, relative: &Path) -> Result<(), AccessDenied> {
+        let denied = |reason: String| AccessDenied {
+            path: relative.to_string_lossy().to_string(),
+            reason,
+        };
+
+        if path.exists() {
+            let canonical = path
+                .canonicalize()
+                .map_err(|e| denied(format!("cannot be resolved: {}", e)))?;
+            if !canonical.starts_with(&self.root) {
+                return Err(denied("symbolic link pointing outside the project root".to_string()));
+            }
+        }
+
+        let is_dir = path.is_dir();
+        match self.ignore_matcher.exclusion(relative, is_dir) {
+            Some(Exclusion::Directory(dir)) => {
+                return Err(denied(format!(
+                    "directory {} is excluded from the analysis",
+                    dir.display()
+                )));
+            }
+            Some(Exclusion::File) => {
+                return Err(denied("file is excluded from the analysis".to_string()));
+            }
+            None => {}
+        }
+
+        if is_dir {
+            return Ok(());
+        }
+        let options = MatchOptions {
+            case_sensitive: false,
+            require_literal_separator: false,
+            require_literal_leading_dot: false,
+        };
+        let file_name = relative
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if let Some(pattern) = self.denied_files.iter().find(|pattern| {
+            pattern.matches_with(&file_name, options)
+                || pattern.matches_with(&relative.to_string_lossy(), options)
+        }) {
+            return Err(denied(format!("matches denied pattern {}", pattern)));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paths_are_confined_to_the_project() {
+        let config = Config {
+            project_path: PathBuf::from("/srv/project"),
+            ..Default::default()
+        };
+        let policy = AccessPolicy::new(&config);
+
+        assert_eq!(policy.confine("./src/../src/main.rs").unwrap(), PathBuf::from("src/main.rs"));
+        assert_eq!(policy.confine("/srv/project/src").unwrap(), PathBuf::from("src"));
+        assert!(policy.confine("../../etc/passwd").is_err());
+        assert!(policy.confine("src/../../etc/passwd").is_err());
+        assert!(policy.confine("/etc/passwd").is_err());
+
+        assert!(policy.resolve("file_reader", "src/.env").is_err());
+        assert!(policy.resolve("file_reader", "certs/server.pem").is_err());
+        assert!(policy.resolve("file_reader", "node_modules/lib/index.js").is_err());
+        assert_eq!(
+            policy.resolve("file_reader", "src/main.rs").unwrap(),
+            PathBuf::from("/srv/project/src/main.rs")
+        );
+    }
+
+    #[test]
+    fn test_tools_see_the_files_the_scanner_sees() {
+        let root = std::env::temp_dir().join(format!("litho-access-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join(".gitignore"), "generated/\n").unwrap();
+        std::fs::write(root.join("src/.gitignore"), "generated.rs\n").unwrap();
+        for path in ["src/main.rs", "src/generated.rs"] {
+            std::fs::write(root.join(path), "").unwrap();
+        }
+        let config = Config {
+            project_path: root.clone(),
+            ..Default::default()
+        };
+        let policy = AccessPolicy::new(&config);
+        let matcher = IgnoreMatcher::new(&config);
+
+        for path in ["src/main.rs", "src/generated.rs", "generated/bundle.js"] {
+            assert_eq!(
+                policy.resolve("file_reader", path).is_ok(),
+                !matcher.is_excluded(Path::new(path), false),
+                "{}",
+                path
+            );
+        }
+        assert!(policy.resolve("file_reader", "src/generated.rs").is_err());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
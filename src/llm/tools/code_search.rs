@@ -0,0 +1,332 @@
+//! Code search tool
+
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::Result;
+use glob::{MatchOptions, Pattern};
+use regex::{Regex, RegexBuilder};
+use rig::tool::Tool;
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+use super::access_policy::AccessPolicy;
+use crate::config::Config;
+
+/// Default and maximum number of returned matches
+const DEFAULT_MAX_RESULTS: usize = 30;
+const MAX_RESULTS: usize = 200;
+/// Default and maximum number of context lines around a match
+const DEFAULT_CONTEXT_LINES: usize = 2;
+const MAX_CONTEXT_LINES: usize = 10;
+/// Longer lines are cut, minified sources would flood the conversation
+const MAX_LINE_CHARS: usize = 300;
+
+/// Code search tool
+#[derive(Debug, Clone)]
+pub struct AgentToolCodeSearch {
+    config: Config,
+    policy: AccessPolicy,
+}
+
+/// Code search parameters
+#[derive(Debug, Deserialize)]
+pub struct CodeSearchArgs {
+    pub pattern: String,
+    /// Treat the pattern as a regular expression instead of a literal string
+    pub regex: Option<bool>,
+    pub case_sensitive: Option<bool>,
+    pub glob: Option<String>,
+    pub path: Option<String>,
+    pub context_lines: Option<usize>,
+    pub max_results: Option<usize>,
+}
+
+/// A matching line
+#[derive(Debug, Serialize)]
+pub struct CodeSearchMatch {
+    /// `file:line`, relative to the project root
+    pub location: String,
+    pub line: String,
+    /// Surrounding lines prefixed with their line number, the matching line marked with `>`
+    pub context: String,
+}
+
+/// Code search result
+#[derive(Debug, Serialize, Default)]
+pub struct CodeSearchResult {
+    pub matches: Vec<CodeSearchMatch>,
+    pub files_searched: usize,
+    pub files_matched: usize,
+    /// Whether matches were dropped because of `max_results`
+    pub truncated: bool,
+    pub insights: Vec<String>,
+}
+
+impl AgentToolCodeSearch {
+    pub fn new(config: Config) -> Self {
+        let policy = AccessPolicy::new(&config);
+        Self { config, policy }
+    }
+
+    async fn search(&self, args: &CodeSearchArgs) -> Result<CodeSearchResult> {
+        let pattern = if args.regex.unwrap_or(false) {
+            args.pattern.clone()
+        } else {
+            regex::escape(&args.pattern)
+        };
+        let matcher = RegexBuilder::new(&pattern)
+            .case_insensitive(!args.case_sensitive.unwrap_or(false))
+            .build()
+            .map_err(|e| anyhow::anyhow!("Invalid search pattern {}: {}", args.pattern, e))?;
+        let glob = args
+            .glob
+            .as_deref()
+            .map(Pattern::new)
+            .transpose()
+            .map_err(|e| anyhow::anyhow!("Invalid glob: {}", e))?;
+
+        let search_path = match &args.path {
+            Some(path) => match self.policy.resolve(Self::NAME, path) {
+                Ok(path) => path,
+                Err(denied) => {
+                    return Ok(CodeSearchResult {
+                        insights: vec![denied.to_string()],
+                        ..Default::default()
+                    });
+                }
+            },
+            None => self.config.project_path.clone(),
+        };
+        if !search_path.exists() {
+            return Ok(CodeSearchResult {
+                insights: vec![format!("Search path does not exist: {}", search_path.display())],
+                ..Default::default()
+            });
+        }
+
+        let context_lines = args
+            .context_lines
+            .unwrap_or(DEFAULT_CONTEXT_LINES)
+            .min(MAX_CONTEXT_LINES);
+        let max_results = args
+            .max_results
+            .unwrap_or(DEFAULT_MAX_RESULTS)
+            .clamp(1, MAX_RESULTS);
+        let mut result = CodeSearchResult::default();
+
+        let entries = WalkDir::new(&search_path)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|entry| {
+                entry.depth() == 0 || !entry.file_type().is_dir() || self.policy.allows(entry.path())
+            });
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if !entry.file_type().is_file() || !self.policy.allows(path) {
+                continue;
+            }
+            let relative_path = path
+                .strip_prefix(&self.config.project_path)
+                .unwrap_or(path)
+                .to_string_lossy()
+                .replace('\\', "/");
+            if let Some(glob) = &glob
+                && !glob_matches(glob, &relative_path)
+            {
+                continue;
+            }
+            // Files that are not valid UTF-8 are not source code
+            let Ok(content) = std::fs::read_to_string(path) else {
+                continue;
+            };
+
+            result.files_searched += 1;
+            let found = search_lines(&matcher, &content, &relative_path, context_lines);
+            if found.is_empty() {
+                continue;
+            }
+            result.files_matched += 1;
+            let room = max_results - result.matches.len();
+            if found.len() > room {
+                result.truncated = true;
+            }
+            result.matches.extend(found.into_iter().take(room));
+            if result.matches.len() >= max_results {
+                break;
+            }
+        }
+
+        result.insights = vec![
+            format!("Search pattern: {}", args.pattern),
+            format!("Search path: {}", search_path.display()),
+            format!(
+                "Found {} matches in {} of {} searched files",
+                result.matches.len(),
+                result.files_matched,
+                result.files_searched
+            ),
+        ];
+        if result.truncated {
+            result.insights.push(format!(
+                "Stopped after {} matches, narrow the search with glob or path to see the rest",
+                max_results
+            ));
+        }
+        Ok(result)
+    }
+}
+
+/// Matching lines of a file with their context
+fn search_lines(
+    matcher: &Regex,
+    content: &str,
+    relative_path: &str,
+    context_lines: usize,
+) -> Vec<CodeSearchMatch> {
+    let lines: Vec<&str> = content.lines().collect();
+    lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| matcher.is_match(line))
+        .map(|(index, line)| {
+            let start = index.saturating_sub(context_lines);
+            let end = (index + context_lines + 1).min(lines.len());
+            let context = (start..end)
+                .map(|i| {
+                    let marker = if i == index { '>' } else { ' ' };
+                    format!("{}{:>5}| {}", marker, i + 1, truncate_line(lines[i]))
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            CodeSearchMatch {
+                location: format!("{}:{}", relative_path, index + 1),
+                line: truncate_line(line.trim()),
+                context,
+            }
+        })
+        .collect()
+}
+
+/// A glob without `/` matches the file name, otherwise the path relative to the project root
+fn glob_matches(glob: &Pattern, relative_path: &str) -> bool {
+    let options = MatchOptions {
+        case_sensitive: false,
+        require_literal_separator: false,
+        require_literal_leading_dot: false,
+    };
+    if glob.as_str().contains('/') {
+        glob.matches_with(relative_path, options)
+    } else {
+        let file_name = Path::new(relative_path)
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        glob.matches_with(&file_name, options)
+    }
+}
+
+fn truncate_line(line: &str) -> String {
+    if line.chars().count() <= MAX_LINE_CHARS {
+        line.to_string()
+    } else {
+        format!("{}…", line.chars().take(MAX_LINE_CHARS).collect::<String>())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("code search tool error: {0}")]
+pub struct CodeSearchToolError(String);
+
+impl Tool for AgentToolCodeSearch {
+    const NAME: &'static str = "code_search";
+
+    type Error = CodeSearchToolError;
+    type Args = CodeSearchArgs;
+    type Output = CodeSearchResult;
+
+    async fn definition(&self, _prompt: String) -> rig::completion::ToolDefinition {
+        rig::completion::ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: "Search the content of the project's source files for a literal string or regular expression, like grep. Returns each matching line as file:line with its surrounding lines. Use it to find where a symbol is defined or used instead of reading files one by one."
+                .to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "pattern": {
+                        "type": "string",
+                        "description": "Text to search for, e.g. a function, type or config key name"
+                    },
+                    "regex": {
+                        "type": "boolean",
+                        "description": "Treat pattern as a regular expression (default false, literal search)"
+                    },
+                    "case_sensitive": {
+                        "type": "boolean",
+                        "description": "Match case (default false)"
+                    },
+                    "glob": {
+                        "type": "string",
+                        "description": "Only search matching files, e.g. '*.rs' for file names or 'src/api/**/*.ts' for paths relative to project root"
+                    },
+                    "path": {
+                        "type": "string",
+                        "description": "Directory to search (relative to project root, default is the whole project)"
+                    },
+                    "context_lines": {
+                        "type": "integer",
+                        "description": "Lines shown before and after each match (default 2, at most 10)"
+                    },
+                    "max_results": {
+                        "type": "integer",
+                        "description": "Maximum number of matches to return (default 30, at most 200)"
+                    }
+                },
+                "required": ["pattern"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        println!("   🔧 tool called...code_search@{:?}", args);
+
+        tokio::time::sleep(Duration::from_secs(1)).await;
+
+        self.search(&args)
+            .await
+            .map_err(|e| CodeSearchToolError(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_lines_with_context() {
+        let content = "fn main() {\n    let config = load();\n    run(config);\n}\n";
+        let matcher = RegexBuilder::new("config")
+            .case_insensitive(true)
+            .build()
+            .unwrap();
+        let matches = search_lines(&matcher, content, "src/main.rs", 1);
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].location, "src/main.rs:2");
+        assert_eq!(matches[0].line, "let config = load();");
+        assert_eq!(
+            matches[0].context,
+            "     1| fn main() {\n>    2|     let config = load();\n     3|     run(config);"
+        );
+        assert_eq!(matches[1].location, "src/main.rs:3");
+    }
+
+    #[test]
+    fn test_glob_matches_file_name_or_path() {
+        let by_name = Pattern::new("*.rs").unwrap();
+        assert!(glob_matches(&by_name, "src/llm/mod.rs"));
+        assert!(!glob_matches(&by_name, "src/main.ts"));
+        let by_path = Pattern::new("src/llm/**/*.rs").unwrap();
+        assert!(glob_matches(&by_path, "src/llm/tools/mod.rs"));
+        assert!(!glob_matches(&by_path, "src/main.rs"));
+    }
+}
@@ -4,18 +4,19 @@ use anyhow::Result;
 use rig::tool::Tool;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 use walkdir::WalkDir;
 
+use super::access_policy::{AccessDenied, AccessPolicy};
 use crate::config::Config;
 use crate::types::FileInfo;
-use crate::utils::file_utils::is_test_file;
 
 /// File exploration tool
 #[derive(Debug, Clone)]
 pub struct AgentToolFileExplorer {
     config: Config,
+    policy: AccessPolicy,
 }
 
 /// File exploration parameters
@@ -40,14 +41,19 @@ pub struct FileExplorerResult {
 
 impl AgentToolFileExplorer {
     pub fn new(config: Config) -> Self {
-        Self { config }
+        let policy = AccessPolicy::new(&config);
+        Self { config, policy }
     }
 
     async fn list_directory(&self, args: &FileExplorerArgs) -> Result<FileExplorerResult> {
-        let target_path = if let Some(path) = &args.path {
-            self.config.project_path.join(path)
-        } else {
-            self.config.project_path.clone()
+        let target_path = match self.resolve(args.path.as_deref()) {
+            Ok(path) => path,
+            Err(denied) => {
+                return Ok(FileExplorerResult {
+                    insights: vec![denied.to_string()],
+                    ..Default::default()
+                });
+            }
         };
 
         if !target_path.exists() {
@@ -65,7 +71,11 @@ impl AgentToolFileExplorer {
 
         if recursive {
             // Recursive traversal, limit depth to 3
-            for entry in WalkDir::new(&target_path).max_depth(3) {
+            let entries = WalkDir::new(&target_path)
+                .max_depth(3)
+                .into_iter()
+                .filter_entry(|entry| entry.depth() == 0 || self.policy.allows(entry.path()));
+            for entry in entries {
                 if files.len() >= max_files {
                     break;
                 }
@@ -73,10 +83,6 @@ impl AgentToolFileExplorer {
                 let entry = entry?;
                 let path = entry.path();
 
-                if self.is_ignored(path) {
-                    continue;
-                }
-
                 if entry.file_type().is_file() {
                     let file_info = self.create_file_info(path)?;
                     if let Some(ext) = &file_info.extension {
@@ -102,7 +108,7 @@ impl AgentToolFileExplorer {
                 let entry = entry?;
                 let path = entry.path();
 
-                if self.is_ignored(&path) {
+                if !self.policy.allows(&path) {
                     continue;
                 }
 
@@ -140,10 +146,14 @@ impl AgentToolFileExplorer {
             .as_ref()
             .ok_or_else(|| anyhow::anyhow!("find_files action requires pattern parameter"))?;
 
-        let search_path = if let Some(path) = &args.path {
-            self.config.project_path.join(path)
-        } else {
-            self.config.project_path.clone()
+        let search_path = match self.resolve(args.path.as_deref()) {
+            Ok(path) => path,
+            Err(denied) => {
+                return Ok(FileExplorerResult {
+                    insights: vec![denied.to_string()],
+                    ..Default::default()
+                });
+            }
         };
 
         if !search_path.exists() {
@@ -158,7 +168,11 @@ impl AgentToolFileExplorer {
         let mut file_types = HashMap::new();
 
         // Use walkdir for recursive search, limit depth to 5
-        for entry in WalkDir::new(&search_path).max_depth(5) {
+        let entries = WalkDir::new(&search_path)
+            .max_depth(5)
+            .into_iter()
+            .filter_entry(|entry| entry.depth() == 0 || self.policy.allows(entry.path()));
+        for entry in entries {
             if files.len() >= max_files {
                 break;
             }
@@ -166,7 +180,7 @@ impl AgentToolFileExplorer {
             let entry = entry?;
             let path = entry.path();
 
-            if !entry.file_type().is_file() || self.is_ignored(path) {
+            if !entry.file_type().is_file() {
                 continue;
             }
 
@@ -203,7 +217,15 @@ impl AgentToolFileExplorer {
             .as_ref()
             .ok_or_else(|| anyhow::anyhow!("get_file_info action requires path parameter"))?;
 
-        let target_path = self.config.project_path.join(file_path);
+        let target_path = match self.resolve(Some(file_path)) {
+            Ok(path) => path,
+            Err(denied) => {
+                return Ok(FileExplorerResult {
+                    insights: vec![denied.to_string()],
+                    ..Default::default()
+                });
+            }
+        };
 
         if !target_path.exists() {
             return Ok(FileExplorerResult {
@@ -219,13 +241,6 @@ impl AgentToolFileExplorer {
             });
         }
 
-        if self.is_ignored(&target_path) {
-            return Ok(FileExplorerResult {
-                insights: vec![format!("File is ignored: {}", target_path.display())],
-                ..Default::default()
-            });
-        }
-
         let file_info = self.create_file_info(&target_path)?;
         let mut file_types = HashMap::new();
         if let Some(ext) = &file_info.extension {
@@ -255,78 +270,12 @@ impl AgentToolFileExplorer {
         })
     }
 
-    fn is_ignored(&self, path: &Path) -> bool {
-        let path_str = path.to_string_lossy().to_lowercase();
-        let file_name = path
-            .file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or("")
-            .to_lowercase();
-
-        // Check excluded directories
-        for excluded_dir in &self.config.excluded_dirs {
-            if path_str.contains(&excluded_dir.to_lowercase()) {
-                return true;
-            }
+    /// Resolve the requested path through the access policy, the project root if none is given
+    fn resolve(&self, path: Option<&str>) -> Result<PathBuf, AccessDenied> {
+        match path {
+            Some(path) => self.policy.resolve(Self::NAME, path),
+            None => Ok(self.config.project_path.clone()),
         }
-
-        // Check excluded files
-        for excluded_file in &self.config.excluded_files {
-            if excluded_file.contains('*') {
-                // Simple wildcard matching
-                let pattern = excluded_file.replace('*', "");
-                if file_name.contains(&pattern.to_lowercase()) {
-                    return true;
-                }
-            } else if file_name == excluded_file.to_lowercase() {
-                return true;
-            }
-        }
-
-        // Check excluded extensions
-        if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
-            if self
-                .config
-                .excluded_extensions
-                .contains(&extension.to_lowercase())
-            {
-                return true;
-            }
-        }
-
-        // Check included extensions (if specified)
-        if !self.config.included_extensions.is_empty() {
-            if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
-                if !self
-                    .config
-                    .included_extensions
-                    .contains(&extension.to_lowercase())
-                {
-                    return true;
-                }
-            } else {
-                return true; // No extension and included list is specified
-            }
-        }
-
-        // Check test files (if not including test files)
-        if !self.config.include_tests && is_test_file(path) {
-            return true;
-        }
-
-        // Check hidden files
-        if !self.config.include_hidden && file_name.starts_with('.') {
-            return true;
-        }
-
-        // Check file size
-        if let Ok(metadata) = std::fs::metadata(path) {
-            if metadata.len() > self.config.max_file_size {
-                return true;
-            }
-        }
-
-        false
     }
 
     fn create_file_info(&self, path: &Path) -> Result<FileInfo> {
@@ -6,12 +6,13 @@ use anyhow::Result;
 use rig::tool::Tool;
 use serde::{Deserialize, Serialize};
 
+use super::access_policy::AccessPolicy;
 use crate::{config::Config, utils::file_utils::is_binary_file_path};
 
 /// File reading tool
 #[derive(Debug, Clone)]
 pub struct AgentToolFileReader {
-    config: Config,
+    policy: AccessPolicy,
 }
 
 /// File reading parameters
@@ -36,12 +37,22 @@ pub struct FileReaderResult {
 
 impl AgentToolFileReader {
     pub fn new(config: Config) -> Self {
-        Self { config }
+        Self {
+            policy: AccessPolicy::new(&config),
+        }
     }
 
     async fn read_file_content(&self, args: &FileReaderArgs) -> Result<FileReaderResult> {
-        let project_root = &self.config.project_path;
-        let file_path = project_root.join(&args.file_path);
+        let file_path = match self.policy.resolve(Self::NAME, &args.file_path) {
+            Ok(file_path) => file_path,
+            Err(denied) => {
+                return Ok(FileReaderResult {
+                    content: denied.to_string(),
+                    file_path: args.file_path.clone(),
+                    ..Default::default()
+                });
+            }
+        };
 
         if !file_path.exists() {
             return Ok(FileReaderResult {
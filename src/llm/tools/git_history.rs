@@ -0,0 +1,200 @@
+//! Git history tool, reads the local git repository of the project
+
+use std::time::Duration;
+
+use anyhow::Result;
+use rig::tool::Tool;
+use serde::{Deserialize, Serialize};
+
+use super::access_policy::AccessPolicy;
+use crate::config::Config;
+use crate::types::git_history::{CoChange, CommitSummary, FileHistory};
+use crate::utils::git_history::{co_changes_of, read_commits, summarize};
+
+/// Commits read for a query, the most recent ones
+const MAX_COMMITS: usize = 300;
+/// Default and maximum number of returned entries
+const DEFAULT_MAX_RESULTS: usize = 15;
+const MAX_RESULTS: usize = 50;
+/// Files listed per commit
+const MAX_FILES_PER_COMMIT: usize = 20;
+
+/// Git history tool
+#[derive(Debug, Clone)]
+pub struct AgentToolGitHistory {
+    config: Config,
+    policy: AccessPolicy,
+}
+
+/// Git history parameters
+#[derive(Debug, Deserialize)]
+pub struct GitHistoryArgs {
+    pub action: String, // "file_history", "recent_commits", "co_changes"
+    pub path: Option<String>,
+    pub max_results: Option<usize>,
+}
+
+/// Git history result
+#[derive(Debug, Serialize, Default)]
+pub struct GitHistoryResult {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<FileHistory>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub commits: Vec<CommitSummary>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub co_changes: Vec<CoChange>,
+    pub notes: Vec<String>,
+}
+
+impl AgentToolGitHistory {
+    pub fn new(config: Config) -> Self {
+        let policy = AccessPolicy::new(&config);
+        Self { config, policy }
+    }
+
+    async fn query(&self, args: &GitHistoryArgs) -> Result<GitHistoryResult> {
+        let path = match args.path.as_deref() {
+            Some(path) => match self.policy.resolve(Self::NAME, path) {
+                Ok(resolved) => Some(
+                    resolved
+                        .strip_prefix(&self.config.project_path)
+                        .unwrap_or(&resolved)
+                        .to_string_lossy()
+                        .replace('\\', "/"),
+                ),
+                Err(denied) => {
+                    return Ok(GitHistoryResult {
+                        notes: vec![denied.to_string()],
+                        ..Default::default()
+                    });
+                }
+            },
+            None => None,
+        }
+        .filter(|path| !path.is_empty());
+        let max_results = args
+            .max_results
+            .unwrap_or(DEFAULT_MAX_RESULTS)
+            .clamp(1, MAX_RESULTS);
+
+        let Some(mut commits) = read_commits(&self.config.project_path, path.as_deref(), MAX_COMMITS)
+        else {
+            return Ok(GitHistoryResult {
+                notes: vec!["The project is not in a git repository, or git is not installed".to_string()],
+                ..Default::default()
+            });
+        };
+        if commits.is_empty() {
+            return Ok(GitHistoryResult {
+                notes: vec![format!("No commits touch {}", path.as_deref().unwrap_or("the project"))],
+                ..Default::default()
+            });
+        }
+
+        let notes = vec![format!(
+            "Read the latest {} commits touching {}",
+            commits.len(),
+            path.as_deref().unwrap_or("the project")
+        )];
+        match args.action.as_str() {
+            "file_history" => {
+                let path = path.ok_or_else(|| anyhow::anyhow!("file_history action requires path parameter"))?;
+                let prefix = format!("{}/", path);
+                let history = summarize(
+                    &commits,
+                    |file| file == path || file.starts_with(&prefix),
+                    max_results,
+                    0,
+                    max_results,
+                );
+                Ok(GitHistoryResult {
+                    files: history.files,
+                    commits: history
+                        .recent_commits
+                        .into_iter()
+                        .map(Self::trim_files)
+                        .collect(),
+                    notes,
+                    ..Default::default()
+                })
+            }
+            "recent_commits" => {
+                commits.truncate(max_results);
+                Ok(GitHistoryResult {
+                    commits: commits.into_iter().map(Self::trim_files).collect(),
+                    notes,
+                    ..Default::default()
+                })
+            }
+            "co_changes" => {
+                let path = path.ok_or_else(|| anyhow::anyhow!("co_changes action requires path parameter"))?;
+                Ok(GitHistoryResult {
+                    co_changes: co_changes_of(&commits, &path, max_results),
+                    notes,
+                    ..Default::default()
+                })
+            }
+            action => Err(anyhow::anyhow!("Unsupported action: {}", action)),
+        }
+    }
+
+    fn trim_files(mut commit: CommitSummary) -> CommitSummary {
+        let total = commit.files.len();
+        if total > MAX_FILES_PER_COMMIT {
+            commit.files.truncate(MAX_FILES_PER_COMMIT);
+            commit
+                .files
+                .push(format!("... and {} more files", total - MAX_FILES_PER_COMMIT));
+        }
+        commit
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("git history tool error: {0}")]
+pub struct GitHistoryToolError(String);
+
+impl Tool for AgentToolGitHistory {
+    const NAME: &'static str = "git_history";
+
+    type Error = GitHistoryToolError;
+    type Args = GitHistoryArgs;
+    type Output = GitHistoryResult;
+
+    async fn definition(&self, _prompt: String) -> rig::completion::ToolDefinition {
+        rig::completion::ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: "Read the project's local git history: commit counts, authors and recent commit messages of a file or directory, the latest commits, or the files most often changed together with a file. Commit messages often explain why a module exists or changed."
+                .to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "action": {
+                        "type": "string",
+                        "enum": ["file_history", "recent_commits", "co_changes"],
+                        "description": "Query to run"
+                    },
+                    "path": {
+                        "type": "string",
+                        "description": "File or directory relative to project root (required for file_history and co_changes, optional filter for recent_commits)"
+                    },
+                    "max_results": {
+                        "type": "integer",
+                        "description": "Maximum number of entries to return (default 15, at most 50)"
+                    }
+                },
+                "required": ["action"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        println!("   🔧 tool called...git_history@{:?}", args);
+
+        tokio::time::sleep(Duration::from_secs(1)).await;
+
+        self.query(&args)
+            .await
+            .map_err(|e| GitHistoryToolError(e.to_string()))
+    }
+}
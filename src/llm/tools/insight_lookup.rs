@@ -0,0 +1,370 @@
+//! Code insight lookup tool, queries the preprocessing results kept in Memory
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use rig::tool::Tool;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::generator::preprocess::memory::{MemoryScope, ScopedKeys};
+use crate::memory::Memory;
+use crate::types::code::{CodeInsight, CodePurpose, Dependency, InterfaceInfo};
+
+/// Default and maximum number of returned entries
+const DEFAULT_MAX_RESULTS: usize = 20;
+const MAX_RESULTS: usize = 100;
+
+/// Code insight lookup tool
+#[derive(Debug, Clone)]
+pub struct AgentToolInsightLookup {
+    /// Memory of the run, None when the client is used outside of a documentation run
+    memory: Option<Arc<RwLock<Memory>>>,
+}
+
+/// Code insight lookup parameters
+#[derive(Debug, Deserialize)]
+pub struct InsightLookupArgs {
+    pub action: String, // "file_insight", "find_interfaces", "file_dependencies", "files_by_purpose"
+    pub file_path: Option<String>,
+    pub name: Option<String>,
+    pub purpose: Option<String>,
+    pub max_results: Option<usize>,
+}
+
+/// An interface and the file declaring it
+#[derive(Debug, Serialize)]
+pub struct InterfaceMatch {
+    pub file_path: String,
+    #[serde(flatten)]
+    pub interface: InterfaceInfo,
+}
+
+/// A dependency between two files of the project, or to an external package
+#[derive(Debug, Serialize)]
+pub struct DependencyMatch {
+    /// "outgoing" for dependencies of the file, "incoming" for files depending on it
+    pub direction: String,
+    pub file_path: String,
+    #[serde(flatten)]
+    pub dependency: Dependency,
+}
+
+/// Short description of a file
+#[derive(Debug, Serialize)]
+pub struct FileSummary {
+    pub file_path: String,
+    pub code_purpose: CodePurpose,
+    pub importance_score: f64,
+    pub description: String,
+}
+
+/// Code insight lookup result
+#[derive(Debug, Serialize, Default)]
+pub struct InsightLookupResult {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub insights: Vec<CodeInsight>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub interfaces: Vec<InterfaceMatch>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<DependencyMatch>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<FileSummary>,
+    pub notes: Vec<String>,
+}
+
+impl InsightLookupResult {
+    fn note(note: impl Into<String>) -> Self {
+        Self {
+            notes: vec![note.into()],
+            ..Default::default()
+        }
+    }
+}
+
+impl AgentToolInsightLookup {
+    pub fn new(memory: Option<Arc<RwLock<Memory>>>) -> Self {
+        Self { memory }
+    }
+
+    async fn lookup(&self, args: &InsightLookupArgs) -> Result<InsightLookupResult> {
+        let insights: Option<Vec<CodeInsight>> = match &self.memory {
+            Some(memory) => memory
+                .read()
+                .await
+                .get_untracked(MemoryScope::PREPROCESS, ScopedKeys::CODE_INSIGHTS),
+            None => None,
+        };
+        let Some(insights) = insights else {
+            return Ok(InsightLookupResult::note(
+                "Code insights are not available yet, preprocessing has not finished",
+            ));
+        };
+        let max_results = args
+            .max_results
+            .unwrap_or(DEFAULT_MAX_RESULTS)
+            .clamp(1, MAX_RESULTS);
+
+        match args.action.as_str() {
+            "file_insight" => {
+                let file_path = required(&args.file_path, "file_insight", "file_path")?;
+                let found: Vec<_> = insights
+                    .into_iter()
+                    .filter(|insight| same_file(&insight.code_dossier.file_path, file_path))
+                    .take(3)
+                    .collect();
+                if found.is_empty() {
+                    return Ok(InsightLookupResult::note(format!(
+                        "No code insight for {}, the file was not analyzed or does not exist",
+                        file_path
+                    )));
+                }
+                Ok(InsightLookupResult {
+                    insights: found,
+                    ..Default::default()
+                })
+            }
+            "find_interfaces" => {
+                let name = required(&args.name, "find_interfaces", "name")?.to_lowercase();
+                let mut interfaces: Vec<_> = insights
+                    .iter()
+                    .flat_map(|insight| {
+                        insight.interfaces.iter().map(|interface| InterfaceMatch {
+                            file_path: display_path(&insight.code_dossier.file_path),
+                            interface: interface.clone(),
+                        })
+                    })
+                    .filter(|found| found.interface.name.to_lowercase().contains(&name))
+                    .collect();
+                // Exact names first
+                interfaces.sort_by_key(|found| found.interface.name.to_lowercase() != name);
+                let total = interfaces.len();
+                interfaces.truncate(max_results);
+                Ok(InsightLookupResult {
+                    notes: vec![format!("Found {} interfaces, showing {}", total, interfaces.len())],
+                    interfaces,
+                    ..Default::default()
+                })
+            }
+            "file_dependencies" => {
+                let file_path = required(&args.file_path, "file_dependencies", "file_path")?;
+                let Some(target) = insights
+                    .iter()
+                    .find(|insight| same_file(&insight.code_dossier.file_path, file_path))
+                else {
+                    return Ok(InsightLookupResult::note(format!(
+                        "No code insight for {}, the file was not analyzed or does not exist",
+                        file_path
+                    )));
+                };
+                let target_path = target.code_dossier.file_path.as_path();
+                let mut dependencies: Vec<_> = target
+                    .dependencies
+                    .iter()
+                    .map(|dependency| DependencyMatch {
+                        direction: "outgoing".to_string(),
+                        file_path: display_path(target_path),
+                        dependency: dependency.clone(),
+                    })
+                    .collect();
+                for insight in &insights {
+                    if insight.code_dossier.file_path == target_path {
+                        continue;
+                    }
+                    for dependency in &insight.dependencies {
+                        if !dependency.is_external
+                            && dependency.refers_to(&insight.code_dossier.file_path, target_path)
+                        {
+                            dependencies.push(DependencyMatch {
+                                direction: "incoming".to_string(),
+                                file_path: display_path(&insight.code_dossier.file_path),
+                                dependency: dependency.clone(),
+                            });
+                        }
+                    }
+                }
+                let total = dependencies.len();
+                dependencies.truncate(max_results);
+                Ok(InsightLookupResult {
+                    notes: vec![format!(
+                        "Found {} dependencies of and on {}, showing {}; incoming dependencies are matched by module path and may be incomplete",
+                        total,
+                        display_path(target_path),
+                        dependencies.len()
+                    )],
+                    dependencies,
+                    ..Default::default()
+                })
+            }
+            "files_by_purpose" => {
+                let purpose = required(&args.purpose, "files_by_purpose", "purpose")?;
+                let purpose = parse_purpose(purpose)
+                    .ok_or_else(|| anyhow::anyhow!("Unknown code purpose: {}", purpose))?;
+                let mut files: Vec<_> = insights
+                    .iter()
+                    .filter(|insight| insight.code_dossier.code_purpose == purpose)
+                    .map(|insight| FileSummary {
+                        file_path: display_path(&insight.code_dossier.file_path),
+                        code_purpose: purpose.clone(),
+                        importance_score: insight.code_dossier.importance_score,
+                        description: insight
+                            .code_dossier
+                            .description
+                            .clone()
+                            .unwrap_or_else(|| insight.detailed_description.chars().take(200).collect()),
+                    })
+                    .collect();
+                files.sort_by(|a, b| b.importance_score.total_cmp(&a.importance_score));
+                let total = files.len();
+                files.truncate(max_results);
+                Ok(InsightLookupResult {
+                    notes: vec![format!(
+                        "Found {} files of purpose {}, showing the {} most important",
+                        total,
+                        purpose,
+                        files.len()
+                    )],
+                    files,
+                    ..Default::default()
+                })
+            }
+            action => Err(anyhow::anyhow!("Unsupported action: {}", action)),
+        }
+    }
+}
+
+fn required<'a>(value: &'a Option<String>, action: &str, name: &str) -> Result<&'a str> {
+    value
+        .as_deref()
+        .filter(|value| !value.trim().is_empty())
+        .ok_or_else(|| anyhow::anyhow!("{} action requires {} parameter", action, name))
+}
+
+fn display_path(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+fn normalize_path(path: &str) -> String {
+    path.replace('\\', "/").trim_start_matches("./").trim_matches('/').to_lowercase()
+}
+
+/// Whether a file path given by the model designates an analyzed file, the model may omit leading directories
+fn same_file(file: &Path, requested: &str) -> bool {
+    let file = normalize_path(&display_path(file));
+    let requested = normalize_path(requested);
+    !requested.is_empty()
+        && (file == requested
+            || file.ends_with(&format!("/{}", requested))
+            || requested.ends_with(&format!("/{}", file)))
+}
+
+/// Parse a code purpose as named in the tool description, separators and case are ignored
+fn parse_purpose(purpose: &str) -> Option<CodePurpose> {
+    let normalized: String = purpose
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect::<String>()
+        .to_lowercase();
+    serde_json::from_value(serde_json::Value::String(normalized)).ok()
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("insight lookup tool error: {0}")]
+pub struct InsightLookupToolError(String);
+
+impl Tool for AgentToolInsightLookup {
+    const NAME: &'static str = "insight_lookup";
+
+    type Error = InsightLookupToolError;
+    type Args = InsightLookupArgs;
+    type Output = InsightLookupResult;
+
+    async fn definition(&self, _prompt: String) -> rig::completion::ToolDefinition {
+        rig::completion::ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: "Query the code analysis made during preprocessing: the detailed insight of a file (responsibilities, interfaces, dependencies, complexity), interfaces matching a name, the dependencies of and on a file, or the files of a given purpose. Use it for modules left out of the prompt instead of guessing."
+                .to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "action": {
+                        "type": "string",
+                        "enum": ["file_insight", "find_interfaces", "file_dependencies", "files_by_purpose"],
+                        "description": "Query to run"
+                    },
+                    "file_path": {
+                        "type": "string",
+                        "description": "File path relative to project root (for file_insight and file_dependencies)"
+                    },
+                    "name": {
+                        "type": "string",
+                        "description": "Part of the interface name to look for, case insensitive (for find_interfaces)"
+                    },
+                    "purpose": {
+                        "type": "string",
+                        "enum": ["entry", "agent", "page", "widget", "specificfeature", "model", "types", "tool", "util", "config", "middleware", "plugin", "router", "database", "api", "controller", "service", "module", "lib", "test", "doc", "dao", "context", "command", "other"],
+                        "description": "Code purpose of the files to list (for files_by_purpose)"
+                    },
+                    "max_results": {
+                        "type": "integer",
+                        "description": "Maximum number of entries to return (default 20, at most 100)"
+                    }
+                },
+                "required": ["action"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        println!("   🔧 tool called...insight_lookup@{:?}", args);
+
+        tokio::time::sleep(Duration::from_secs(1)).await;
+
+        self.lookup(&args)
+            .await
+            .map_err(|e| InsightLookupToolError(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::preprocess::extractors::language_processors::LanguageProcessorManager;
+
+    #[test]
+    fn test_incoming_dependencies_are_resolved_from_processed_imports() {
+        let processors = LanguageProcessorManager::new();
+        let workflow = Path::new("src/generator/workflow.rs");
+        let dependencies = processors.extract_dependencies(
+            workflow,
+            "use crate::llm::client::LLMClient;\nuse crate::utils::{sources, threads as pool};\nuse std::sync::Arc;\n",
+        );
+        let refers_to = |file: &str| {
+            dependencies
+                .iter()
+                .filter(|dependency| !dependency.is_external)
+                .any(|dependency| dependency.refers_to(workflow, Path::new(file)))
+        };
+        assert!(refers_to("src/llm/client/mod.rs"));
+        assert!(refers_to("src/utils/threads.rs"));
+        assert!(!refers_to("src/generator/mod.rs"));
+        assert!(!refers_to("src/llm/tools/mod.rs"));
+
+        let app = Path::new("web/src/app.ts");
+        let dependencies = processors.extract_dependencies(app, "import { formatDate } from './utils/format';\n");
+        assert!(dependencies[0].refers_to(app, Path::new("web/src/utils/format.ts")));
+        assert!(!dependencies[0].refers_to(app, Path::new("web/src/utils/parse.ts")));
+        assert!(!dependencies[0].refers_to(app, Path::new("web/src/app.ts")));
+    }
+
+    #[test]
+    fn test_file_and_purpose_matching() {
+        assert!(same_file(Path::new("src/llm/client/mod.rs"), "./llm/client/mod.rs"));
+        assert!(!same_file(Path::new("src/llm/client/mod.rs"), "mod.rs.bak"));
+        assert_eq!(parse_purpose("Specific_Feature"), Some(CodePurpose::SpecificFeature));
+        assert_eq!(parse_purpose("dao"), Some(CodePurpose::Dao));
+        assert_eq!(parse_purpose("nonsense"), None);
+    }
+}
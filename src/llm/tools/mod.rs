@@ -1,3 +1,23 @@
+pub mod access_policy;
+pub mod code_search;
 pub mod file_explorer;
 pub mod file_reader;
+pub mod git_history;
+pub mod insight_lookup;
 pub mod time;
+
+use code_search::AgentToolCodeSearch;
+use file_explorer::AgentToolFileExplorer;
+use file_reader::AgentToolFileReader;
+use git_history::AgentToolGitHistory;
+use insight_lookup::AgentToolInsightLookup;
+
+/// Tools given to ReAct agents unless preset tools are disabled
+#[derive(Debug, Clone)]
+pub struct PresetTools {
+    pub file_explorer: AgentToolFileExplorer,
+    pub file_reader: AgentToolFileReader,
+    pub code_search: AgentToolCodeSearch,
+    pub insight_lookup: AgentToolInsightLookup,
+    pub git_history: AgentToolGitHistory,
+}
@@ -1,4 +1,4 @@
-use crate::generator::workflow::launch;
+use crate::generator::workflow::{launch, resume};
 use anyhow::Result;
 use clap::Parser;
 
@@ -15,23 +15,59 @@ mod utils;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let args = cli::Args::parse();
+    let mut args = cli::Args::parse();
 
     // Handle subcommands
-    if let Some(command) = args.command {
-        return handle_subcommand(command, args.config).await;
+    if let Some(command) = args.command.take() {
+        return handle_subcommand(command, args).await;
     }
 
     // Default: run documentation generation
+    let dry_run = args.dry_run;
     let config = args.to_config();
+    if dry_run {
+        return generator::dry_run::launch(&config).await;
+    }
     launch(&config).await
 }
 
 /// Handle CLI subcommands
-async fn handle_subcommand(command: cli::Commands, config_path: Option<std::path::PathBuf>) -> Result<()> {
+async fn handle_subcommand(command: cli::Commands, args: cli::Args) -> Result<()> {
     match command {
         cli::Commands::SyncKnowledge { config, force } => {
-            sync_knowledge(config.or(config_path), force).await
+            sync_knowledge(config.or(args.config), force).await
+        }
+        cli::Commands::Resume { run_id } => resume(&args.to_config(), run_id.as_deref()).await,
+        cli::Commands::Trace {
+            action: cli::TraceCommand::Show { agent, all, full },
+        } => llm::client::trace::show(&args.to_config(), &agent, all, full),
+        cli::Commands::Prompts {
+            action: cli::PromptsCommand::Export { output, force },
+        } => generator::prompt_templates::export(&args.to_config(), output, force),
+        cli::Commands::Cache { action } => {
+            let config = args.to_config();
+            match action {
+                cli::CacheCommand::Stats => cache::maintenance::stats(&config).await,
+                cli::CacheCommand::Prune {
+                    older_than,
+                    max_size,
+                } => cache::maintenance::prune(&config, older_than, max_size).await,
+                cli::CacheCommand::Clear { category } => {
+                    cache::maintenance::clear(&config, category.as_deref()).await
+                }
+                cli::CacheCommand::Show { hash } => cache::maintenance::show(&config, &hash).await,
+                cli::CacheCommand::Export {
+                    output,
+                    since,
+                    category,
+                } => {
+                    cache::bundle::export(&config, &output, since.as_deref(), category.as_deref())
+                        .await
+                }
+                cli::CacheCommand::Import { archive } => {
+                    cache::bundle::import(&config, &archive).await
+                }
+            }
         }
     }
 }
@@ -3,6 +3,7 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
 /// Memory metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -83,6 +84,21 @@ impl Memory {
             .and_then(|value| serde_json::from_value(value.clone()).ok())
     }
 
+    /// Get data from specified scope and key without recording an access, deserialized in place
+    pub fn get_untracked<T>(&self, scope: &str, key: &str) -> Option<T>
+    where
+        T: for<'a> Deserialize<'a>,
+    {
+        self.data
+            .get(&format!("{}:{}", scope, key))
+            .and_then(|value| T::deserialize(value).ok())
+    }
+
+    /// Get raw value from specified scope and key without recording an access
+    pub fn get_value(&self, scope: &str, key: &str) -> Option<Value> {
+        self.data.get(&format!("{}:{}", scope, key)).cloned()
+    }
+
     /// List all keys in the specified scope
     pub fn list_keys(&self, scope: &str) -> Vec<String> {
         let prefix = format!("{}:", scope);
@@ -110,4 +126,55 @@ impl Memory {
 
         stats
     }
+
+    /// Export all data within a scope, keyed by the scope-relative key
+    pub fn export_scope(&self, scope: &str) -> HashMap<String, Value> {
+        let prefix = format!("{}:", scope);
+        self.data
+            .iter()
+            .filter(|(key, _)| key.starts_with(&prefix))
+            .map(|(key, value)| (key[prefix.len()..].to_string(), value.clone()))
+            .collect()
+    }
+
+    /// Import previously exported data into a scope, replacing existing keys
+    pub fn import_scope(&mut self, scope: &str, entries: HashMap<String, Value>) -> Result<()> {
+        for (key, value) in entries {
+            self.store(scope, &key, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Persisted snapshot of a single Memory scope
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MemorySnapshot {
+    pub scope: String,
+    pub created_at: DateTime<Utc>,
+    pub entries: HashMap<String, Value>,
+}
+
+impl MemorySnapshot {
+    /// Snapshot file path of a scope under the given directory
+    pub fn path_for(dir: &Path, scope: &str) -> PathBuf {
+        dir.join(format!("{}.json", scope))
+    }
+
+    /// Write snapshot to disk
+    pub fn save(&self, dir: &Path) -> Result<PathBuf> {
+        std::fs::create_dir_all(dir)?;
+        let path = Self::path_for(dir, &self.scope);
+        std::fs::write(&path, serde_json::to_string(self)?)?;
+        Ok(path)
+    }
+
+    /// Read snapshot from disk, returns None if it does not exist
+    pub fn load(dir: &Path, scope: &str) -> Result<Option<Self>> {
+        let path = Self::path_for(dir, scope);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(&path)?;
+        Ok(Some(serde_json::from_str(&content)?))
+    }
 }
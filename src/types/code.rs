@@ -1,6 +1,6 @@
 use std::{
     fmt::{Display, Formatter},
-    path::PathBuf,
+    path::{Component, Path, PathBuf},
 };
 
 use schemars::JsonSchema;
@@ -89,6 +89,111 @@ impl Display for Dependency {
     }
 }
 
+impl Dependency {
+    /// What the dependency imports. Most processors record it in `path`, the Rust, Java, C#, PHP and Swift
+    /// processors record the importing file there and the import in `name`
+    pub fn target(&self, source_file: &Path) -> &str {
+        match self.path.as_deref() {
+            Some(path) if Path::new(path) != source_file => path,
+            _ => &self.name,
+        }
+    }
+
+    /// Whether the dependency, declared in `source_file`, designates `file`. Relative imports are resolved
+    /// against the importing file, module paths are compared by their trailing segments, with a last segment
+    /// naming an imported symbol allowed: `crate::llm::client::LLMClient` designates `src/llm/client/mod.rs`
+    pub fn refers_to(&self, source_file: &Path, file: &Path) -> bool {
+        let file_segments = file_module_segments(file);
+        if file_segments.is_empty() {
+            return false;
+        }
+        let target = self.target(source_file).trim();
+
+        if target.starts_with("./") || target.starts_with("../") {
+            let mut resolved = source_file.parent().map(Path::to_path_buf).unwrap_or_default();
+            for component in Path::new(target).components() {
+                match component {
+                    Component::ParentDir => {
+                        resolved.pop();
+                    }
+                    Component::Normal(part) => resolved.push(part),
+                    _ => {}
+                }
+            }
+            return file_module_segments(&resolved) == file_segments;
+        }
+
+        import_candidates(target).iter().any(|candidate| {
+            let segments = import_segments(candidate);
+            (0..=1).any(|symbol| {
+                let module = &segments[..segments.len().saturating_sub(symbol)];
+                !module.is_empty() && file_segments.ends_with(module)
+            })
+        })
+    }
+}
+
+/// Lowercase module path of a file, `mod`, `index`, `__init__`, `lib` and `main` files are named after their directory
+fn file_module_segments(file: &Path) -> Vec<String> {
+    let file = match file.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) if is_file_extension(ext) => file.with_extension(""),
+        _ => file.to_path_buf(),
+    };
+    let mut segments: Vec<String> = file
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_string_lossy().to_lowercase()),
+            _ => None,
+        })
+        .collect();
+    if matches!(
+        segments.last().map(String::as_str),
+        Some("mod" | "index" | "__init__" | "lib" | "main")
+    ) {
+        segments.pop();
+    }
+    segments
+}
+
+/// Imports of a statement, `crate::a::{b, c as d}` imports `crate::a::b` and `crate::a::c`
+fn import_candidates(target: &str) -> Vec<String> {
+    let without_alias = |import: &str| import.split(" as ").next().unwrap_or(import).trim().to_string();
+    match target.split_once('{') {
+        Some((prefix, group)) => group
+            .trim_end_matches('}')
+            .split(',')
+            .map(|item| format!("{}{}", prefix, without_alias(item)))
+            .collect(),
+        None => vec![without_alias(target)],
+    }
+}
+
+/// Lowercase segments of a module path or file path, without relative prefixes
+fn import_segments(import: &str) -> Vec<String> {
+    let segments: Vec<&str> = if import.contains('/') {
+        let path = Path::new(import);
+        let stem_len = match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if is_file_extension(ext) => import.len() - ext.len() - 1,
+            _ => import.len(),
+        };
+        import[..stem_len].split('/').collect()
+    } else {
+        import.split([':', '.', '\\']).collect()
+    };
+    segments
+        .into_iter()
+        .map(|segment| segment.trim().to_lowercase())
+        .filter(|segment| {
+            !segment.is_empty()
+                && !matches!(segment.as_str(), "." | ".." | "@" | "~" | "*" | "crate" | "self" | "super")
+        })
+        .collect()
+}
+
+fn is_file_extension(ext: &str) -> bool {
+    ext.len() <= 5 && ext.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
 /// Component complexity metrics
 #[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct CodeComplexity {
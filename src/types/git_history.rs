@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+
+/// Version control history of the project, read from the local git repository
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct GitHistory {
+    /// HEAD commit when the history was read
+    pub head: Option<String>,
+    /// Number of commits the history was built from, the most recent ones
+    pub analyzed_commits: usize,
+    /// Files of the project by number of commits, most changed first
+    pub files: Vec<FileHistory>,
+    /// Files that are often changed in the same commit, most frequent first
+    pub co_changes: Vec<CoChange>,
+    /// Latest commits touching the project
+    pub recent_commits: Vec<CommitSummary>,
+}
+
+/// History of a single file
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FileHistory {
+    /// Path relative to the project root
+    pub path: String,
+    pub commits: usize,
+    pub last_author: String,
+    pub last_changed: String,
+    /// Number of distinct authors
+    pub authors: usize,
+    /// Subjects of the latest commits touching the file
+    pub recent_messages: Vec<String>,
+}
+
+/// Two files changed together
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct CoChange {
+    pub first: String,
+    pub second: String,
+    /// Commits touching both files
+    pub commits: usize,
+}
+
+/// A commit of the project
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct CommitSummary {
+    /// Abbreviated commit hash
+    pub hash: String,
+    pub author: String,
+    pub date: String,
+    pub subject: String,
+    /// Changed files, relative to the project root
+    pub files: Vec<String>,
+}
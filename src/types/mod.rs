@@ -1,5 +1,6 @@
 pub mod code;
 pub mod code_releationship;
+pub mod git_history;
 pub mod original_document;
 pub mod project_structure;
 
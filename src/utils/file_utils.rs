@@ -1,5 +1,7 @@
 use std::path::Path;
 
+use crate::config::Config;
+
 /// Check if a file is a test file
 pub fn is_test_file(path: &Path) -> bool {
     let file_name = path
@@ -161,3 +163,76 @@ pub fn is_binary_file_path(path: &Path) -> bool {
         false
     }
 }
+
+/// Check if a directory is excluded from analysis as a test or hidden directory,
+/// `excluded_dirs` is applied by the `IgnoreMatcher`
+pub fn should_ignore_directory(config: &Config, dir_name: &str) -> bool {
+    // Check if it's a test directory (if not including test files)
+    if !config.include_tests && is_test_directory(dir_name) {
+        return true;
+    }
+
+    // Check hidden directories
+    if !config.include_hidden && dir_name.starts_with('.') {
+        return true;
+    }
+
+    false
+}
+
+/// Check if a file is excluded from analysis by its extension, size or kind, binary files included.
+/// `excluded_files` is applied by the `IgnoreMatcher`
+pub fn should_ignore_file(config: &Config, path: &Path) -> bool {
+    let file_name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+
+    // Check excluded extensions
+    if let Some(extension) = path.extension().and_then(|e| e.to_str())
+        && config
+            .excluded_extensions
+            .contains(&extension.to_lowercase())
+    {
+        return true;
+    }
+
+    // Check included extensions (if specified)
+    if !config.included_extensions.is_empty() {
+        if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
+            if !config
+                .included_extensions
+                .contains(&extension.to_lowercase())
+            {
+                return true;
+            }
+        } else {
+            return true; // No extension and include list is specified
+        }
+    }
+
+    // Check test files (if not including test files)
+    if !config.include_tests && is_test_file(path) {
+        return true;
+    }
+
+    // Check hidden files
+    if !config.include_hidden && file_name.starts_with('.') {
+        return true;
+    }
+
+    // Check file size
+    if let Ok(metadata) = std::fs::metadata(path)
+        && metadata.len() > config.max_file_size
+    {
+        return true;
+    }
+
+    // Check binary files
+    if is_binary_file_path(path) {
+        return true;
+    }
+
+    false
+}
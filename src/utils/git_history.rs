@@ -0,0 +1,229 @@
+//! Git history - Reads commits from the local git repository of the project, no remote is contacted
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+use std::process::Command;
+
+use crate::types::git_history::{CoChange, CommitSummary, FileHistory, GitHistory};
+
+/// Commits touching more files are bulk changes (formatting, renames), they say nothing about coupling
+const MAX_CO_CHANGE_FILES: usize = 20;
+/// Commit subjects kept per file
+const RECENT_MESSAGES_PER_FILE: usize = 3;
+
+const RECORD_SEPARATOR: char = '\u{1e}';
+const FIELD_SEPARATOR: char = '\u{1f}';
+
+/// Latest commits touching `path` (the whole project if None), newest first, listing every file they changed.
+/// None if the project is not in a git repository
+pub fn read_commits(project_path: &Path, path: Option<&str>, max_commits: usize) -> Option<Vec<CommitSummary>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(project_path)
+        .args([
+            "log",
+            "--no-merges",
+            "--relative",
+            "--name-only",
+            "--full-diff",
+            "--date=short",
+            "--format=%x1e%h%x1f%an%x1f%ad%x1f%s",
+        ])
+        .arg(format!("--max-count={}", max_commits))
+        .arg("--")
+        .arg(path.unwrap_or("."))
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(parse_log(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// HEAD commit of the repository containing the project
+pub fn head(project_path: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(project_path)
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Parse the output of `git log` in the format used by `read_commits`
+fn parse_log(log: &str) -> Vec<CommitSummary> {
+    log.split(RECORD_SEPARATOR)
+        .filter_map(|record| {
+            let mut lines = record.lines();
+            let mut fields = lines.next()?.splitn(4, FIELD_SEPARATOR);
+            let hash = fields.next()?.trim().to_string();
+            if hash.is_empty() {
+                return None;
+            }
+            Some(CommitSummary {
+                hash,
+                author: fields.next()?.to_string(),
+                date: fields.next()?.to_string(),
+                subject: fields.next().unwrap_or_default().to_string(),
+                files: lines
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(str::to_string)
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+/// Aggregate commits (newest first) into per-file history and co-change pairs.
+/// `keep_file` filters the files listed, e.g. to drop deleted or excluded files
+pub fn summarize(
+    commits: &[CommitSummary],
+    keep_file: impl Fn(&str) -> bool,
+    max_files: usize,
+    max_co_changes: usize,
+    max_recent_commits: usize,
+) -> GitHistory {
+    let mut files: HashMap<&str, (FileHistory, BTreeSet<&str>)> = HashMap::new();
+    let mut pairs: BTreeMap<(&str, &str), usize> = BTreeMap::new();
+
+    for commit in commits {
+        let touched: Vec<&str> = commit
+            .files
+            .iter()
+            .map(String::as_str)
+            .filter(|file| keep_file(file))
+            .collect();
+        for file in &touched {
+            let (history, authors) = files.entry(file).or_insert_with(|| {
+                (
+                    FileHistory {
+                        path: file.to_string(),
+                        commits: 0,
+                        last_author: commit.author.clone(),
+                        last_changed: commit.date.clone(),
+                        authors: 0,
+                        recent_messages: Vec::new(),
+                    },
+                    BTreeSet::new(),
+                )
+            });
+            history.commits += 1;
+            authors.insert(commit.author.as_str());
+            if history.recent_messages.len() < RECENT_MESSAGES_PER_FILE {
+                history.recent_messages.push(commit.subject.clone());
+            }
+        }
+        if touched.len() > 1 && touched.len() <= MAX_CO_CHANGE_FILES {
+            for (i, first) in touched.iter().enumerate() {
+                for second in &touched[i + 1..] {
+                    let pair = if first < second { (*first, *second) } else { (*second, *first) };
+                    *pairs.entry(pair).or_insert(0) += 1;
+                }
+            }
+        }
+    }
+
+    let mut files: Vec<FileHistory> = files
+        .into_values()
+        .map(|(mut history, authors)| {
+            history.authors = authors.len();
+            history
+        })
+        .collect();
+    files.sort_by(|a, b| b.commits.cmp(&a.commits).then_with(|| a.path.cmp(&b.path)));
+    files.truncate(max_files);
+
+    // A single shared commit is a coincidence
+    let mut co_changes: Vec<CoChange> = pairs
+        .into_iter()
+        .filter(|(_, count)| *count > 1)
+        .map(|((first, second), commits)| CoChange {
+            first: first.to_string(),
+            second: second.to_string(),
+            commits,
+        })
+        .collect();
+    co_changes.sort_by_key(|pair| std::cmp::Reverse(pair.commits));
+    co_changes.truncate(max_co_changes);
+
+    GitHistory {
+        head: None,
+        analyzed_commits: commits.len(),
+        files,
+        co_changes,
+        recent_commits: commits.iter().take(max_recent_commits).cloned().collect(),
+    }
+}
+
+/// Files most often changed in the same commits as `path`
+pub fn co_changes_of(commits: &[CommitSummary], path: &str, max_results: usize) -> Vec<CoChange> {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for commit in commits {
+        if commit.files.len() > MAX_CO_CHANGE_FILES || !commit.files.iter().any(|file| file == path) {
+            continue;
+        }
+        for file in commit.files.iter().filter(|file| *file != path) {
+            *counts.entry(file).or_insert(0) += 1;
+        }
+    }
+    let mut co_changes: Vec<CoChange> = counts
+        .into_iter()
+        .map(|(file, commits)| CoChange {
+            first: path.to_string(),
+            second: file.to_string(),
+            commits,
+        })
+        .collect();
+    co_changes.sort_by_key(|pair| std::cmp::Reverse(pair.commits));
+    co_changes.truncate(max_results);
+    co_changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = "\u{1e}a1b2c3d\u{1f}Ada\u{1f}2025-03-02\u{1f}Retry failed LLM calls\n\nsrc/llm/client.rs\nsrc/config.rs\n\
+\u{1e}e4f5a6b\u{1f}Linus\u{1f}2025-03-01\u{1f}Add retry settings\n\nsrc/config.rs\nsrc/llm/client.rs\nREADME.md\n\
+\u{1e}c7d8e9f\u{1f}Ada\u{1f}2025-02-20\u{1f}Initial commit\n\nsrc/main.rs\n";
+
+    #[test]
+    fn test_parse_log() {
+        let commits = parse_log(LOG);
+        assert_eq!(commits.len(), 3);
+        assert_eq!(commits[0].hash, "a1b2c3d");
+        assert_eq!(commits[0].subject, "Retry failed LLM calls");
+        assert_eq!(commits[1].files, vec!["src/config.rs", "src/llm/client.rs", "README.md"]);
+    }
+
+    #[test]
+    fn test_summarize_counts_files_and_co_changes() {
+        let commits = parse_log(LOG);
+        let history = summarize(&commits, |file| file != "README.md", 10, 10, 2);
+
+        assert_eq!(history.analyzed_commits, 3);
+        assert_eq!(history.recent_commits.len(), 2);
+        assert_eq!(history.files[0].path, "src/config.rs");
+        assert_eq!(history.files[0].commits, 2);
+        assert_eq!(history.files[0].authors, 2);
+        assert_eq!(history.files[0].last_author, "Ada");
+        assert_eq!(
+            history.files[0].recent_messages,
+            vec!["Retry failed LLM calls", "Add retry settings"]
+        );
+        assert_eq!(co_changes_of(&commits, "src/config.rs", 10).len(), 2);
+        assert_eq!(
+            history.co_changes,
+            vec![CoChange {
+                first: "src/config.rs".to_string(),
+                second: "src/llm/client.rs".to_string(),
+                commits: 2,
+            }]
+        );
+    }
+}
@@ -0,0 +1,323 @@
+//! Ignore matcher - Decides which project paths are excluded from the analysis, with gitignore semantics
+//!
+//! Rules come from `excluded_dirs` and `excluded_files`, then from the `.gitignore` and `.lithoignore` files
+//! of every directory from the project root down to the path. The last matching rule wins, and a path
+//! inside an excluded directory stays excluded whatever the rules below say, as in git.
+
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use glob::{MatchOptions, Pattern};
+
+use crate::config::Config;
+use crate::utils::file_utils::{should_ignore_directory, should_ignore_file};
+
+/// Ignore file read by Litho only, same syntax as `.gitignore`
+pub const LITHO_IGNORE_FILE: &str = ".lithoignore";
+
+const GIT_IGNORE_FILE: &str = ".gitignore";
+
+/// Why a path is excluded
+#[derive(Debug, Clone, PartialEq)]
+pub enum Exclusion {
+    /// The path is, or lies inside, this excluded directory (relative to the project root)
+    Directory(PathBuf),
+    File,
+}
+
+/// Kind of paths a rule applies to
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RuleTarget {
+    Any,
+    Directories,
+    Files,
+}
+
+/// One line of an ignore file, or one configured exclusion
+#[derive(Debug, Clone)]
+struct IgnoreRule {
+    pattern: Pattern,
+    negated: bool,
+    /// Matched against the path relative to `base` rather than against the file name
+    anchored: bool,
+    target: RuleTarget,
+    /// Directory of the ignore file, relative to the project root
+    base: PathBuf,
+    case_sensitive: bool,
+}
+
+impl IgnoreRule {
+    /// Parse a gitignore line, None for blank lines, comments and invalid patterns
+    fn parse(line: &str, base: &Path, target: RuleTarget, case_sensitive: bool) -> Option<Result<Self, String>> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line.strip_prefix('\\').filter(|rest| rest.starts_with(['#', '!'])).unwrap_or(line)),
+        };
+        let (target, line) = match line.strip_suffix('/') {
+            Some(rest) => (RuleTarget::Directories, rest),
+            None => (target, line),
+        };
+        if line.is_empty() {
+            return None;
+        }
+        let anchored = line.contains('/');
+        let line = line.strip_prefix('/').unwrap_or(line);
+        Some(
+            Pattern::new(line)
+                .map(|pattern| Self {
+                    pattern,
+                    negated,
+                    anchored,
+                    target,
+                    base: base.to_path_buf(),
+                    case_sensitive,
+                })
+                .map_err(|e| format!("{}: {}", line, e)),
+        )
+    }
+
+    /// Whether the rule matches a path relative to the project root
+    fn matches(&self, relative: &Path, is_dir: bool) -> bool {
+        match self.target {
+            RuleTarget::Directories if !is_dir => return false,
+            RuleTarget::Files if is_dir => return false,
+            _ => {}
+        }
+        let Ok(below_base) = relative.strip_prefix(&self.base) else {
+            return false;
+        };
+        let options = MatchOptions {
+            case_sensitive: self.case_sensitive,
+            require_literal_separator: true,
+            require_literal_leading_dot: false,
+        };
+        if self.anchored {
+            self.pattern.matches_with(&slash_path(below_base), options)
+        } else {
+            below_base
+                .file_name()
+                .is_some_and(|name| self.pattern.matches_with(&name.to_string_lossy(), options))
+        }
+    }
+}
+
+/// Excluded paths of a project, shared by the structure scanner and the agent tools so both see the same files
+#[derive(Debug, Clone)]
+pub struct IgnoreMatcher {
+    config: Config,
+    config_rules: Arc<Vec<IgnoreRule>>,
+    /// Rules of the ignore files of each directory, loaded when a path below it is first checked
+    directory_rules: Arc<RwLock<HashMap<PathBuf, Arc<Vec<IgnoreRule>>>>>,
+}
+
+impl IgnoreMatcher {
+    pub fn new(config: &Config) -> Self {
+        let root = Path::new("");
+        let dirs = config
+            .excluded_dirs
+            .iter()
+            .map(|line| (line, RuleTarget::Directories));
+        let files = config
+            .excluded_files
+            .iter()
+            .map(|line| (line, RuleTarget::Files));
+        let config_rules = dirs
+            .chain(files)
+            .filter_map(|(line, target)| match IgnoreRule::parse(line, root, target, false)? {
+                Ok(rule) => Some(rule),
+                Err(e) => {
+                    eprintln!("⚠️  Ignoring invalid exclusion pattern {}", e);
+                    None
+                }
+            })
+            .collect();
+        Self {
+            config: config.clone(),
+            config_rules: Arc::new(config_rules),
+            directory_rules: Arc::default(),
+        }
+    }
+
+    /// Whether a path relative to the project root is excluded from the analysis
+    pub fn is_excluded(&self, relative: &Path, is_dir: bool) -> bool {
+        self.exclusion(relative, is_dir).is_some()
+    }
+
+    /// Why a path relative to the project root is excluded, None if it is analyzed
+    pub fn exclusion(&self, relative: &Path, is_dir: bool) -> Option<Exclusion> {
+        let parts = relative
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(part) => Some(part),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let mut current = PathBuf::new();
+        let mut rules = vec![self.config_rules.clone()];
+        for (index, part) in parts.iter().enumerate() {
+            let parent = current.clone();
+            current.push(part);
+            let part_is_dir = index + 1 < parts.len() || is_dir;
+            rules.push(self.rules_of(&parent));
+
+            let ruled = rules
+                .iter()
+                .flat_map(|rules| rules.iter())
+                .rev()
+                .find(|rule| rule.matches(&current, part_is_dir))
+                .map(|rule| !rule.negated);
+            if part_is_dir {
+                if ruled.unwrap_or(false) || should_ignore_directory(&self.config, &part.to_string_lossy()) {
+                    return Some(Exclusion::Directory(current));
+                }
+            } else if ruled.unwrap_or(false)
+                || should_ignore_file(&self.config, &self.config.project_path.join(&current))
+            {
+                return Some(Exclusion::File);
+            }
+        }
+        None
+    }
+
+    /// Rules of the ignore files in a directory relative to the project root
+    fn rules_of(&self, dir: &Path) -> Arc<Vec<IgnoreRule>> {
+        if let Some(rules) = self.directory_rules.read().ok().and_then(|cache| cache.get(dir).cloned()) {
+            return rules;
+        }
+        let file_names = if self.config.use_gitignore {
+            vec![GIT_IGNORE_FILE, LITHO_IGNORE_FILE]
+        } else {
+            vec![LITHO_IGNORE_FILE]
+        };
+        let mut rules = Vec::new();
+        for file_name in file_names {
+            let path = self.config.project_path.join(dir).join(file_name);
+            let Ok(content) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            for line in content.lines() {
+                match IgnoreRule::parse(line, dir, RuleTarget::Any, true) {
+                    Some(Ok(rule)) => rules.push(rule),
+                    Some(Err(e)) => eprintln!("⚠️  Ignoring invalid pattern in {}: {}", path.display(), e),
+                    None => {}
+                }
+            }
+        }
+        let rules = Arc::new(rules);
+        if let Ok(mut cache) = self.directory_rules.write() {
+            cache.insert(dir.to_path_buf(), rules.clone());
+        }
+        rules
+    }
+}
+
+/// Path with `/` separators, as ignore patterns are written
+fn slash_path(path: &Path) -> String {
+    path.iter()
+        .map(|part| part.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(lines: &[&str], base: &str) -> Vec<IgnoreRule> {
+        lines
+            .iter()
+            .filter_map(|line| IgnoreRule::parse(line, Path::new(base), RuleTarget::Any, true))
+            .map(|rule| rule.unwrap())
+            .collect()
+    }
+
+    fn ignored(rules: &[IgnoreRule], path: &str, is_dir: bool) -> bool {
+        rules
+            .iter()
+            .rev()
+            .find(|rule| rule.matches(Path::new(path), is_dir))
+            .is_some_and(|rule| !rule.negated)
+    }
+
+    #[test]
+    fn test_gitignore_rules() {
+        let root = rules(
+            &["# comment", "*.min.js", "!keep.min.js", "/generated", "docs/**/*.html", "out/", "\\#notes"],
+            "",
+        );
+        assert!(ignored(&root, "web/app.min.js", false));
+        assert!(!ignored(&root, "web/foo.min.json.bak", false));
+        assert!(!ignored(&root, "web/keep.min.js", false));
+        assert!(ignored(&root, "generated", true));
+        assert!(!ignored(&root, "src/generated", true));
+        assert!(ignored(&root, "docs/index.html", false));
+        assert!(ignored(&root, "docs/api/v1/index.html", false));
+        assert!(!ignored(&root, "src/index.html", false));
+        assert!(ignored(&root, "src/out", true));
+        assert!(!ignored(&root, "src/out", false));
+        assert!(ignored(&root, "#notes", false));
+
+        let nested = rules(&["*.rs", "/local.rs"], "crates/core");
+        assert!(ignored(&nested, "crates/core/src/lib.rs", false));
+        assert!(ignored(&nested, "crates/core/local.rs", false));
+        assert!(!ignored(&nested, "src/main.rs", false));
+    }
+
+    #[test]
+    fn test_project_exclusions() {
+        let root = std::env::temp_dir().join(format!("litho-ignore-{}", uuid::Uuid::new_v4()));
+        for (path, content) in [
+            (".gitignore", "*.csv\n/gen/\n"),
+            (".lithoignore", "!keep.csv\n!gen/main.rs\n"),
+            ("src/.gitignore", "generated.rs\n"),
+            ("src/lib.rs", ""),
+            ("src/generated.rs", ""),
+            ("app.csv", ""),
+            ("keep.csv", ""),
+            ("gen/main.rs", ""),
+            ("Secrets.YAML", ""),
+        ] {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        let config = Config {
+            project_path: root.clone(),
+            excluded_files: vec!["secrets.yaml".to_string()],
+            ..Config::default()
+        };
+        let matcher = IgnoreMatcher::new(&config);
+        let exclusion = |path: &str, is_dir: bool| matcher.exclusion(Path::new(path), is_dir);
+
+        assert_eq!(exclusion("src/lib.rs", false), None);
+        // Rules of nested ignore files apply below their directory
+        assert_eq!(exclusion("src/generated.rs", false), Some(Exclusion::File));
+        assert_eq!(exclusion("generated.rs", false), None);
+        // .lithoignore is read after .gitignore, so its rules win
+        assert_eq!(exclusion("app.csv", false), Some(Exclusion::File));
+        assert_eq!(exclusion("keep.csv", false), None);
+        // A negated file stays excluded inside an excluded directory
+        assert_eq!(
+            exclusion("gen/main.rs", false),
+            Some(Exclusion::Directory(PathBuf::from("gen")))
+        );
+        // Configured exclusions ignore case
+        assert_eq!(exclusion("Secrets.YAML", false), Some(Exclusion::File));
+
+        let without_gitignore = IgnoreMatcher::new(&Config {
+            use_gitignore: false,
+            ..config.clone()
+        });
+        assert!(!without_gitignore.is_excluded(Path::new("src/generated.rs"), false));
+        assert!(!without_gitignore.is_excluded(Path::new("app.csv"), false));
+        assert!(!without_gitignore.is_excluded(Path::new("gen/main.rs"), false));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
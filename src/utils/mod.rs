@@ -1,4 +1,7 @@
 pub mod file_utils;
+pub mod git_history;
+pub mod ignore_matcher;
+pub mod project_fingerprint;
 pub mod project_structure_formatter;
 pub mod sources;
 pub mod threads;
@@ -0,0 +1,139 @@
+use md5::{Digest, Md5};
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::config::{BudgetConfig, CacheConfig, Config};
+use crate::utils::file_utils::is_binary_file_path;
+use crate::utils::ignore_matcher::IgnoreMatcher;
+
+/// Content fingerprint of a project, maps relative file paths to the MD5 of their content
+#[derive(Debug, Clone, Default)]
+pub struct ProjectFingerprint {
+    pub files: BTreeMap<PathBuf, String>,
+}
+
+impl ProjectFingerprint {
+    /// Scan the project and hash the content of every file the analysis reads, excluded with the same rules
+    /// as the structure scanner
+    pub fn scan(config: &Config) -> Self {
+        let root = &config.project_path;
+        let ignore_matcher = IgnoreMatcher::new(config);
+        let mut files = BTreeMap::new();
+
+        let walker = WalkDir::new(root)
+            .max_depth(config.max_depth as usize + 1)
+            .into_iter()
+            .filter_entry(|entry| {
+                entry.depth() == 0
+                    || !entry.file_type().is_dir()
+                    || !(Self::is_generated_dir(config, relative(root, entry.path()))
+                        || ignore_matcher.is_excluded(relative(root, entry.path()), true))
+            });
+
+        for entry in walker.flatten() {
+            if !entry.file_type().is_file() || is_binary_file_path(entry.path()) {
+                continue;
+            }
+            let relative_path = relative(root, entry.path());
+            if ignore_matcher.is_excluded(relative_path, false) {
+                continue;
+            }
+            if let Ok(content) = std::fs::read(entry.path()) {
+                files.insert(relative_path.to_path_buf(), hash_bytes(&content));
+            }
+        }
+
+        Self { files }
+    }
+
+    /// Digest over all file paths and content hashes
+    pub fn digest(&self) -> String {
+        let mut hasher = Md5::new();
+        for (path, hash) in &self.files {
+            hasher.update(path.to_string_lossy().as_bytes());
+            hasher.update(b"\0");
+            hasher.update(hash.as_bytes());
+            hasher.update(b"\n");
+        }
+        format!("{:x}", hasher.finalize())
+    }
+
+    /// Whether a path relative to the project root is Litho's own working or output directory
+    fn is_generated_dir(config: &Config, relative_path: &Path) -> bool {
+        let relative_path = normalized(relative_path);
+        [&config.internal_path, &config.output_path]
+            .into_iter()
+            .any(|dir| normalized(relative(&config.project_path, dir)) == relative_path)
+    }
+}
+
+fn relative<'a>(root: &Path, path: &'a Path) -> &'a Path {
+    path.strip_prefix(root).unwrap_or(path)
+}
+
+/// Path without `.` components, so `./.litho` and `.litho` compare equal
+fn normalized(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| !matches!(component, Component::CurDir))
+        .collect()
+}
+
+/// MD5 hex digest of raw bytes
+pub fn hash_bytes(bytes: &[u8]) -> String {
+    let mut hasher = Md5::new();
+    hasher.update(bytes);
+    format!("{:x}", hasher.finalize())
+}
+
+/// Hash of the configuration fields that influence generated results
+pub fn config_digest(config: &Config) -> String {
+    let mut normalized = config.clone();
+    // Run control flags, credentials, the budget, fixtures, prices, rate limits and the cache do not change what gets generated
+    normalized.skip_preprocessing = false;
+    normalized.skip_research = false;
+    normalized.skip_documentation = false;
+    normalized.incremental = false;
+    normalized.llm.api_key = String::new();
+    normalized.llm.budget = BudgetConfig::default();
+    normalized.llm.fixtures = None;
+    normalized.llm.pricing.clear();
+    normalized.llm.rate_limits.clear();
+    normalized.cache = CacheConfig::default();
+    for route in normalized.llm.routing.routes.values_mut() {
+        route.api_key = None;
+    }
+    for backend in &mut normalized.llm.fallback {
+        backend.api_key = None;
+    }
+    let serialized = serde_json::to_string(&normalized).unwrap_or_default();
+    hash_bytes(serialized.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_dirs_are_not_fingerprinted() {
+        let root = std::env::temp_dir().join(format!("litho-fingerprint-{}", uuid::Uuid::new_v4()));
+        for path in ["src/main.rs", "docs/overview.rs", "work/state.rs"] {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "content").unwrap();
+        }
+        let config = Config {
+            project_path: root.clone(),
+            output_path: PathBuf::from("./docs"),
+            internal_path: root.join("work"),
+            ..Config::default()
+        };
+        let fingerprint = ProjectFingerprint::scan(&config);
+        assert_eq!(
+            fingerprint.files.keys().collect::<Vec<_>>(),
+            vec![&PathBuf::from("src/main.rs")]
+        );
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -100,10 +100,14 @@ impl PromptCompressor {
             return Ok(self.create_no_compression_result(content));
         }
 
-        // Check cache
+        // Check cache, compressions of another model or provider are not reused
+        let identity = context
+            .llm_client
+            .for_agent("PromptCompressor", &format!("prompt_compression_{}", content_type))
+            .cache_identity("", "", false);
         let cache_manager = context.cache_manager.read().await;
         if let Ok(Some(cached_result)) = cache_manager
-            .get_compression_cache(content, content_type)
+            .get_compression_cache(content, content_type, &identity)
             .await
         {
             let msg = context.config.target_language.msg_cache_compression_hit().replace("{}", content_type);
@@ -141,7 +145,12 @@ impl PromptCompressor {
         if result.was_compressed {
             let cache_manager = context.cache_manager.write().await;
             let _ = cache_manager
-                .set_compression_cache(content, content_type, result.compressed_content.clone())
+                .set_compression_cache(
+                    content,
+                    content_type,
+                    &identity,
+                    result.compressed_content.clone(),
+                )
                 .await;
         }
 
@@ -170,7 +179,9 @@ impl PromptCompressor {
                     .to_string(),
             prompt_user: compression_prompt,
             cache_scope: format!("prompt_compression_{}", content_type),
+            agent: "PromptCompressor".to_string(),
             log_tag: format!("Context-Compression-{}", content_type),
+            template_version: None,
         };
 
         // Check if content is already too large for compression